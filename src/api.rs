@@ -0,0 +1,319 @@
+use std::{cell::RefCell, rc::Rc, str::FromStr};
+
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    find_ingredients, Effect, Ingredient, IngredientRecord, Potion, PotionQuery, PotionRecord,
+    Ruleset,
+};
+
+/// Largest request body that will be read, far more than any query needs
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+
+/// A response from the API, always with a JSON body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn ok<T: Serialize>(value: &T) -> ApiResponse {
+        match serde_json::to_string(value) {
+            Ok(body) => ApiResponse { status: 200, body },
+            Err(error) => {
+                ApiResponse::error(500, format!("Unable to serialize response: {}", error))
+            }
+        }
+    }
+
+    fn error(status: u16, message: String) -> ApiResponse {
+        ApiResponse {
+            status,
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// Body of `POST /potions`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PotionsRequest {
+    effects: Vec<String>,
+    #[serde(default)]
+    excluded_effects: Vec<String>,
+    #[serde(default)]
+    allow_extra_effects: bool,
+    /// Defaults to the most the game allows
+    #[serde(default)]
+    max_ingredients: Option<usize>,
+    /// Only use these ingredients, by name or ID, rather than every loaded ingredient
+    #[serde(default)]
+    ingredients: Vec<String>,
+}
+
+/// Body of `POST /mix`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MixRequest {
+    ingredients: Vec<String>,
+}
+
+/// Answers JSON requests about a set of ingredients
+///
+/// | Request            | Response                                                   |
+/// | ------------------ | ---------------------------------------------------------- |
+/// | `GET /effects`     | Every effect name in the game                              |
+/// | `GET /ingredients` | Every loaded ingredient                                    |
+/// | `POST /potions`    | The potions found by a query, e.g. `{"effects": ["Restore Health"], "allow_extra_effects": true}` |
+/// | `POST /mix`        | The potion made from exactly these ingredients, e.g. `{"ingredients": ["ash_yam", "Daedra Skin"]}` |
+///
+/// Errors are reported as `{"error": "..."}` with a 4xx status
+/// The same requests are available directly, without the routing, for the JavaScript API
+#[derive(Debug, Clone)]
+pub struct Api {
+    ruleset: Ruleset,
+    ingredients: Vec<Rc<RefCell<Ingredient>>>,
+}
+
+impl Api {
+    /// Answer requests about Morrowind ingredients
+    pub fn new(ingredients: Vec<Rc<RefCell<Ingredient>>>) -> Api {
+        Self::with_ruleset(Ruleset::Morrowind, ingredients)
+    }
+
+    pub fn with_ruleset(ruleset: Ruleset, ingredients: Vec<Rc<RefCell<Ingredient>>>) -> Api {
+        Api {
+            ruleset,
+            ingredients,
+        }
+    }
+
+    /// Route a request, without any networking
+    pub fn handle(&self, method: &str, url: &str, body: &str) -> ApiResponse {
+        // Query strings aren't used by any endpoint
+        let path = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+        match (method, path) {
+            ("GET", "/effects") => ApiResponse::ok(&self.effects()),
+            ("GET", "/ingredients") => ApiResponse::ok(&self.ingredients()),
+            ("POST", "/potions") => match self.potions(body) {
+                Ok(potions) => ApiResponse::ok(&potions),
+                Err(error) => ApiResponse::error(400, error),
+            },
+            ("POST", "/mix") => match self.mix(body) {
+                Ok(potion) => ApiResponse::ok(&potion),
+                Err(error) => ApiResponse::error(400, error),
+            },
+            (_, "/effects" | "/ingredients" | "/potions" | "/mix") => {
+                ApiResponse::error(405, format!("{} is not supported for {}", method, path))
+            }
+            _ => ApiResponse::error(404, format!("Unknown endpoint: {}", path)),
+        }
+    }
+
+    pub fn effects(&self) -> Vec<String> {
+        self.ruleset
+            .effects()
+            .iter()
+            .map(|effect| effect.to_string())
+            .collect()
+    }
+
+    pub fn ingredients(&self) -> Vec<IngredientRecord> {
+        self.ingredients
+            .iter()
+            .filter_map(|ingredient| ingredient.try_borrow().ok())
+            .map(|ingredient| IngredientRecord::from_ingredient(&ingredient))
+            .collect()
+    }
+
+    /// Run the query in a `POST /potions` body
+    pub fn potions(&self, body: &str) -> Result<Vec<PotionRecord>, String> {
+        let request: PotionsRequest = serde_json::from_str(body)
+            .map_err(|error| format!("Invalid potions request: {}", error))?;
+        if request.effects.is_empty() {
+            return Err("At least one effect is needed".to_string());
+        }
+        let max_ingredients = request
+            .max_ingredients
+            .unwrap_or_else(|| self.ruleset.max_ingredients());
+        if !(2..=self.ruleset.max_ingredients()).contains(&max_ingredients) {
+            return Err(format!(
+                "max_ingredients must be from 2 to {}, not {}",
+                self.ruleset.max_ingredients(),
+                max_ingredients
+            ));
+        }
+
+        let query = PotionQuery {
+            desired_effects: Self::parse_effects(&request.effects)?,
+            excluded_effects: Self::parse_effects(&request.excluded_effects)?,
+            allow_extra_effects: request.allow_extra_effects,
+            max_ingredients,
+            ..Default::default()
+        };
+        let potions = if request.ingredients.is_empty() {
+            query.search(&self.ingredients)
+        } else {
+            query.search(&find_ingredients(&self.ingredients, &request.ingredients)?)
+        };
+
+        Ok(potions.iter().map(PotionRecord::from_potion).collect())
+    }
+
+    /// Mix the ingredients in a `POST /mix` body
+    pub fn mix(&self, body: &str) -> Result<PotionRecord, String> {
+        let request: MixRequest = serde_json::from_str(body)
+            .map_err(|error| format!("Invalid mix request: {}", error))?;
+        if !(2..=self.ruleset.max_ingredients()).contains(&request.ingredients.len()) {
+            return Err(format!(
+                "A potion needs from 2 to {} ingredients, not {}",
+                self.ruleset.max_ingredients(),
+                request.ingredients.len()
+            ));
+        }
+
+        let ingredients = find_ingredients(&self.ingredients, &request.ingredients)?;
+        for (index, ingredient) in ingredients.iter().enumerate() {
+            if ingredients[..index]
+                .iter()
+                .any(|other_ingredient| Rc::ptr_eq(other_ingredient, ingredient))
+            {
+                return Err(format!(
+                    "{} is used more than once",
+                    request.ingredients[index]
+                ));
+            }
+        }
+
+        let ingredients: Vec<&Rc<RefCell<Ingredient>>> = ingredients.iter().collect();
+        Ok(PotionRecord::from_potion(
+            &Potion::new_potion_from_ingredients(&ingredients),
+        ))
+    }
+
+    fn parse_effects(names: &[String]) -> Result<[Option<Effect>; 4], String> {
+        if names.len() > 4 {
+            return Err(format!(
+                "At most 4 effects can be given, not {}",
+                names.len()
+            ));
+        }
+
+        let mut effects = [None; 4];
+        for (effect, name) in effects.iter_mut().zip(names) {
+            *effect = Some(Effect::from_str(name)?);
+        }
+
+        Ok(effects)
+    }
+}
+
+/// Serves an `Api` over HTTP on localhost only
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub struct ApiServer {
+    api: Api,
+    server: tiny_http::Server,
+    /// The one web page origin allowed to call the API from a browser, e.g. "http://localhost:3000"
+    /// Without one, browsers keep other pages from reading the answers
+    allowed_origin: Option<String>,
+}
+
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+impl ApiServer {
+    /// Listen on 127.0.0.1, using any free port if `port` is 0
+    /// Only `allowed_origin`, if given, may call the API from a page in a browser
+    pub fn bind(api: Api, port: u16, allowed_origin: Option<String>) -> Result<ApiServer, String> {
+        if let Some(origin) = &allowed_origin {
+            if tiny_http::Header::from_bytes("Access-Control-Allow-Origin", origin.as_str())
+                .is_err()
+                || origin.chars().any(|character| character.is_whitespace())
+            {
+                return Err(format!("Not a valid origin: {}", origin));
+            }
+        }
+        let server = tiny_http::Server::http(("127.0.0.1", port))
+            .map_err(|error| format!("Unable to listen on port {}: {}", port, error))?;
+
+        Ok(ApiServer {
+            api,
+            server,
+            allowed_origin,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server
+            .server_addr()
+            .to_ip()
+            .map(|address| address.port())
+            .unwrap_or(0)
+    }
+
+    /// Answer requests until the process is stopped, or until receiving a request fails, which
+    /// would keep failing and so is returned
+    /// A response that can't be sent, e.g. because the client went away, only affects that request
+    pub fn run(&self) -> Result<(), String> {
+        loop {
+            let request = self.receive()?;
+            // Nothing can be done for a client that isn't listening anymore
+            let _ = self.answer(request);
+        }
+    }
+
+    /// Wait for one request and answer it
+    pub fn handle_next(&self) -> Result<(), String> {
+        self.answer(self.receive()?)
+    }
+
+    fn receive(&self) -> Result<tiny_http::Request, String> {
+        self.server
+            .recv()
+            .map_err(|error| format!("Unable to receive request: {}", error))
+    }
+
+    fn answer(&self, mut request: tiny_http::Request) -> Result<(), String> {
+        let method = request.method().to_string().to_uppercase();
+        let response = if method == "OPTIONS" {
+            // CORS preflight from a dashboard served on another port
+            ApiResponse {
+                status: 204,
+                body: String::new(),
+            }
+        } else {
+            let mut body = String::new();
+            match request
+                .as_reader()
+                .take(MAX_BODY_LENGTH)
+                .read_to_string(&mut body)
+            {
+                Ok(_) => self.api.handle(&method, request.url(), &body),
+                Err(error) => ApiResponse::error(400, format!("Unable to read request: {}", error)),
+            }
+        };
+
+        let mut headers = vec![("Content-Type", "application/json")];
+        if let Some(origin) = &self.allowed_origin {
+            headers.extend([
+                ("Access-Control-Allow-Origin", origin.as_str()),
+                ("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+                ("Access-Control-Allow-Headers", "Content-Type"),
+            ]);
+        }
+        let mut http_response =
+            tiny_http::Response::from_string(response.body).with_status_code(response.status);
+        for (field, value) in headers {
+            if let Ok(header) = tiny_http::Header::from_bytes(field, value) {
+                http_response.add_header(header);
+            }
+        }
+
+        request
+            .respond(http_response)
+            .map_err(|error| format!("Unable to send response: {}", error))
+    }
+}
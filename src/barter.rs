@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Character, Potion};
+
+/// The fatigue term of a fully rested trader, fFatigueBase, which is assumed for both sides
+const FATIGUE_TERM: f32 = 1.25;
+/// Highest Mercantile that still changes prices
+const MAX_MERCANTILE: f32 = 100.0;
+/// Most that Luck or Personality can each change prices by, as Luck counts a tenth and
+/// Personality a fifth
+const MAX_ATTRIBUTE_TERM: f32 = 10.0;
+
+/// The merchant's side of a trade, which along with the character's Mercantile, Personality and
+/// Luck decides what the merchant charges and pays
+///
+/// Prices follow the Morrowind barter formula, without haggling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Barter {
+    /// How much the merchant likes the character, from 0 to 100
+    pub disposition: u16,
+    pub merchant_mercantile: u16,
+    pub merchant_personality: u16,
+    pub merchant_luck: u16,
+}
+
+impl Default for Barter {
+    /// A typical merchant who neither likes nor dislikes the character
+    fn default() -> Self {
+        Barter {
+            disposition: 50,
+            merchant_mercantile: 30,
+            merchant_personality: 40,
+            merchant_luck: 40,
+        }
+    }
+}
+
+impl Barter {
+    /// What the merchant charges for an item worth `base_price`
+    pub fn buy_price(&self, character: &Character, base_price: u32) -> u32 {
+        let (player_term, merchant_term) = self.terms(character);
+        Self::offer(
+            base_price,
+            0.01 * (100.0 - 0.5 * (player_term - merchant_term)),
+        )
+    }
+
+    /// What the merchant pays for an item worth `base_price`
+    pub fn sell_price(&self, character: &Character, base_price: u32) -> u32 {
+        let (player_term, merchant_term) = self.terms(character);
+        Self::offer(
+            base_price,
+            0.01 * (50.0 - 0.5 * (merchant_term - player_term)),
+        )
+    }
+
+    /// What buying one of each ingredient of `potion` costs, as each one is priced separately
+    pub fn recipe_cost(&self, character: &Character, potion: &Potion) -> u32 {
+        potion
+            .ingredients
+            .iter()
+            .flatten()
+            .filter_map(|ingredient| ingredient.try_borrow().ok())
+            .map(|ingredient| self.buy_price(character, u32::from(ingredient.value)))
+            .sum()
+    }
+
+    /// How strongly the character and the merchant each push the price their way
+    fn terms(&self, character: &Character) -> (f32, f32) {
+        let trader_term = |mercantile: u16, personality: u16, luck: u16| {
+            f32::from(mercantile).min(MAX_MERCANTILE)
+                + (0.1 * f32::from(luck)).min(MAX_ATTRIBUTE_TERM)
+                + (0.2 * f32::from(personality)).min(MAX_ATTRIBUTE_TERM)
+        };
+        let disposition = f32::from(self.disposition.min(100)) - 50.0;
+        let player_term = (disposition
+            + trader_term(character.mercantile, character.personality, character.luck))
+            * FATIGUE_TERM;
+        let merchant_term = trader_term(
+            self.merchant_mercantile,
+            self.merchant_personality,
+            self.merchant_luck,
+        ) * FATIGUE_TERM;
+
+        (player_term, merchant_term)
+    }
+
+    /// Items with no value stay worthless, and anything else is worth at least one gold
+    fn offer(base_price: u32, multiplier: f32) -> u32 {
+        if base_price == 0 {
+            return 0;
+        }
+        ((base_price as f32 * multiplier) as u32).max(1)
+    }
+}
@@ -0,0 +1,218 @@
+use std::{cell::RefCell, cmp::Reverse, collections::BTreeMap, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+
+use super::{Barter, Character, Effect, Engine, Ingredient, Potion};
+
+/// Lightest weight a recipe counts as when ranking, so weightless ones don't look infinitely good
+const MIN_RECIPE_WEIGHT: f32 = 0.01;
+/// Slack in the weight limit, so adding up weights in floating point never rules out a last potion
+const WEIGHT_TOLERANCE: f32 = 0.001;
+
+/// What a batch of potions should have the most of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BatchObjective {
+    /// Gold from selling every potion
+    #[default]
+    SaleValue,
+    /// Potions with this effect, whatever else they do
+    EffectCount(Effect),
+}
+
+impl Display for BatchObjective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchObjective::SaleValue => f.write_str("Sale Value"),
+            BatchObjective::EffectCount(effect) => write!(f, "{} Potions", effect),
+        }
+    }
+}
+
+/// Everything needed to plan brewing a whole bag of ingredients at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchQuery {
+    pub engine: Engine,
+    pub character: Character,
+    /// The merchant the potions are sold to
+    pub barter: Barter,
+    /// Count of each available ingredient, by ingredient ID
+    pub stock: BTreeMap<String, u32>,
+    pub objective: BatchObjective,
+    /// Most combined weight of the ingredients to brew, if there's a limit
+    pub max_weight: Option<f32>,
+    pub max_ingredients: usize,
+}
+
+/// Potions of one recipe in a batch
+#[derive(Debug, Clone)]
+pub struct BatchBrew {
+    pub recipe: Potion,
+    pub count: u32,
+    /// What the merchant pays for each potion
+    pub price: u32,
+}
+
+/// How many of which recipes to brew
+#[derive(Debug, Clone)]
+pub struct BatchPlan {
+    /// Best recipes first
+    pub brews: Vec<BatchBrew>,
+    pub potions: u32,
+    /// What the merchant pays for all of the potions
+    pub total_value: u32,
+    /// Combined weight of the ingredients brewed
+    pub weight: f32,
+    /// Ingredients left over, by ingredient ID
+    pub leftover: BTreeMap<String, u32>,
+}
+
+/// A recipe that could be brewed from the stock, with what brewing it once uses and earns
+struct Candidate {
+    recipe: Potion,
+    ingredient_ids: Vec<String>,
+    weight: f32,
+    price: u32,
+    score: u32,
+}
+
+impl BatchQuery {
+    /// Plan to brew the stock of `ingredients`, assuming every brew succeeds
+    ///
+    /// Potions are brewed one at a time from the recipe that earns most for the ingredients, or
+    /// the weight, it uses, and of equally good recipes the one whose ingredients are most
+    /// plentiful, which leaves the most recipes for later
+    /// This makes a good plan quickly, though not always the very best one
+    pub fn plan(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> BatchPlan {
+        let stocked_ingredients: Vec<&Rc<RefCell<Ingredient>>> = ingredients
+            .iter()
+            .filter(|ingredient| {
+                ingredient.try_borrow().is_ok_and(|ingredient| {
+                    self.stock.get(&ingredient.id()).copied().unwrap_or(0) > 0
+                })
+            })
+            .collect();
+
+        let mut candidates: Vec<Candidate> = (2..=self.max_ingredients.clamp(2, 4))
+            .flat_map(|size| stocked_ingredients.iter().copied().combinations(size))
+            .filter_map(|recipe_ingredients| self.candidate(&recipe_ingredients))
+            .collect();
+        let density = |candidate: &Candidate| match self.max_weight {
+            Some(_) => candidate.score as f32 / candidate.weight.max(MIN_RECIPE_WEIGHT),
+            None => candidate.score as f32 / candidate.ingredient_ids.len() as f32,
+        };
+        // A stable sort, so equally good recipes stay in the order of their ingredients' names
+        candidates.sort_by(|candidate_1, candidate_2| {
+            density(candidate_2).total_cmp(&density(candidate_1))
+        });
+        let densities: Vec<f32> = candidates.iter().map(density).collect();
+
+        let mut stock = self.stock.clone();
+        let mut weight = 0.0;
+        let mut counts = vec![0; candidates.len()];
+        loop {
+            let brewable = |candidate: &Candidate| {
+                candidate
+                    .ingredient_ids
+                    .iter()
+                    .all(|id| stock.get(id).copied().unwrap_or(0) > 0)
+                    && self.max_weight.is_none_or(|max_weight| {
+                        weight + candidate.weight <= max_weight + WEIGHT_TOLERANCE
+                    })
+            };
+            // How many of the recipe's ingredients are left altogether
+            let plenty = |candidate: &Candidate| -> u32 {
+                candidate
+                    .ingredient_ids
+                    .iter()
+                    .map(|id| stock.get(id).copied().unwrap_or(0))
+                    .sum()
+            };
+
+            let mut next = None;
+            let mut group_start = 0;
+            while next.is_none() && group_start < candidates.len() {
+                let group_end = densities[group_start..]
+                    .iter()
+                    .position(|density| *density != densities[group_start])
+                    .map_or(candidates.len(), |length| group_start + length);
+                next = (group_start..group_end)
+                    .filter(|index| brewable(&candidates[*index]))
+                    .max_by_key(|index| (plenty(&candidates[*index]), Reverse(*index)));
+                group_start = group_end;
+            }
+            let Some(index) = next else {
+                break;
+            };
+
+            for id in candidates[index].ingredient_ids.iter() {
+                if let Some(stocked) = stock.get_mut(id) {
+                    *stocked -= 1;
+                }
+            }
+            weight += candidates[index].weight;
+            counts[index] += 1;
+        }
+
+        let brews: Vec<BatchBrew> = candidates
+            .into_iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(candidate, count)| BatchBrew {
+                recipe: candidate.recipe,
+                count,
+                price: candidate.price,
+            })
+            .collect();
+        stock.retain(|_, count| *count > 0);
+        BatchPlan {
+            potions: brews.iter().map(|brew| brew.count).sum(),
+            total_value: brews.iter().map(|brew| brew.price * brew.count).sum(),
+            weight,
+            brews,
+            leftover: stock,
+        }
+    }
+
+    /// The recipe these ingredients make, if it counts towards the objective and every ingredient
+    /// adds to it
+    fn candidate(&self, recipe_ingredients: &[&Rc<RefCell<Ingredient>>]) -> Option<Candidate> {
+        let recipe = Potion::new_potion_from_ingredients(recipe_ingredients);
+        let mut ingredient_ids = Vec::new();
+        let mut weight = 0.0;
+        for ingredient in recipe_ingredients {
+            let ingredient = ingredient.try_borrow().ok()?;
+            // An ingredient that shares no effect is wasted, as the recipe brews the same without it
+            if !ingredient
+                .effects
+                .iter()
+                .flatten()
+                .any(|effect| recipe.effects.contains(effect))
+            {
+                return None;
+            }
+            ingredient_ids.push(ingredient.id());
+            weight += ingredient.weight;
+        }
+
+        let brewed_potion = self.engine.brew(&recipe, &self.character);
+        if brewed_potion.effects.is_empty() {
+            return None;
+        }
+        let price = self.barter.sell_price(&self.character, brewed_potion.value);
+        let score = match self.objective {
+            BatchObjective::SaleValue => price,
+            BatchObjective::EffectCount(effect) => brewed_potion
+                .effects
+                .iter()
+                .any(|brewed_effect| brewed_effect.effect == effect)
+                .into(),
+        };
+        (score > 0).then_some(Candidate {
+            recipe,
+            ingredient_ids,
+            weight,
+            price,
+            score,
+        })
+    }
+}
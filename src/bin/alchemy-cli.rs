@@ -0,0 +1,28 @@
+//! Headless commands and the local JSON API, for scripts and other tools
+//! This is a console program everywhere, unlike the GUI which has no console on Windows
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use morrowind_alchemy::cli::{self, Output};
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::run(&args) {
+        Ok(Output::Text(text)) => print!("{}", text),
+        #[cfg(feature = "server")]
+        Ok(Output::Serve(server)) => {
+            println!("Answering requests on http://127.0.0.1:{}", server.port());
+            if let Err(error) = server.run() {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    }
+}
+
+// The commands read files and listen on a port, so there's nothing to run on the web
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,161 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+/// The grades of alchemy apparatus, from worst to best
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, EnumIter,
+)]
+pub enum ApparatusQuality {
+    Apprentice,
+    Journeyman,
+    Master,
+    Grandmaster,
+    SecretMaster,
+}
+
+impl ApparatusQuality {
+    /// The quality value the game uses in its alchemy formulas
+    pub fn quality(&self) -> f32 {
+        match self {
+            ApparatusQuality::Apprentice => 0.5,
+            ApparatusQuality::Journeyman => 1.0,
+            ApparatusQuality::Master => 1.2,
+            ApparatusQuality::Grandmaster => 1.5,
+            ApparatusQuality::SecretMaster => 2.0,
+        }
+    }
+}
+
+impl Display for ApparatusQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApparatusQuality::Apprentice => f.write_str("Apprentice"),
+            ApparatusQuality::Journeyman => f.write_str("Journeyman"),
+            ApparatusQuality::Master => f.write_str("Master"),
+            ApparatusQuality::Grandmaster => f.write_str("Grandmaster"),
+            ApparatusQuality::SecretMaster => f.write_str("Secret Master"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum ApparatusType {
+    MortarAndPestle,
+    Alembic,
+    Calcinator,
+    Retort,
+}
+
+impl ApparatusType {
+    /// Recognise a piece of apparatus from its editor ID, e.g. "apparatus_j_mortar_01" is a
+    /// Journeyman's Mortar and Pestle
+    pub fn from_editor_id(editor_id: &str) -> Option<(ApparatusType, ApparatusQuality)> {
+        let editor_id = editor_id.to_lowercase();
+        let mut parts = editor_id.strip_prefix("apparatus_")?.split('_');
+        let quality = match parts.next()? {
+            "a" => ApparatusQuality::Apprentice,
+            "j" => ApparatusQuality::Journeyman,
+            "m" => ApparatusQuality::Master,
+            "g" => ApparatusQuality::Grandmaster,
+            "sm" => ApparatusQuality::SecretMaster,
+            _ => return None,
+        };
+        let apparatus_type = match parts.next()? {
+            "mortar" => ApparatusType::MortarAndPestle,
+            "alembic" => ApparatusType::Alembic,
+            "calcinator" => ApparatusType::Calcinator,
+            "retort" => ApparatusType::Retort,
+            _ => return None,
+        };
+
+        Some((apparatus_type, quality))
+    }
+}
+
+impl Display for ApparatusType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApparatusType::MortarAndPestle => f.write_str("Mortar and Pestle"),
+            ApparatusType::Alembic => f.write_str("Alembic"),
+            ApparatusType::Calcinator => f.write_str("Calcinator"),
+            ApparatusType::Retort => f.write_str("Retort"),
+        }
+    }
+}
+
+/// The best apparatus of each type the character has, if any
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Apparatus {
+    pub mortar_and_pestle: Option<ApparatusQuality>,
+    pub alembic: Option<ApparatusQuality>,
+    pub calcinator: Option<ApparatusQuality>,
+    pub retort: Option<ApparatusQuality>,
+}
+
+impl Apparatus {
+    pub fn get(&self, apparatus_type: ApparatusType) -> Option<ApparatusQuality> {
+        match apparatus_type {
+            ApparatusType::MortarAndPestle => self.mortar_and_pestle,
+            ApparatusType::Alembic => self.alembic,
+            ApparatusType::Calcinator => self.calcinator,
+            ApparatusType::Retort => self.retort,
+        }
+    }
+
+    pub fn get_mut(&mut self, apparatus_type: ApparatusType) -> &mut Option<ApparatusQuality> {
+        match apparatus_type {
+            ApparatusType::MortarAndPestle => &mut self.mortar_and_pestle,
+            ApparatusType::Alembic => &mut self.alembic,
+            ApparatusType::Calcinator => &mut self.calcinator,
+            ApparatusType::Retort => &mut self.retort,
+        }
+    }
+
+    /// Keep whichever is better of the current apparatus of this type and the new one
+    pub fn add(&mut self, apparatus_type: ApparatusType, quality: ApparatusQuality) {
+        let current = self.get_mut(apparatus_type);
+        *current = (*current).max(Some(quality));
+    }
+}
+
+/// The player stats used by the alchemy and barter formulas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Character {
+    pub level: u16,
+    pub alchemy: u16,
+    pub intelligence: u16,
+    pub luck: u16,
+    pub personality: u16,
+    pub mercantile: u16,
+    pub apparatus: Apparatus,
+}
+
+impl Character {
+    /// Alchemy + Intelligence / 10 + Luck / 10, which Morrowind uses for both the strength of a
+    /// potion and the chance of brewing it
+    pub fn alchemy_factor(&self) -> f32 {
+        f32::from(self.alchemy) + 0.1 * f32::from(self.intelligence) + 0.1 * f32::from(self.luck)
+    }
+}
+
+impl Default for Character {
+    /// A new character who hasn't studied alchemy, with the mortar and pestle needed to brew at all
+    fn default() -> Self {
+        Character {
+            level: 1,
+            alchemy: 5,
+            intelligence: 40,
+            luck: 40,
+            personality: 40,
+            mercantile: 5,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::Apprentice),
+                ..Default::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc, str::FromStr};
+
+use itertools::Itertools;
+
+#[cfg(feature = "server")]
+use super::api::{Api, ApiServer};
+
+use super::{
+    export_ingredients, export_potions, find_ingredients, get_potential_ingredients,
+    load_ingredients, Effect, ExportFormat, Ingredient, Potion, PotionQuery, Ruleset,
+};
+
+pub const USAGE: &str = "Morrowind Alchemy Tool
+
+Usage: alchemy-cli <COMMAND> [OPTIONS]
+
+Commands:
+    find          List the potions that have all of the given effects
+    ingredients   List the ingredients that have any of the given effects, or all ingredients
+    effects       List every effect name in the game
+    serve         Answer the same queries as JSON over HTTP, on localhost only
+    help          Show this message
+
+Options for find and ingredients:
+    --effect <EFFECT>        Desired effect, e.g. \"Restore Health\"; repeat for up to 4 effects
+    --format <FORMAT>        table, json, csv or markdown [default: table]
+
+Options for find only:
+    --exclude <EFFECT>       Leave out potions with this effect; repeat for up to 4 effects
+    --allow-extra            Allow effects other than the desired ones
+    --max-ingredients <N>    Largest number of ingredients in a potion, 2 to 4, or 2 to 3 for
+                             Skyrim [default: the most the game allows]
+    --ingredient <INGREDIENT>
+                             Only use these ingredients, by name or ID; repeat for each one
+
+Options for serve only:
+    --port <PORT>            Port to listen on [default: 8080]
+    --allow-origin <ORIGIN>  Let web pages from this origin call the API, e.g.
+                             http://localhost:3000 [default: none]
+
+Options for every command:
+    --game <GAME>            morrowind, oblivion or skyrim [default: morrowind]
+    --config <PATH>          Config file naming the Morrowind ingredient lists [default: config.yaml]
+
+Examples:
+    find --effect \"Restore Health\" --effect \"Restore Fatigue\" --allow-extra --max-ingredients 3
+    find --game skyrim --effect \"Fortify Smithing\" --effect \"Fortify Enchanting\"
+    serve --port 8080 --allow-origin http://localhost:3000
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Export(ExportFormat),
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Export(ExportFormat::Json)),
+            "csv" => Ok(OutputFormat::Export(ExportFormat::Csv)),
+            "markdown" | "md" => Ok(OutputFormat::Export(ExportFormat::Markdown)),
+            _ => Err(format!(
+                "Unknown format: {} (expected table, json, csv or markdown)",
+                format
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Options {
+    query: PotionQuery,
+    ingredients: Vec<String>,
+    config_path: PathBuf,
+    ruleset: Ruleset,
+    format: OutputFormat,
+    port: u16,
+    allowed_origin: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            query: PotionQuery::default(),
+            ingredients: Vec::new(),
+            config_path: PathBuf::from("config.yaml"),
+            ruleset: Ruleset::default(),
+            format: OutputFormat::Table,
+            port: 8080,
+            allowed_origin: None,
+        };
+
+        let mut max_ingredients_given = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--effect" => {
+                    let effect = Effect::from_str(value()?)?;
+                    Self::push_effect(&mut options.query.desired_effects, effect, arg)?
+                }
+                "--exclude" => {
+                    let effect = Effect::from_str(value()?)?;
+                    Self::push_effect(&mut options.query.excluded_effects, effect, arg)?
+                }
+                "--allow-extra" => options.query.allow_extra_effects = true,
+                "--max-ingredients" => {
+                    max_ingredients_given = true;
+                    let max_ingredients = value()?;
+                    options.query.max_ingredients = match max_ingredients.parse() {
+                        Ok(max_ingredients @ 2..=4) => max_ingredients,
+                        _ => {
+                            return Err(format!(
+                                "--max-ingredients must be 2, 3 or 4, not {}",
+                                max_ingredients
+                            ))
+                        }
+                    }
+                }
+                "--ingredient" => options.ingredients.push(value()?.clone()),
+                "--config" => options.config_path = PathBuf::from(value()?),
+                "--game" => options.ruleset = Ruleset::from_str(value()?)?,
+                "--format" => options.format = OutputFormat::from_str(value()?)?,
+                "--port" => {
+                    let port = value()?;
+                    options.port = port
+                        .parse()
+                        .map_err(|_| format!("--port must be a port number, not {}", port))?
+                }
+                "--allow-origin" => options.allowed_origin = Some(value()?.clone()),
+                _ => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+            }
+        }
+        if options.query.max_ingredients > options.ruleset.max_ingredients() {
+            if max_ingredients_given {
+                return Err(format!(
+                    "{} potions have at most {} ingredients",
+                    options.ruleset,
+                    options.ruleset.max_ingredients()
+                ));
+            }
+            options.query.max_ingredients = options.ruleset.max_ingredients();
+        }
+
+        Ok(options)
+    }
+
+    /// The configured ingredient lists for Morrowind, or the bundled ones for the other games
+    fn load_ingredients(&self) -> Result<Vec<Rc<RefCell<Ingredient>>>, String> {
+        match self.ruleset {
+            Ruleset::Morrowind => load_ingredients(&self.config_path),
+            ruleset => Ok(ruleset.bundled_ingredients()),
+        }
+    }
+
+    fn push_effect(
+        effects: &mut [Option<Effect>; 4],
+        effect: Effect,
+        arg: &str,
+    ) -> Result<(), String> {
+        match effects.iter_mut().find(|effect| effect.is_none()) {
+            Some(empty_effect) => {
+                *empty_effect = Some(effect);
+                Ok(())
+            }
+            None => Err(format!("{} can be given at most 4 times", arg)),
+        }
+    }
+}
+
+/// What a command leaves for the binary running it to do
+pub enum Output {
+    /// Text to print
+    Text(String),
+    /// A server listening on localhost, to announce and then run
+    #[cfg(feature = "server")]
+    Serve(ApiServer),
+}
+
+/// Run a command, returning what to print or the server to run
+/// Nothing is printed here, so the binary decides where output goes
+pub fn run(args: &[String]) -> Result<Output, String> {
+    let Some((command, args)) = args.split_first() else {
+        return Ok(Output::Text(USAGE.to_string()));
+    };
+
+    let text = match command.as_str() {
+        "find" => find(&Options::parse(args)?)?,
+        "ingredients" => list_ingredients(&Options::parse(args)?)?,
+        "effects" => Options::parse(args)?
+            .ruleset
+            .effects()
+            .iter()
+            .map(|effect| format!("{}\n", effect))
+            .collect(),
+        "serve" => return serve(&Options::parse(args)?),
+        "help" | "--help" | "-h" => USAGE.to_string(),
+        _ => return Err(format!("Unknown command: {}\n\n{}", command, USAGE)),
+    };
+
+    Ok(Output::Text(text))
+}
+
+fn find(options: &Options) -> Result<String, String> {
+    if options.query.desired_effects.iter().flatten().count() == 0 {
+        return Err("find needs at least one --effect".to_string());
+    }
+
+    let ingredients = options.load_ingredients()?;
+    let potions = if options.ingredients.is_empty() {
+        options.query.search(&ingredients)
+    } else {
+        options
+            .query
+            .search(&find_ingredients(&ingredients, &options.ingredients)?)
+    };
+
+    match options.format {
+        OutputFormat::Table => Ok(potion_table(&potions)),
+        OutputFormat::Export(format) => export_potions(&potions, format),
+    }
+}
+
+fn list_ingredients(options: &Options) -> Result<String, String> {
+    let ingredients = options.load_ingredients()?;
+    let ingredients = if options.query.desired_effects.iter().flatten().count() == 0 {
+        ingredients
+    } else {
+        get_potential_ingredients(&options.query.desired_effects, &ingredients)
+    };
+
+    match options.format {
+        OutputFormat::Table => Ok(ingredient_table(&ingredients)),
+        OutputFormat::Export(format) => export_ingredients(&ingredients, format),
+    }
+}
+
+fn potion_table(potions: &[Potion]) -> String {
+    let rows: Vec<Vec<String>> = potions
+        .iter()
+        .map(|potion| {
+            vec![
+                potion.ingredient_names().join(", "),
+                potion
+                    .effects
+                    .iter()
+                    .map(|effect| effect.to_string())
+                    .join(", "),
+                potion.total_value().to_string(),
+            ]
+        })
+        .collect();
+
+    format!(
+        "{}{} potions found\n",
+        table(&["Ingredients", "Effects", "Value"], &rows),
+        potions.len()
+    )
+}
+
+fn ingredient_table(ingredients: &[Rc<RefCell<Ingredient>>]) -> String {
+    let rows: Vec<Vec<String>> = ingredients
+        .iter()
+        .filter_map(|ingredient| ingredient.try_borrow().ok())
+        .map(|ingredient| {
+            vec![
+                ingredient.name.clone(),
+                ingredient
+                    .effects
+                    .iter()
+                    .flatten()
+                    .map(|effect| effect.to_string())
+                    .join(", "),
+                ingredient.value.to_string(),
+                ingredient.weight.to_string(),
+            ]
+        })
+        .collect();
+
+    format!(
+        "{}{} ingredients found\n",
+        table(&["Name", "Effects", "Value", "Weight"], &rows),
+        rows.len()
+    )
+}
+
+#[cfg(feature = "server")]
+fn serve(options: &Options) -> Result<Output, String> {
+    let api = Api::with_ruleset(options.ruleset, options.load_ingredients()?);
+    Ok(Output::Serve(ApiServer::bind(
+        api,
+        options.port,
+        options.allowed_origin.clone(),
+    )?))
+}
+
+#[cfg(not(feature = "server"))]
+fn serve(_options: &Options) -> Result<Output, String> {
+    Err("This build does not include the server; build with the server feature".to_string())
+}
+
+/// Lay out rows as left aligned, space separated columns
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain(std::iter::once(header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |fields: Vec<&str>| {
+        let line = fields
+            .iter()
+            .zip(widths.iter())
+            .map(|(field, width)| format!("{:width$}", field, width = width))
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+
+    let dashes: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut table = line(headers.to_vec());
+    table.push_str(&line(dashes.iter().map(|dashes| dashes.as_str()).collect()));
+    for row in rows {
+        table.push_str(&line(row.iter().map(|field| field.as_str()).collect()));
+    }
+
+    table
+}
@@ -0,0 +1,108 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+use strum_macros::EnumIter;
+
+use super::{Barter, Character, Effect, Engine, Ingredient, Potion};
+
+/// Most ingredients a completion adds
+const MAX_ADDITIONS: usize = 2;
+
+/// How to order the ways of completing a potion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, EnumIter)]
+pub enum CompletionRanking {
+    /// Lowest price of buying the ingredients from the merchant
+    #[default]
+    Cost,
+    /// Fewest effects beyond the desired ones, harmful ones counting most
+    SideEffects,
+    /// Highest value of the potion as the character brews it
+    Value,
+}
+
+impl Display for CompletionRanking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompletionRanking::Cost => f.write_str("Cost"),
+            CompletionRanking::SideEffects => f.write_str("Side Effects"),
+            CompletionRanking::Value => f.write_str("Value"),
+        }
+    }
+}
+
+/// A potion with some ingredients already decided, to complete with one or two more
+#[derive(Debug, Clone)]
+pub struct CompletionQuery {
+    /// The ingredients every completion keeps
+    pub locked: Vec<Rc<RefCell<Ingredient>>>,
+    pub desired_effects: [Option<Effect>; 4],
+    pub max_ingredients: usize,
+    pub ranking: CompletionRanking,
+    /// Brews the potions when ranking by value
+    pub engine: Engine,
+    /// Brews the potions, and buys the ingredients when ranking by cost
+    pub character: Character,
+    /// The merchant the ingredients are bought from when ranking by cost
+    pub barter: Barter,
+}
+
+impl CompletionQuery {
+    /// Every potion of the locked ingredients and one or two of `ingredients` that has all of the
+    /// desired effects, best first
+    pub fn complete(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Vec<Potion> {
+        let desired_effects: Vec<Effect> = self.desired_effects.iter().flatten().copied().collect();
+        let additions: Vec<&Rc<RefCell<Ingredient>>> = ingredients
+            .iter()
+            .filter(|ingredient| {
+                !self
+                    .locked
+                    .iter()
+                    .any(|locked| Rc::ptr_eq(locked, ingredient))
+            })
+            .collect();
+        let max_additions = self
+            .max_ingredients
+            .clamp(2, 4)
+            .saturating_sub(self.locked.len())
+            .min(MAX_ADDITIONS);
+
+        (1..=max_additions)
+            .flat_map(|count| additions.iter().copied().combinations(count))
+            .map(|added| {
+                let recipe_ingredients: Vec<&Rc<RefCell<Ingredient>>> =
+                    self.locked.iter().chain(added).collect();
+                Potion::new_potion_from_ingredients(&recipe_ingredients)
+            })
+            .filter(|potion| {
+                potion.ingredients.iter().flatten().count() >= 2
+                    && desired_effects
+                        .iter()
+                        .all(|effect| potion.effects.contains(effect))
+            })
+            .sorted_by_cached_key(|potion| {
+                let rank: (i64, i64) = match self.ranking {
+                    CompletionRanking::Cost => {
+                        (self.barter.recipe_cost(&self.character, potion).into(), 0)
+                    }
+                    CompletionRanking::SideEffects => {
+                        let (harmful, other): (Vec<&Effect>, Vec<&Effect>) = potion
+                            .effects
+                            .iter()
+                            .filter(|effect| !desired_effects.contains(effect))
+                            .partition(|effect| effect.is_harmful());
+                        (harmful.len() as i64, other.len() as i64)
+                    }
+                    CompletionRanking::Value => (
+                        -i64::from(self.engine.brew(potion, &self.character).value),
+                        0,
+                    ),
+                };
+                (
+                    rank,
+                    potion.ingredients.iter().flatten().count(),
+                    potion.recipe_key(),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,99 @@
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use super::{Effect, Ruleset};
+
+/// The query state that is mirrored into the URL fragment of the web build
+/// e.g. "#effects=RestoreHealth,RestoreFatigue&exclude=Poison&extra=1&ingredients=ash_yam,saltrice"
+/// Links to another game than Morrowind start with it, e.g. "#game=skyrim&effects=FortifySmithing"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeepLink {
+    pub ruleset: Ruleset,
+    pub desired_effects: [Option<Effect>; 4],
+    pub excluded_effects: [Option<Effect>; 4],
+    pub allow_extra_effects: bool,
+    pub selected_ingredient_ids: Vec<String>,
+}
+
+impl DeepLink {
+    /// Build the fragment for this link, without the leading '#'
+    /// Empty values are left out so a blank query produces an empty fragment
+    pub fn to_fragment(&self) -> String {
+        let mut parts = Vec::new();
+        if self.ruleset != Ruleset::default() {
+            parts.push(format!("game={}", self.ruleset.key()));
+        }
+        if self.desired_effects.iter().flatten().count() > 0 {
+            parts.push(format!(
+                "effects={}",
+                Self::effects_to_string(&self.desired_effects)
+            ));
+        }
+        if self.excluded_effects.iter().flatten().count() > 0 {
+            parts.push(format!(
+                "exclude={}",
+                Self::effects_to_string(&self.excluded_effects)
+            ));
+        }
+        if self.allow_extra_effects {
+            parts.push("extra=1".to_string());
+        }
+        if !self.selected_ingredient_ids.is_empty() {
+            parts.push(format!(
+                "ingredients={}",
+                self.selected_ingredient_ids.join(",")
+            ));
+        }
+
+        parts.join("&")
+    }
+
+    /// Parse a fragment, with or without the leading '#'
+    /// Unknown keys and effects are ignored so that old or hand-edited links still open
+    pub fn from_fragment(fragment: &str) -> DeepLink {
+        let mut deep_link = DeepLink::default();
+        for part in fragment.trim_start_matches('#').split('&') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key {
+                "game" => deep_link.ruleset = Ruleset::from_str(value).unwrap_or_default(),
+                "effects" => deep_link.desired_effects = Self::effects_from_string(value),
+                "exclude" => deep_link.excluded_effects = Self::effects_from_string(value),
+                "extra" => deep_link.allow_extra_effects = value == "1" || value == "true",
+                "ingredients" => {
+                    deep_link.selected_ingredient_ids = value
+                        .split(',')
+                        .filter(|ingredient_id| !ingredient_id.is_empty())
+                        .map(|ingredient_id| ingredient_id.to_string())
+                        .collect()
+                }
+                _ => continue,
+            }
+        }
+
+        deep_link
+    }
+
+    fn effects_to_string(effects: &[Option<Effect>; 4]) -> String {
+        effects
+            .iter()
+            .flatten()
+            .map(|effect| format!("{:?}", effect))
+            .join(",")
+    }
+
+    fn effects_from_string(value: &str) -> [Option<Effect>; 4] {
+        let mut effects = [None; 4];
+        for (effect, parsed_effect) in effects.iter_mut().zip(
+            value
+                .split(',')
+                .filter_map(|effect| Effect::from_str(effect).ok()),
+        ) {
+            *effect = Some(parsed_effect);
+        }
+
+        effects
+    }
+}
@@ -0,0 +1,124 @@
+use std::{cell::RefCell, rc::Rc};
+
+use itertools::Itertools;
+
+use super::{Effect, Ingredient};
+
+/// How many raw ingredient combinations make each pair of effects, to see at a glance which
+/// multi-effect potions are possible at all
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EffectMatrix {
+    /// Every effect of the ingredients, in the order of the effect dropdowns
+    pub effects: Vec<Effect>,
+    /// By index into `effects`, the same either way round
+    counts: Vec<Vec<u64>>,
+}
+
+impl EffectMatrix {
+    /// Count the raw combinations of up to `max_ingredients` of `ingredients` that make both
+    /// effects of every pair
+    /// A pair of the same effect counts the combinations that make that effect
+    ///
+    /// These are more than `PotionQuery::find_potions` returns for the pair, which leaves out a
+    /// three or four ingredient combination whenever a smaller recipe already has all its effects
+    ///
+    /// Only ingredients with one of the two effects take part, and a combination makes both
+    /// when at least two of its ingredients have each, so the counts are worked out from how
+    /// many ingredients have either or both effects without brewing every combination
+    pub fn new(ingredients: &[Rc<RefCell<Ingredient>>], max_ingredients: usize) -> EffectMatrix {
+        let ingredient_effects: Vec<Vec<Effect>> = ingredients
+            .iter()
+            .filter_map(|ingredient| {
+                let ingredient = ingredient.try_borrow().ok()?;
+                Some(
+                    ingredient
+                        .effects
+                        .iter()
+                        .flatten()
+                        .copied()
+                        .unique()
+                        .collect(),
+                )
+            })
+            .collect();
+        let effects: Vec<Effect> = Effect::effects_list()
+            .into_iter()
+            .filter(|effect| {
+                ingredient_effects
+                    .iter()
+                    .any(|ingredient_effects| ingredient_effects.contains(effect))
+            })
+            .collect();
+
+        // How many ingredients have both effects, which for the same effect twice is how many have it
+        let mut both = vec![vec![0; effects.len()]; effects.len()];
+        for ingredient_effects in ingredient_effects.iter() {
+            let indices: Vec<usize> = ingredient_effects
+                .iter()
+                .filter_map(|effect| effects.iter().position(|other| other == effect))
+                .collect();
+            for &index_1 in indices.iter() {
+                for &index_2 in indices.iter() {
+                    both[index_1][index_2] += 1;
+                }
+            }
+        }
+
+        let max_ingredients = max_ingredients.clamp(2, 4);
+        let counts = (0..effects.len())
+            .map(|index_1| {
+                (0..effects.len())
+                    .map(|index_2| {
+                        let shared = both[index_1][index_2];
+                        combinations(
+                            shared,
+                            both[index_1][index_1] - shared,
+                            both[index_2][index_2] - shared,
+                            max_ingredients,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        EffectMatrix { effects, counts }
+    }
+
+    /// How many combinations make both effects, 0 if no ingredient has one of them
+    pub fn count(&self, effect_1: Effect, effect_2: Effect) -> u64 {
+        let index_1 = self.effects.iter().position(|effect| *effect == effect_1);
+        let index_2 = self.effects.iter().position(|effect| *effect == effect_2);
+        match (index_1, index_2) {
+            (Some(index_1), Some(index_2)) => self.counts[index_1][index_2],
+            _ => 0,
+        }
+    }
+}
+
+/// Combinations of 2 up to `max_ingredients` ingredients, taken from `both` with the two effects,
+/// `first_only` with just the first and `second_only` with just the second, in which at least
+/// two ingredients have each effect
+fn combinations(both: u64, first_only: u64, second_only: u64, max_ingredients: usize) -> u64 {
+    let mut total = 0;
+    for size in 2..=max_ingredients as u64 {
+        for from_both in 0..=size {
+            for from_first in 0..=size - from_both {
+                let from_second = size - from_both - from_first;
+                if from_both + from_first >= 2 && from_both + from_second >= 2 {
+                    total += binomial(both, from_both)
+                        * binomial(first_only, from_first)
+                        * binomial(second_only, from_second);
+                }
+            }
+        }
+    }
+    total
+}
+
+/// Ways to choose `k` of `n`
+fn binomial(n: u64, k: u64) -> u64 {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |total, index| total * (n - index) / (index + 1))
+}
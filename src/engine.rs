@@ -0,0 +1,410 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::{Apparatus, Character, Effect, Potion, Ruleset};
+
+/// fPotionStrengthMult: share of the alchemy factor that goes into a potion
+pub(crate) const POTION_STRENGTH_MULT: f32 = 0.5;
+/// iAlchemyMod: gold a potion is worth per point of strength
+const ALCHEMY_MOD: f32 = 2.0;
+/// fPotionT1MagMult: strength needed per point of magnitude, before the effect's base cost
+const POTION_MAGNITUDE_MULT: f32 = 1.5;
+/// fPotionT1DurMult: strength needed per second of duration, before the effect's base cost
+const POTION_DURATION_MULT: f32 = 0.5;
+/// fEffectCostMult: scales the cost of an effect, as for spells and enchantments
+const EFFECT_COST_MULT: f32 = 0.5;
+
+/// The program running Morrowind, as each brews slightly different potions from the same stats
+///
+/// Sources: for OpenMW, `Alchemy::updateEffects`, `applyTools` and `createSingle` in
+/// apps/openmw/mwmechanics/alchemy.cpp; for Morrowind.exe, UESP's Morrowind:Alchemy page, from
+/// Hrnchamd's research into the executable; for the Code Patch, its readme's alchemy changes
+///
+/// These are the same for every engine, as OpenMW copies them from Morrowind.exe and the Code
+/// Patch leaves them alone:
+/// - the strength of a potion, from the alchemy factor and the mortar and pestle
+/// - the chance of brewing it, a roll from 0 to 99 against the alchemy factor
+/// - how the alembic, calcinator and retort change beneficial and harmful effects, including an
+///   alembic weakening harmful effects with or without a calcinator
+///
+/// They differ in:
+/// - rounding: OpenMW rounds magnitudes and durations to the nearest whole number (`roundf` in
+///   `updateEffects`), while Morrowind.exe truncates them, so its potions are often a point or a
+///   second weaker
+/// - value: both price a potion by its strength alone (`iAlchemyMod` × strength), so a potion is
+///   worth the same whatever its effects. The Morrowind Code Patch prices it by its effects
+///   instead, like a spell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum Engine {
+    #[default]
+    OpenMw,
+    Original,
+    CodePatch,
+}
+
+/// How strong one effect of a brewed potion is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectStrength {
+    pub effect: Effect,
+    /// Points, or 1 for effects without a magnitude such as Cure Poison
+    pub magnitude: u32,
+    /// Seconds, or 1 for effects without a duration such as Dispel
+    pub duration: u32,
+}
+
+/// What a character gets from brewing a potion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrewedPotion {
+    /// Effects too weak to last a second or to do anything are left out, as in the game
+    pub effects: Vec<EffectStrength>,
+    /// Base value in gold
+    pub value: u32,
+    /// From 0 to 1
+    pub success_chance: f32,
+}
+
+/// The parts of a Morrowind magic effect record used to brew a potion
+struct MagicEffect {
+    base_cost: f32,
+    has_magnitude: bool,
+    has_duration: bool,
+}
+
+impl MagicEffect {
+    const fn new(base_cost: f32) -> MagicEffect {
+        MagicEffect {
+            base_cost,
+            has_magnitude: true,
+            has_duration: true,
+        }
+    }
+
+    /// An effect that either happens or not, e.g. Invisibility
+    const fn without_magnitude(base_cost: f32) -> MagicEffect {
+        MagicEffect {
+            has_magnitude: false,
+            ..MagicEffect::new(base_cost)
+        }
+    }
+
+    /// An effect that happens at once, e.g. Dispel
+    const fn without_duration(base_cost: f32) -> MagicEffect {
+        MagicEffect {
+            has_duration: false,
+            ..MagicEffect::new(base_cost)
+        }
+    }
+
+    /// An effect that happens at once and either happens or not, e.g. Cure Poison
+    const fn instant(base_cost: f32) -> MagicEffect {
+        MagicEffect {
+            has_magnitude: false,
+            has_duration: false,
+            ..MagicEffect::new(base_cost)
+        }
+    }
+
+    /// Strengthen or weaken a magnitude or duration of this effect with the alembic, calcinator
+    /// and retort, following OpenMW's research into the original engine, for every engine
+    ///
+    /// The retort strengthens beneficial effects and the alembic weakens harmful ones. A calcinator
+    /// on its own strengthens every effect, harmful ones included, but alongside an alembic it
+    /// weakens harmful effects even further
+    fn apply_apparatus(&self, value: f32, harmful: bool, apparatus: &Apparatus) -> f32 {
+        let tool = if harmful {
+            apparatus.alembic
+        } else {
+            apparatus.retort
+        };
+        let tool = tool.map(|quality| quality.quality());
+        let calcinator = apparatus.calcinator.map(|quality| quality.quality());
+        // Effects with only a magnitude or only a duration get a little extra from apparatus
+        let has_both = self.has_magnitude && self.has_duration;
+
+        match (tool, calcinator) {
+            (Some(alembic), Some(calcinator)) if harmful => {
+                value / (2.0 * alembic + 3.0 * calcinator)
+            }
+            (Some(retort), Some(calcinator)) if has_both => value + 2.0 * retort + calcinator,
+            (Some(retort), Some(calcinator)) => value + 2.0 / 3.0 * (retort + calcinator) + 0.5,
+            (Some(alembic), None) if harmful => value / (1.0 + alembic),
+            (Some(retort), None) if has_both => value + retort,
+            (Some(retort), None) => value + retort + 0.5,
+            (None, Some(calcinator)) if has_both => value + calcinator,
+            (None, Some(calcinator)) => value + calcinator + 0.5,
+            (None, None) => value,
+        }
+    }
+
+    /// The Morrowind magic effect behind an effect, or None if Morrowind doesn't have it
+    fn morrowind(effect: Effect) -> Option<MagicEffect> {
+        let magic_effect = match effect {
+            Effect::CureBlightDisease => MagicEffect::instant(200.0),
+            Effect::CureCommonDisease => MagicEffect::instant(70.0),
+            Effect::CureParalyzation => MagicEffect::instant(50.0),
+            Effect::CurePoison => MagicEffect::instant(60.0),
+            Effect::Recall => MagicEffect::instant(350.0),
+            Effect::Vampirism => MagicEffect::instant(1000.0),
+            Effect::Dispel => MagicEffect::without_duration(5.0),
+            Effect::Invisibility => MagicEffect::without_magnitude(40.0),
+            Effect::Paralyze => MagicEffect::without_magnitude(40.0),
+            Effect::Silence => MagicEffect::without_magnitude(40.0),
+            Effect::WaterBreathing => MagicEffect::without_magnitude(10.0),
+            Effect::WaterWalking => MagicEffect::without_magnitude(8.0),
+            Effect::Light => MagicEffect::new(0.3),
+            Effect::DetectAnimal => MagicEffect::new(0.75),
+            Effect::ResistParalysis => MagicEffect::new(0.2),
+            Effect::Chameleon
+            | Effect::DetectEnchantment
+            | Effect::DetectKey
+            | Effect::Feather
+            | Effect::FortifyAttack
+            | Effect::FortifyStrength
+            | Effect::FortifyIntelligence
+            | Effect::FortifyWillpower
+            | Effect::FortifyAgility
+            | Effect::FortifySpeed
+            | Effect::FortifyEndurance
+            | Effect::FortifyPersonality
+            | Effect::FortifyLuck
+            | Effect::FortifyHealth
+            | Effect::FortifyMagicka
+            | Effect::FortifyFatigue
+            | Effect::NightEye
+            | Effect::RestoreStrength
+            | Effect::RestoreIntelligence
+            | Effect::RestoreWillpower
+            | Effect::RestoreAgility
+            | Effect::RestoreSpeed
+            | Effect::RestoreEndurance
+            | Effect::RestorePersonality
+            | Effect::RestoreLuck
+            | Effect::RestoreFatigue
+            | Effect::Telekinesis
+            | Effect::Blind
+            | Effect::Burden
+            | Effect::DrainAlteration
+            | Effect::DrainStrength
+            | Effect::DrainIntelligence
+            | Effect::DrainWillpower
+            | Effect::DrainAgility
+            | Effect::DrainSpeed
+            | Effect::DrainEndurance
+            | Effect::DrainPersonality
+            | Effect::DrainLuck
+            | Effect::DrainFatigue => MagicEffect::new(1.0),
+            Effect::ResistCommonDisease
+            | Effect::ResistFire
+            | Effect::ResistFrost
+            | Effect::ResistMagicka
+            | Effect::ResistPoison
+            | Effect::ResistShock
+            | Effect::Shield
+            | Effect::SwiftSwim
+            | Effect::WeaknessToFire
+            | Effect::WeaknessToFrost
+            | Effect::WeaknessToPoison
+            | Effect::WeaknessToShock => MagicEffect::new(2.0),
+            Effect::FireShield | Effect::FrostShield | Effect::LightningShield => {
+                MagicEffect::new(3.0)
+            }
+            Effect::DrainHealth | Effect::DrainMagicka | Effect::DamageFatigue => {
+                MagicEffect::new(4.0)
+            }
+            Effect::RestoreHealth
+            | Effect::FireDamage
+            | Effect::FrostDamage
+            | Effect::ResistNormalWeapons => MagicEffect::new(5.0),
+            Effect::FortifyMaximumMagicka | Effect::ShockDamage => MagicEffect::new(7.0),
+            Effect::DamageStrength
+            | Effect::DamageIntelligence
+            | Effect::DamageWillpower
+            | Effect::DamageAgility
+            | Effect::DamageSpeed
+            | Effect::DamageEndurance
+            | Effect::DamagePersonality
+            | Effect::DamageLuck
+            | Effect::DamageHealth
+            | Effect::DamageMagicka => MagicEffect::new(8.0),
+            Effect::Poison => MagicEffect::new(9.0),
+            Effect::Levitate
+            | Effect::Reflect
+            | Effect::RestoreMagicka
+            | Effect::SpellAbsorption => MagicEffect::new(10.0),
+            _ => return None,
+        };
+
+        Some(magic_effect)
+    }
+}
+
+impl Engine {
+    /// The effects, value and chance of success of brewing this potion
+    /// A character without a mortar and pestle can't brew at all
+    pub fn brew(&self, potion: &Potion, character: &Character) -> BrewedPotion {
+        let strength = self.potion_strength(character);
+        if strength <= 0.0 {
+            return BrewedPotion {
+                effects: Vec::new(),
+                value: 0,
+                success_chance: 0.0,
+            };
+        }
+
+        let effects: Vec<EffectStrength> = potion
+            .effects
+            .iter()
+            .filter_map(|effect| self.effect_strength(*effect, strength, &character.apparatus))
+            .collect();
+        let value = match self {
+            Engine::OpenMw | Engine::Original => (strength * ALCHEMY_MOD) as u32,
+            Engine::CodePatch => effects
+                .iter()
+                .filter_map(|effect_strength| effect_strength.cost())
+                .sum::<f32>()
+                .round() as u32,
+        };
+
+        BrewedPotion {
+            effects,
+            value,
+            success_chance: self.success_chance(character),
+        }
+    }
+
+    /// How strongly this character brews, which is the same for every engine
+    pub fn potion_strength(&self, character: &Character) -> f32 {
+        Ruleset::Morrowind.potion_strength(character)
+    }
+
+    /// Chance from 0 to 1 that a brew succeeds rather than wasting its ingredients
+    /// The game fails a brew when a roll from 0 to 99 comes up above the alchemy factor, which is
+    /// the same for every engine
+    pub fn success_chance(&self, character: &Character) -> f32 {
+        ((character.alchemy_factor().floor() + 1.0) / 100.0).clamp(0.0, 1.0)
+    }
+
+    /// Magnitude and duration of one effect of a potion with this strength, or None if it would
+    /// be too weak to have any effect
+    fn effect_strength(
+        &self,
+        effect: Effect,
+        strength: f32,
+        apparatus: &Apparatus,
+    ) -> Option<EffectStrength> {
+        let magic_effect = MagicEffect::morrowind(effect)?;
+        let magnitude = if magic_effect.has_magnitude {
+            let magnitude = strength / POTION_MAGNITUDE_MULT / magic_effect.base_cost;
+            self.round(magic_effect.apply_apparatus(magnitude, effect.is_harmful(), apparatus))
+        } else {
+            1
+        };
+        let duration = if magic_effect.has_duration {
+            let duration = strength / POTION_DURATION_MULT / magic_effect.base_cost;
+            self.round(magic_effect.apply_apparatus(duration, effect.is_harmful(), apparatus))
+        } else {
+            1
+        };
+        if magnitude == 0 || duration == 0 {
+            return None;
+        }
+
+        Some(EffectStrength {
+            effect,
+            magnitude,
+            duration,
+        })
+    }
+
+    fn round(&self, value: f32) -> u32 {
+        match self {
+            Engine::OpenMw => value.round() as u32,
+            Engine::Original | Engine::CodePatch => value as u32,
+        }
+    }
+
+    /// Short lowercase name used in settings and on the command line, e.g. "openmw"
+    pub fn key(&self) -> &'static str {
+        match self {
+            Engine::OpenMw => "openmw",
+            Engine::Original => "original",
+            Engine::CodePatch => "mcp",
+        }
+    }
+}
+
+impl BrewedPotion {
+    /// Whether `effect` is a harmful side effect too weak to matter: weaker than
+    /// `min_magnitude` points, or left out of the potion altogether
+    /// Harmful effects without a magnitude, such as Paralyze, always matter
+    pub fn is_negligible(&self, effect: Effect, min_magnitude: u32) -> bool {
+        if !effect.is_harmful() {
+            return false;
+        }
+        match self
+            .effects
+            .iter()
+            .find(|effect_strength| effect_strength.effect == effect)
+        {
+            Some(effect_strength) => {
+                MagicEffect::morrowind(effect)
+                    .is_some_and(|magic_effect| magic_effect.has_magnitude)
+                    && effect_strength.magnitude < min_magnitude
+            }
+            None => true,
+        }
+    }
+}
+
+impl EffectStrength {
+    /// What this effect would cost as part of a spell, which the Morrowind Code Patch uses to price
+    /// potions
+    fn cost(&self) -> Option<f32> {
+        let base_cost = MagicEffect::morrowind(self.effect)?.base_cost;
+        let magnitude_cost = 0.1 * base_cost * self.magnitude.max(1) as f32;
+        let area_cost = 0.05 * base_cost;
+        Some((magnitude_cost * (1 + self.duration) as f32 + area_cost) * EFFECT_COST_MULT)
+    }
+}
+
+impl Display for EffectStrength {
+    /// Describe the effect the way the game does, e.g. "Restore Health 5 pts for 12 secs"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.effect)?;
+        if let Some(magic_effect) = MagicEffect::morrowind(self.effect) {
+            if magic_effect.has_magnitude {
+                write!(f, " {} pts", self.magnitude)?;
+            }
+            if magic_effect.has_duration {
+                write!(f, " for {} secs", self.duration)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    /// Parse an engine from its key or name, ignoring case
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "openmw" => Ok(Engine::OpenMw),
+            "original" | "morrowind.exe" => Ok(Engine::Original),
+            "mcp" | "morrowind code patch" => Ok(Engine::CodePatch),
+            _ => Err(format!("Unknown engine: {}", name)),
+        }
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Engine::OpenMw => f.write_str("OpenMW"),
+            Engine::Original => f.write_str("Morrowind.exe"),
+            Engine::CodePatch => f.write_str("Morrowind Code Patch"),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Reading the record based files used by Morrowind: plugins (.esm, .esp) and saves (.ess)
+
+/// Split a plugin or save file into its records
+/// Each record has a 16 byte header: a 4 character name, the length of its data, and 8 unused bytes
+pub(crate) fn read_records(data: &[u8]) -> Result<Vec<(&str, &[u8])>, String> {
+    read_chunks(data, 16, "record")
+}
+
+/// Split a record's data into its subrecords
+/// Each subrecord has an 8 byte header: a 4 character name and the length of its data
+pub(crate) fn read_subrecords(data: &[u8]) -> Result<Vec<(&str, &[u8])>, String> {
+    read_chunks(data, 8, "subrecord")
+}
+
+fn read_chunks<'a>(
+    mut data: &'a [u8],
+    header_length: usize,
+    kind: &str,
+) -> Result<Vec<(&'a str, &'a [u8])>, String> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        if data.len() < header_length {
+            return Err(format!("The file ends part way through a {} header", kind));
+        }
+        let name = std::str::from_utf8(&data[0..4])
+            .map_err(|_| format!("Invalid {} name in the file", kind))?;
+        let length = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        // The length comes from the file, so it may be too big to add to on 32 bit targets
+        let end = header_length
+            .checked_add(length)
+            .ok_or_else(|| format!("Invalid {} {} length in the file", name, kind))?;
+        let chunk = data
+            .get(header_length..end)
+            .ok_or_else(|| format!("The file ends part way through a {} {}", name, kind))?;
+        chunks.push((name, chunk));
+        data = &data[end..];
+    }
+
+    Ok(chunks)
+}
+
+/// Read a null terminated or null padded string
+pub(crate) fn read_string(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
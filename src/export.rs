@@ -0,0 +1,280 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+use serde::Serialize;
+use strum_macros::EnumIter;
+
+use super::{Barter, Character, Engine, Ingredient, Potion, RecipeCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => f.write_str("CSV"),
+            ExportFormat::Json => f.write_str("JSON"),
+            ExportFormat::Markdown => f.write_str("Markdown"),
+        }
+    }
+}
+
+/// How a Morrowind character brews potions and buys their ingredients, to add what each
+/// exported potion brews into and costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrewingDetails {
+    pub engine: Engine,
+    pub character: Character,
+    /// The merchant the ingredients are bought from
+    pub barter: Barter,
+}
+
+/// One exported potion
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PotionRecord {
+    pub ingredients: Vec<String>,
+    pub effects: Vec<String>,
+    /// Base value of the ingredients
+    pub total_value: u32,
+    pub total_weight: f32,
+    pub recipe_code: String,
+    /// Each effect as the character brews it, e.g. "Restore Health 5 pts for 14 secs"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brewed_effects: Option<Vec<String>>,
+    /// Value of the potion as the character brews it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brewed_value: Option<u32>,
+    /// What buying one of each ingredient costs the character
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ingredient_cost: Option<u32>,
+}
+
+impl PotionRecord {
+    pub fn from_potion(potion: &Potion) -> PotionRecord {
+        PotionRecord {
+            ingredients: potion.ingredient_names(),
+            effects: potion
+                .effects
+                .iter()
+                .map(|effect| effect.to_string())
+                .collect(),
+            total_value: potion.total_value(),
+            total_weight: potion.total_weight(),
+            recipe_code: RecipeCode::from_potion(potion, None).encode(),
+            brewed_effects: None,
+            brewed_value: None,
+            ingredient_cost: None,
+        }
+    }
+
+    /// A record that also has what the character brews and what the ingredients cost
+    pub fn from_brewed_potion(potion: &Potion, brewing: &BrewingDetails) -> PotionRecord {
+        let brewed_potion = brewing.engine.brew(potion, &brewing.character);
+        PotionRecord {
+            brewed_effects: Some(
+                brewed_potion
+                    .effects
+                    .iter()
+                    .map(|effect_strength| effect_strength.to_string())
+                    .collect(),
+            ),
+            brewed_value: Some(brewed_potion.value),
+            ingredient_cost: Some(brewing.barter.recipe_cost(&brewing.character, potion)),
+            ..PotionRecord::from_potion(potion)
+        }
+    }
+
+    fn headers(brewed: bool) -> Vec<&'static str> {
+        let mut headers = vec![
+            "Ingredients",
+            "Effects",
+            "Total Value",
+            "Total Weight",
+            "Recipe Code",
+        ];
+        if brewed {
+            headers.extend(["Brewed Effects", "Brewed Value", "Ingredient Cost"]);
+        }
+        headers
+    }
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = vec![
+            self.ingredients.join("; "),
+            self.effects.join("; "),
+            self.total_value.to_string(),
+            self.total_weight.to_string(),
+            self.recipe_code.clone(),
+        ];
+        if let Some(brewed_effects) = &self.brewed_effects {
+            fields.push(brewed_effects.join("; "));
+        }
+        fields.extend(
+            self.brewed_value
+                .map(|brewed_value| brewed_value.to_string()),
+        );
+        fields.extend(
+            self.ingredient_cost
+                .map(|ingredient_cost| ingredient_cost.to_string()),
+        );
+        fields
+    }
+}
+
+/// One exported ingredient
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngredientRecord {
+    pub id: String,
+    pub name: String,
+    pub effects: Vec<String>,
+    pub value: u16,
+    pub weight: f32,
+    pub harvest_chance: String,
+    pub selected: bool,
+}
+
+impl IngredientRecord {
+    pub fn from_ingredient(ingredient: &Ingredient) -> IngredientRecord {
+        IngredientRecord {
+            id: ingredient.id(),
+            name: ingredient.name.clone(),
+            effects: ingredient
+                .effects
+                .iter()
+                .flatten()
+                .map(|effect| effect.to_string())
+                .collect(),
+            value: ingredient.value,
+            weight: ingredient.weight,
+            harvest_chance: ingredient.harvest_chance.clone(),
+            selected: ingredient.selected,
+        }
+    }
+
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "ID",
+            "Name",
+            "Effects",
+            "Value",
+            "Weight",
+            "Harvest Chance",
+            "Selected",
+        ]
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.effects.join("; "),
+            self.value.to_string(),
+            self.weight.to_string(),
+            self.harvest_chance.clone(),
+            self.selected.to_string(),
+        ]
+    }
+}
+
+pub fn export_potions(potions: &[Potion], format: ExportFormat) -> Result<String, String> {
+    let records: Vec<PotionRecord> = potions.iter().map(PotionRecord::from_potion).collect();
+    let rows: Vec<Vec<String>> = records.iter().map(|record| record.fields()).collect();
+
+    export(&records, &PotionRecord::headers(false), &rows, format)
+}
+
+/// Export potions along with the magnitudes, durations and value the character brews them with,
+/// and what their ingredients cost
+pub fn export_brewed_potions(
+    potions: &[Potion],
+    brewing: &BrewingDetails,
+    format: ExportFormat,
+) -> Result<String, String> {
+    let records: Vec<PotionRecord> = potions
+        .iter()
+        .map(|potion| PotionRecord::from_brewed_potion(potion, brewing))
+        .collect();
+    let rows: Vec<Vec<String>> = records.iter().map(|record| record.fields()).collect();
+
+    export(&records, &PotionRecord::headers(true), &rows, format)
+}
+
+pub fn export_ingredients(
+    ingredients: &[Rc<RefCell<Ingredient>>],
+    format: ExportFormat,
+) -> Result<String, String> {
+    let records: Vec<IngredientRecord> = ingredients
+        .iter()
+        .filter_map(|ingredient| ingredient.try_borrow().ok())
+        .map(|ingredient| IngredientRecord::from_ingredient(&ingredient))
+        .collect();
+    let rows: Vec<Vec<String>> = records.iter().map(|record| record.fields()).collect();
+
+    export(&records, &IngredientRecord::headers(), &rows, format)
+}
+
+fn export<T: Serialize>(
+    records: &[T],
+    headers: &[&str],
+    rows: &[Vec<String>],
+    format: ExportFormat,
+) -> Result<String, String> {
+    match format {
+        ExportFormat::Csv => Ok(to_csv(headers, rows)),
+        ExportFormat::Json => serde_json::to_string_pretty(records)
+            .map_err(|error| format!("Unable to export as JSON: {}", error)),
+        ExportFormat::Markdown => Ok(to_markdown(headers, rows)),
+    }
+}
+
+fn to_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    // Quote any field containing a separator, quote or line break, doubling any quotes inside it
+    let escape = |field: &str| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+
+    let mut csv = headers.iter().map(|header| escape(header)).join(",");
+    csv.push('\n');
+    for row in rows {
+        csv.push_str(&row.iter().map(|field| escape(field)).join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn to_markdown(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let escape = |field: &str| field.replace('|', "\\|").replace('\n', " ");
+
+    let mut markdown = format!(
+        "| {} |\n",
+        headers.iter().map(|header| escape(header)).join(" | ")
+    );
+    markdown.push_str(&format!("|{}\n", " --- |".repeat(headers.len())));
+    for row in rows {
+        markdown.push_str(&format!(
+            "| {} |\n",
+            row.iter().map(|field| escape(field)).join(" | ")
+        ));
+    }
+
+    markdown
+}
@@ -0,0 +1,75 @@
+use std::{cell::RefCell, rc::Rc};
+
+#[cfg(target_arch = "wasm32")]
+use eframe::egui;
+
+#[cfg(target_arch = "wasm32")]
+use super::web;
+
+/// A file chosen by the user, read in full
+#[derive(Debug, Clone)]
+pub struct LoadedFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Receives a file chosen by the user
+/// Natively the file is read straight away from a path, but on web it only arrives once the user
+/// has picked it, so poll `take` every frame
+#[derive(Debug, Default, Clone)]
+pub struct FileOpener {
+    result: Rc<RefCell<Option<Result<LoadedFile, String>>>>,
+}
+
+impl FileOpener {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(&self, path: &str) {
+        let result = std::fs::read(path)
+            .map(|bytes| LoadedFile {
+                name: path.to_string(),
+                bytes,
+            })
+            .map_err(|error| format!("Unable to read {}: {}", path, error));
+        *self.result.borrow_mut() = Some(result);
+    }
+
+    /// Show the browser's file chooser, limited to the comma separated file types in `accept`
+    #[cfg(target_arch = "wasm32")]
+    pub fn open(&self, ctx: &egui::Context, accept: &str) {
+        let result = self.result.clone();
+        let ctx = ctx.clone();
+        let picked = web::pick_file(accept, move |loaded| {
+            *result.borrow_mut() = Some(loaded.map(|(name, bytes)| LoadedFile { name, bytes }));
+            ctx.request_repaint();
+        });
+        if let Err(error) = picked {
+            *self.result.borrow_mut() = Some(Err(error));
+        }
+    }
+
+    /// Take the chosen file, if it has arrived
+    pub fn take(&self) -> Option<Result<LoadedFile, String>> {
+        match self.result.try_borrow_mut() {
+            Ok(mut result) => result.take(),
+            Err(_) => None, // Still being written, so try again next frame
+        }
+    }
+}
+
+/// Hand text to the user as a file
+/// Natively this writes to `path`; on web the file name of `path` is offered as a download
+/// Returns a message describing where the file went
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_text_file(path: &str, contents: &str) -> Result<String, String> {
+    std::fs::write(path, contents)
+        .map_err(|error| format!("Unable to write {}: {}", path, error))?;
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+    Ok(format!("Saved to {}", path.display()))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_text_file(path: &str, contents: &str) -> Result<String, String> {
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    web::download_file(file_name, contents)?;
+    Ok(format!("Downloaded {}", file_name))
+}
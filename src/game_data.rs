@@ -0,0 +1,568 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::{
+    esm::{read_records, read_string, read_subrecords},
+    Effect, Ingredient,
+};
+
+/// Names of the magic effects, by the index used for them in plugins
+const MAGIC_EFFECT_NAMES: [&str; 143] = [
+    "Water Breathing",
+    "Swift Swim",
+    "Water Walking",
+    "Shield",
+    "Fire Shield",
+    "Lightning Shield",
+    "Frost Shield",
+    "Burden",
+    "Feather",
+    "Jump",
+    "Levitate",
+    "Slow Fall",
+    "Lock",
+    "Open",
+    "Fire Damage",
+    "Shock Damage",
+    "Frost Damage",
+    "Drain Attribute",
+    "Drain Health",
+    "Drain Magicka",
+    "Drain Fatigue",
+    "Drain Skill",
+    "Damage Attribute",
+    "Damage Health",
+    "Damage Magicka",
+    "Damage Fatigue",
+    "Damage Skill",
+    "Poison",
+    "Weakness To Fire",
+    "Weakness To Frost",
+    "Weakness To Shock",
+    "Weakness To Magicka",
+    "Weakness To Common Disease",
+    "Weakness To Blight Disease",
+    "Weakness To Corprus Disease",
+    "Weakness To Poison",
+    "Weakness To Normal Weapons",
+    "Disintegrate Weapon",
+    "Disintegrate Armor",
+    "Invisibility",
+    "Chameleon",
+    "Light",
+    "Sanctuary",
+    "Night Eye",
+    "Charm",
+    "Paralyze",
+    "Silence",
+    "Blind",
+    "Sound",
+    "Calm Humanoid",
+    "Calm Creature",
+    "Frenzy Humanoid",
+    "Frenzy Creature",
+    "Demoralize Humanoid",
+    "Demoralize Creature",
+    "Rally Humanoid",
+    "Rally Creature",
+    "Dispel",
+    "Soultrap",
+    "Telekinesis",
+    "Mark",
+    "Recall",
+    "Divine Intervention",
+    "Almsivi Intervention",
+    "Detect Animal",
+    "Detect Enchantment",
+    "Detect Key",
+    "Spell Absorption",
+    "Reflect",
+    "Cure Common Disease",
+    "Cure Blight Disease",
+    "Cure Corprus Disease",
+    "Cure Poison",
+    "Cure Paralyzation",
+    "Restore Attribute",
+    "Restore Health",
+    "Restore Magicka",
+    "Restore Fatigue",
+    "Restore Skill",
+    "Fortify Attribute",
+    "Fortify Health",
+    "Fortify Magicka",
+    "Fortify Fatigue",
+    "Fortify Skill",
+    "Fortify Maximum Magicka",
+    "Absorb Attribute",
+    "Absorb Health",
+    "Absorb Magicka",
+    "Absorb Fatigue",
+    "Absorb Skill",
+    "Resist Fire",
+    "Resist Frost",
+    "Resist Shock",
+    "Resist Magicka",
+    "Resist Common Disease",
+    "Resist Blight Disease",
+    "Resist Corprus Disease",
+    "Resist Poison",
+    "Resist Normal Weapons",
+    "Resist Paralysis",
+    "Remove Curse",
+    "Turn Undead",
+    "Summon Scamp",
+    "Summon Clannfear",
+    "Summon Daedroth",
+    "Summon Dremora",
+    "Summon Ancestral Ghost",
+    "Summon Skeletal Minion",
+    "Summon Bonewalker",
+    "Summon Greater Bonewalker",
+    "Summon Bonelord",
+    "Summon Winged Twilight",
+    "Summon Hunger",
+    "Summon Golden Saint",
+    "Summon Flame Atronach",
+    "Summon Frost Atronach",
+    "Summon Storm Atronach",
+    "Fortify Attack",
+    "Command Creature",
+    "Command Humanoid",
+    "Bound Dagger",
+    "Bound Longsword",
+    "Bound Mace",
+    "Bound Battle Axe",
+    "Bound Spear",
+    "Bound Longbow",
+    "Extra Spell",
+    "Bound Cuirass",
+    "Bound Helm",
+    "Bound Boots",
+    "Bound Shield",
+    "Bound Gloves",
+    "Corprus",
+    "Vampirism",
+    "Summon Centurion Sphere",
+    "Sun Damage",
+    "Stunted Magicka",
+    "Summon Fabricant",
+    "Summon Wolf",
+    "Summon Bear",
+    "Summon Bonewolf",
+    "Summon Creature 04",
+    "Summon Creature 05",
+];
+
+/// Names of the attributes, by the index used for them in plugins
+const ATTRIBUTE_NAMES: [&str; 8] = [
+    "Strength",
+    "Intelligence",
+    "Willpower",
+    "Agility",
+    "Speed",
+    "Endurance",
+    "Personality",
+    "Luck",
+];
+
+/// Names of the skills, by the index used for them in plugins
+const SKILL_NAMES: [&str; 27] = [
+    "Block",
+    "Armorer",
+    "Medium Armor",
+    "Heavy Armor",
+    "Blunt Weapon",
+    "Long Blade",
+    "Axe",
+    "Spear",
+    "Athletics",
+    "Enchant",
+    "Destruction",
+    "Alteration",
+    "Illusion",
+    "Conjuration",
+    "Mysticism",
+    "Restoration",
+    "Alchemy",
+    "Unarmored",
+    "Security",
+    "Sneak",
+    "Acrobatics",
+    "Light Armor",
+    "Short Blade",
+    "Marksman",
+    "Mercantile",
+    "Speechcraft",
+    "Hand To Hand",
+];
+
+/// Length of an INGR IRDT subrecord: weight, value, then 4 effect, skill and attribute indexes
+const IRDT_LENGTH: usize = 56;
+
+/// The game's data files, as set up for OpenMW or Morrowind.exe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameFiles {
+    /// Directories holding content files, lowest priority first
+    pub data_directories: Vec<PathBuf>,
+    /// The active plugins, in load order
+    pub content_files: Vec<String>,
+}
+
+/// An ingredient record from a plugin
+#[derive(Debug, Clone)]
+pub struct PluginIngredient {
+    /// Editor ID, e.g. "ingred_ash_yam_01"
+    pub id: String,
+    /// None if the plugin deletes this ingredient
+    pub ingredient: Option<Ingredient>,
+}
+
+impl GameFiles {
+    /// Read the `data=` and `content=` lines of an openmw.cfg
+    /// Relative data directories are relative to `cfg_directory`
+    pub fn parse_openmw_cfg(text: &str, cfg_directory: &Path) -> GameFiles {
+        let mut game_files = GameFiles {
+            data_directories: Vec::new(),
+            content_files: Vec::new(),
+        };
+        let mut data_local = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = Self::unquote(value.trim());
+            match key.trim() {
+                // Paths such as "?userdata?data" depend on the OpenMW install, so can't be followed
+                "data" | "data-local" if value.starts_with('?') => {}
+                "data" => game_files.data_directories.push(cfg_directory.join(value)),
+                "data-local" => data_local = Some(cfg_directory.join(value)),
+                "content" => game_files.content_files.push(value),
+                "replace" => match value.as_str() {
+                    "data" => game_files.data_directories.clear(),
+                    "content" => game_files.content_files.clear(),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        // The local data directory always has the highest priority
+        game_files.data_directories.extend(data_local);
+
+        game_files
+    }
+
+    pub fn from_openmw_cfg(path: &Path) -> Result<GameFiles, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+
+        Ok(Self::parse_openmw_cfg(
+            &text,
+            path.parent().unwrap_or_else(|| Path::new("")),
+        ))
+    }
+
+    /// Read the `[Game Files]` section of a Morrowind.ini, whose data files are in "Data Files"
+    /// next to it
+    /// Masters (.esm) load before plugins (.esp), but otherwise the files are in the order listed;
+    /// `from_morrowind_ini` puts them in the game's load order
+    pub fn parse_morrowind_ini(text: &str, ini_directory: &Path) -> GameFiles {
+        let mut content_files: Vec<String> = Vec::new();
+        let mut in_game_files = false;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_game_files = line.eq_ignore_ascii_case("[Game Files]");
+                continue;
+            }
+            if !in_game_files {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim().to_lowercase().starts_with("gamefile") && !value.trim().is_empty() {
+                    content_files.push(value.trim().to_string());
+                }
+            }
+        }
+        content_files.sort_by_key(|content_file| !Self::is_master(content_file));
+
+        GameFiles {
+            data_directories: vec![ini_directory.join("Data Files")],
+            content_files,
+        }
+    }
+
+    /// Masters load before plugins, and Morrowind.exe loads each in order of when they were last
+    /// modified
+    pub fn from_morrowind_ini(path: &Path) -> Result<GameFiles, String> {
+        let data = std::fs::read(path)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        let mut game_files = Self::parse_morrowind_ini(
+            &String::from_utf8_lossy(&data),
+            path.parent().unwrap_or_else(|| Path::new("")),
+        );
+
+        let mut content_files: Vec<(bool, SystemTime, String)> = game_files
+            .content_files
+            .iter()
+            .map(|content_file| {
+                let modified = game_files
+                    .find_content_file(content_file)
+                    .and_then(|path| std::fs::metadata(path).ok())
+                    .and_then(|metadata| metadata.modified().ok())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (
+                    !Self::is_master(content_file),
+                    modified,
+                    content_file.clone(),
+                )
+            })
+            .collect();
+        content_files.sort_by_key(|(is_plugin, modified, _)| (*is_plugin, *modified));
+        game_files.content_files = content_files
+            .into_iter()
+            .map(|(_, _, content_file)| content_file)
+            .collect();
+
+        Ok(game_files)
+    }
+
+    /// Look for an openmw.cfg, then a Morrowind.ini, in the usual places
+    /// Returns None if neither is found, or an error if the one found couldn't be read
+    pub fn detect() -> Option<Result<GameFiles, String>> {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from);
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+
+        let mut openmw_cfgs = vec![PathBuf::from("openmw.cfg")];
+        openmw_cfgs.extend(config_home.map(|config_home| config_home.join("openmw/openmw.cfg")));
+        if let Some(home) = &home {
+            openmw_cfgs.push(home.join(".var/app/org.openmw.OpenMW/config/openmw/openmw.cfg"));
+            openmw_cfgs.push(home.join("Library/Preferences/openmw/openmw.cfg"));
+            openmw_cfgs.push(home.join("Documents/My Games/OpenMW/openmw.cfg"));
+        }
+
+        let mut morrowind_inis = vec![
+            PathBuf::from("Morrowind.ini"),
+            PathBuf::from(r"C:\Program Files (x86)\Steam\steamapps\common\Morrowind\Morrowind.ini"),
+            PathBuf::from(r"C:\Program Files (x86)\Bethesda Softworks\Morrowind\Morrowind.ini"),
+            PathBuf::from(r"C:\GOG Games\Morrowind\Morrowind.ini"),
+        ];
+        if let Some(home) = &home {
+            morrowind_inis.push(home.join(".steam/steam/steamapps/common/Morrowind/Morrowind.ini"));
+            morrowind_inis
+                .push(home.join(".local/share/Steam/steamapps/common/Morrowind/Morrowind.ini"));
+        }
+
+        if let Some(openmw_cfg) = openmw_cfgs.iter().find(|path| path.is_file()) {
+            return Some(Self::from_openmw_cfg(openmw_cfg));
+        }
+        morrowind_inis
+            .iter()
+            .find(|path| path.is_file())
+            .map(|morrowind_ini| Self::from_morrowind_ini(morrowind_ini))
+    }
+
+    /// Find a content file in the highest priority data directory that has it, ignoring case
+    pub fn find_content_file(&self, content_file: &str) -> Option<PathBuf> {
+        self.data_directories
+            .iter()
+            .rev()
+            .find_map(|data_directory| {
+                let path = data_directory.join(content_file);
+                if path.is_file() {
+                    return Some(path);
+                }
+                std::fs::read_dir(data_directory)
+                    .ok()?
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .find(|path| {
+                        path.file_name()
+                            .map(|file_name| {
+                                file_name
+                                    .to_string_lossy()
+                                    .eq_ignore_ascii_case(content_file)
+                            })
+                            .unwrap_or(false)
+                    })
+            })
+    }
+
+    /// Read the ingredients from every active plugin in load order, so later plugins replace or
+    /// delete the ingredients of earlier ones
+    pub fn load_ingredients(&self) -> Result<Vec<Ingredient>, String> {
+        let mut ingredients: Vec<(String, Ingredient)> = Vec::new();
+
+        for content_file in self.content_files.iter() {
+            let extension = Path::new(content_file)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !matches!(extension.as_str(), "esm" | "esp" | "omwgame" | "omwaddon") {
+                continue; // e.g. .omwscripts, which have no records
+            }
+
+            let path = self.find_content_file(content_file).ok_or_else(|| {
+                format!(
+                    "Unable to find {} in the game's data directories",
+                    content_file
+                )
+            })?;
+            let data = std::fs::read(&path)
+                .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+            let plugin_ingredients = read_plugin_ingredients(&data, content_file)
+                .map_err(|error| format!("Unable to read {}: {}", content_file, error))?;
+
+            for plugin_ingredient in plugin_ingredients {
+                let id = plugin_ingredient.id.to_lowercase();
+                let existing = ingredients
+                    .iter()
+                    .position(|(existing_id, _)| *existing_id == id);
+                match (existing, plugin_ingredient.ingredient) {
+                    (Some(index), Some(ingredient)) => ingredients[index].1 = ingredient,
+                    (Some(index), None) => {
+                        ingredients.remove(index);
+                    }
+                    (None, Some(ingredient)) => ingredients.push((id, ingredient)),
+                    (None, None) => {}
+                }
+            }
+        }
+
+        Ok(ingredients
+            .into_iter()
+            .map(|(_, ingredient)| ingredient)
+            .collect())
+    }
+
+    fn is_master(content_file: &str) -> bool {
+        content_file.to_lowercase().ends_with(".esm")
+    }
+
+    /// Read an openmw.cfg value, which may be quoted with `&` escaping `"` and `&`
+    fn unquote(value: &str) -> String {
+        let Some(quoted) = value.strip_prefix('"') else {
+            return value.to_string();
+        };
+
+        let mut unquoted = String::new();
+        let mut characters = quoted.chars();
+        while let Some(character) = characters.next() {
+            match character {
+                '&' => unquoted.extend(characters.next()),
+                '"' => break,
+                _ => unquoted.push(character),
+            }
+        }
+
+        unquoted
+    }
+}
+
+/// Read the ingredient (INGR) records of a plugin
+/// Effects that this tool doesn't know, such as Charm, are left out
+pub fn read_plugin_ingredients(
+    data: &[u8],
+    plugin_name: &str,
+) -> Result<Vec<PluginIngredient>, String> {
+    let mut plugin_ingredients = Vec::new();
+
+    for (name, data) in read_records(data)? {
+        if name != "INGR" {
+            continue;
+        }
+
+        let mut id = String::new();
+        let mut ingredient = Ingredient {
+            name: String::new(),
+            description: format!("From {}", plugin_name),
+            weight: 0.0,
+            value: 0,
+            effects: [None; 4],
+            harvest_chance: String::new(),
+            selected: false,
+        };
+        let mut deleted = false;
+        for (subrecord_name, subrecord_data) in read_subrecords(data)? {
+            match subrecord_name {
+                "NAME" => id = read_string(subrecord_data),
+                "FNAM" => ingredient.name = read_string(subrecord_data),
+                "DELE" => deleted = true,
+                "IRDT" if subrecord_data.len() == IRDT_LENGTH => {
+                    let read_i32 = |offset: usize| {
+                        i32::from_le_bytes([
+                            subrecord_data[offset],
+                            subrecord_data[offset + 1],
+                            subrecord_data[offset + 2],
+                            subrecord_data[offset + 3],
+                        ])
+                    };
+                    ingredient.weight = f32::from_le_bytes([
+                        subrecord_data[0],
+                        subrecord_data[1],
+                        subrecord_data[2],
+                        subrecord_data[3],
+                    ]);
+                    ingredient.value = read_i32(4).clamp(0, u16::MAX.into()) as u16;
+                    for (index, effect) in ingredient.effects.iter_mut().enumerate() {
+                        *effect = effect_from_indexes(
+                            read_i32(8 + index * 4),
+                            read_i32(24 + index * 4),
+                            read_i32(40 + index * 4),
+                        );
+                    }
+                }
+                "IRDT" => return Err(format!("Invalid ingredient data for {}", id)),
+                _ => {}
+            }
+        }
+        if ingredient.name.is_empty() {
+            ingredient.name = id.clone();
+        }
+
+        plugin_ingredients.push(PluginIngredient {
+            id,
+            ingredient: (!deleted).then_some(ingredient),
+        });
+    }
+
+    Ok(plugin_ingredients)
+}
+
+/// The effect for a magic effect index, and the skill or attribute index it applies to if any
+/// e.g. 79 (Fortify Attribute) with attribute 1 (Intelligence) is Fortify Intelligence
+fn effect_from_indexes(
+    effect_index: i32,
+    skill_index: i32,
+    attribute_index: i32,
+) -> Option<Effect> {
+    let effect_name = MAGIC_EFFECT_NAMES.get(usize::try_from(effect_index).ok()?)?;
+    let effect_name = if let Some(prefix) = effect_name.strip_suffix(" Attribute") {
+        format!(
+            "{} {}",
+            prefix,
+            ATTRIBUTE_NAMES.get(usize::try_from(attribute_index).ok()?)?
+        )
+    } else if let Some(prefix) = effect_name.strip_suffix(" Skill") {
+        format!(
+            "{} {}",
+            prefix,
+            SKILL_NAMES.get(usize::try_from(skill_index).ok()?)?
+        )
+    } else {
+        effect_name.to_string()
+    };
+
+    effect_name.parse().ok()
+}
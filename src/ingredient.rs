@@ -1,5 +1,6 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
+#[cfg(feature = "gui")]
 use eframe::{
     egui::{self, Sense, TextFormat, TextStyle, Widget, WidgetInfo, WidgetText, WidgetType},
     emath::NumExt,
@@ -23,6 +24,22 @@ pub struct Ingredient {
 }
 
 impl Ingredient {
+    /// Stable identifier for this ingredient, derived from its name
+    /// e.g. "Bungler's Bane" becomes "bunglers_bane"
+    /// Adding new ingredients to the data set never changes the ID of an existing one
+    pub fn id(&self) -> String {
+        Self::id_from_name(&self.name)
+    }
+
+    /// The ID an ingredient with this name would have
+    pub fn id_from_name(name: &str) -> String {
+        name.to_lowercase()
+            .split(|character: char| !character.is_alphanumeric() && character != '\'')
+            .map(|word| word.replace('\'', ""))
+            .filter(|word| !word.is_empty())
+            .join("_")
+    }
+
     /// This function is used to create a default ingredient with an input effects array/slice
     /// This is used as a utility function for unit testing where only the effects field matters
     #[cfg(test)]
@@ -39,6 +56,7 @@ impl Ingredient {
     }
 }
 
+#[cfg(feature = "gui")]
 impl Widget for &mut Ingredient {
     // fn ui(self, ui: &mut egui::Ui) -> egui::Response {
     //     ui.group(|ui| {
@@ -138,20 +156,23 @@ impl Widget for &mut Ingredient {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Hash, Eq, EnumIter)]
-
+/// The ordering of `Effect` follows the declaration order below, beneficial effects first and
+/// harmful effects last. This is the canonical order used when listing the effects of a potion.
+#[derive(
+    PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Hash, Eq, PartialOrd, Ord, EnumIter,
+)]
 pub enum Effect {
-    // Chameleon,
+    Chameleon,
     CureBlightDisease,
     CureCommonDisease,
-    // CureDisease,
-    // CureParalysis,
+    CureDisease,
+    CureParalysis,
     CureParalyzation,
     CurePoison,
     DetectAnimal,
     DetectEnchantment,
     DetectKey,
-    // DetectLife,
+    DetectLife,
     Dispel,
     Feather,
     FireShield,
@@ -168,6 +189,25 @@ pub enum Effect {
     FortifyMagicka,
     FortifyMaximumMagicka,
     FortifyFatigue,
+    FortifyStamina,
+    FortifyAlteration,
+    FortifyBarter,
+    FortifyBlock,
+    FortifyCarryWeight,
+    FortifyConjuration,
+    FortifyDestruction,
+    FortifyEnchanting,
+    FortifyHeavyArmor,
+    FortifyIllusion,
+    FortifyLightArmor,
+    FortifyLockpicking,
+    FortifyMarksman,
+    FortifyOneHanded,
+    FortifyPickpocket,
+    FortifyRestoration,
+    FortifySmithing,
+    FortifySneak,
+    FortifyTwoHanded,
     FrostShield,
     Invisibility,
     Levitate,
@@ -176,13 +216,18 @@ pub enum Effect {
     NightEye,
     Recall,
     Reflect,
-    // ReflectDamage,
-    // ReflectSpell,
+    ReflectDamage,
+    ReflectSpell,
+    RegenerateHealth,
+    RegenerateMagicka,
+    RegenerateStamina,
     ResistCommonDisease,
-    // ResistDisease,
+    ResistDisease,
     ResistFire,
     ResistFrost,
+    ResistMagic,
     ResistMagicka,
+    ResistNormalWeapons,
     ResistParalysis,
     ResistPoison,
     ResistShock,
@@ -197,8 +242,9 @@ pub enum Effect {
     RestoreHealth,
     RestoreMagicka,
     RestoreFatigue,
-    // Shield,
-    // ShockShield,
+    RestoreStamina,
+    Shield,
+    ShockShield,
     SpellAbsorption,
     SwiftSwim,
     Telekinesis,
@@ -206,17 +252,20 @@ pub enum Effect {
     WaterWalking,
     Blind,
     Burden,
-    // DamageStrength,
+    DamageStrength,
     DamageIntelligence,
-    // DamageWillpower,
-    // DamageAgility,
-    // DamageSpeed,
-    // DamageEndurance,
-    // DamagePersonality,
-    // DamageLuck,
+    DamageWillpower,
+    DamageAgility,
+    DamageSpeed,
+    DamageEndurance,
+    DamagePersonality,
+    DamageLuck,
     DamageHealth,
     DamageMagicka,
+    DamageMagickaRegen,
     DamageFatigue,
+    DamageStamina,
+    DamageStaminaRegen,
     DrainAlteration,
     DrainStrength,
     DrainIntelligence,
@@ -229,15 +278,28 @@ pub enum Effect {
     DrainHealth,
     DrainMagicka,
     DrainFatigue,
-    // FireDamage,
+    Fear,
+    FireDamage,
+    Frenzy,
     FrostDamage,
+    LingeringDamageHealth,
+    LingeringDamageMagicka,
+    LingeringDamageStamina,
+    Paralysis,
     Paralyze,
     Poison,
-    // ShockDamage,
-    // Silence,
+    RavageHealth,
+    RavageMagicka,
+    RavageStamina,
+    ShockDamage,
+    Silence,
+    Slow,
     Vampirism,
     WeaknessToFire,
+    WeaknessToFrost,
+    WeaknessToMagic,
     WeaknessToPoison,
+    WeaknessToShock,
     // ModdedEffect(String),
 }
 
@@ -247,22 +309,100 @@ impl Effect {
             .sorted_by(|effect_1, effect_2| effect_1.to_string().cmp(&effect_2.to_string()))
             .collect()
     }
+
+    /// Whether this effect hurts whoever drinks it, e.g. Poison or Drain Health
+    /// Every game's harmful effects are listed here by name, so reordering or adding effects can't
+    /// change which ones count
+    pub fn is_harmful(&self) -> bool {
+        matches!(
+            self,
+            Effect::Blind
+                | Effect::Burden
+                | Effect::DamageStrength
+                | Effect::DamageIntelligence
+                | Effect::DamageWillpower
+                | Effect::DamageAgility
+                | Effect::DamageSpeed
+                | Effect::DamageEndurance
+                | Effect::DamagePersonality
+                | Effect::DamageLuck
+                | Effect::DamageHealth
+                | Effect::DamageMagicka
+                | Effect::DamageMagickaRegen
+                | Effect::DamageFatigue
+                | Effect::DamageStamina
+                | Effect::DamageStaminaRegen
+                | Effect::DrainAlteration
+                | Effect::DrainStrength
+                | Effect::DrainIntelligence
+                | Effect::DrainWillpower
+                | Effect::DrainAgility
+                | Effect::DrainSpeed
+                | Effect::DrainEndurance
+                | Effect::DrainPersonality
+                | Effect::DrainLuck
+                | Effect::DrainHealth
+                | Effect::DrainMagicka
+                | Effect::DrainFatigue
+                | Effect::Fear
+                | Effect::FireDamage
+                | Effect::Frenzy
+                | Effect::FrostDamage
+                | Effect::LingeringDamageHealth
+                | Effect::LingeringDamageMagicka
+                | Effect::LingeringDamageStamina
+                | Effect::Paralysis
+                | Effect::Paralyze
+                | Effect::Poison
+                | Effect::RavageHealth
+                | Effect::RavageMagicka
+                | Effect::RavageStamina
+                | Effect::ShockDamage
+                | Effect::Silence
+                | Effect::Slow
+                | Effect::Vampirism
+                | Effect::WeaknessToFire
+                | Effect::WeaknessToFrost
+                | Effect::WeaknessToMagic
+                | Effect::WeaknessToPoison
+                | Effect::WeaknessToShock
+        )
+    }
+}
+
+impl FromStr for Effect {
+    type Err = String;
+
+    /// Parse an effect from either its display name or its serialized name, ignoring case and
+    /// spacing e.g. "Restore Health", "RestoreHealth" and "restore health" are all accepted
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let normalize = |name: &str| {
+            name.chars()
+                .filter(|character| character.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let normalized_name = normalize(name);
+        Effect::iter()
+            .find(|effect| normalize(&effect.to_string()) == normalized_name)
+            .ok_or_else(|| format!("Unknown effect: {}", name))
+    }
 }
 
 impl Display for Effect {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            // Effect::Chameleon => f.write_str("Chameleon"),
+            Effect::Chameleon => f.write_str("Chameleon"),
             Effect::CureBlightDisease => f.write_str("Cure Blight Disease"),
             Effect::CureCommonDisease => f.write_str("Cure Common Disease"),
-            // Effect::CureDisease => f.write_str("Cure Disease"),
-            // Effect::CureParalysis => f.write_str("Cure Paralysis"),
+            Effect::CureDisease => f.write_str("Cure Disease"),
+            Effect::CureParalysis => f.write_str("Cure Paralysis"),
             Effect::CureParalyzation => f.write_str("Cure Paralyzation"),
             Effect::CurePoison => f.write_str("Cure Poison"),
             Effect::DetectAnimal => f.write_str("Detect Animal"),
             Effect::DetectEnchantment => f.write_str("Detect Enchantment"),
             Effect::DetectKey => f.write_str("Detect Key"),
-            // Effect::DetectLife => f.write_str("Detect Life"),
+            Effect::DetectLife => f.write_str("Detect Life"),
             Effect::Dispel => f.write_str("Dispel"),
             Effect::Feather => f.write_str("Feather"),
             Effect::FireShield => f.write_str("Fire Shield"),
@@ -279,6 +419,25 @@ impl Display for Effect {
             Effect::FortifyMagicka => f.write_str("Fortify Magicka"),
             Effect::FortifyMaximumMagicka => f.write_str("Fortify Maximum Magicka"),
             Effect::FortifyFatigue => f.write_str("Fortify Fatigue"),
+            Effect::FortifyStamina => f.write_str("Fortify Stamina"),
+            Effect::FortifyAlteration => f.write_str("Fortify Alteration"),
+            Effect::FortifyBarter => f.write_str("Fortify Barter"),
+            Effect::FortifyBlock => f.write_str("Fortify Block"),
+            Effect::FortifyCarryWeight => f.write_str("Fortify Carry Weight"),
+            Effect::FortifyConjuration => f.write_str("Fortify Conjuration"),
+            Effect::FortifyDestruction => f.write_str("Fortify Destruction"),
+            Effect::FortifyEnchanting => f.write_str("Fortify Enchanting"),
+            Effect::FortifyHeavyArmor => f.write_str("Fortify Heavy Armor"),
+            Effect::FortifyIllusion => f.write_str("Fortify Illusion"),
+            Effect::FortifyLightArmor => f.write_str("Fortify Light Armor"),
+            Effect::FortifyLockpicking => f.write_str("Fortify Lockpicking"),
+            Effect::FortifyMarksman => f.write_str("Fortify Marksman"),
+            Effect::FortifyOneHanded => f.write_str("Fortify One-Handed"),
+            Effect::FortifyPickpocket => f.write_str("Fortify Pickpocket"),
+            Effect::FortifyRestoration => f.write_str("Fortify Restoration"),
+            Effect::FortifySmithing => f.write_str("Fortify Smithing"),
+            Effect::FortifySneak => f.write_str("Fortify Sneak"),
+            Effect::FortifyTwoHanded => f.write_str("Fortify Two-Handed"),
             Effect::FrostShield => f.write_str("Frost Shield"),
             Effect::Invisibility => f.write_str("Invisibility"),
             Effect::Levitate => f.write_str("Levitate"),
@@ -287,13 +446,18 @@ impl Display for Effect {
             Effect::NightEye => f.write_str("Night Eye"),
             Effect::Recall => f.write_str("Recall"),
             Effect::Reflect => f.write_str("Reflect"),
-            // Effect::ReflectDamage => f.write_str("Reflect Damage"),
-            // Effect::ReflectSpell => f.write_str("Reflect Spell"),
+            Effect::ReflectDamage => f.write_str("Reflect Damage"),
+            Effect::ReflectSpell => f.write_str("Reflect Spell"),
+            Effect::RegenerateHealth => f.write_str("Regenerate Health"),
+            Effect::RegenerateMagicka => f.write_str("Regenerate Magicka"),
+            Effect::RegenerateStamina => f.write_str("Regenerate Stamina"),
             Effect::ResistCommonDisease => f.write_str("Resist Common Disease"),
-            // Effect::ResistDisease => f.write_str("Resist Disease"),
+            Effect::ResistDisease => f.write_str("Resist Disease"),
             Effect::ResistFire => f.write_str("Resist Fire"),
             Effect::ResistFrost => f.write_str("Resist Frost"),
+            Effect::ResistMagic => f.write_str("Resist Magic"),
             Effect::ResistMagicka => f.write_str("Resist Magicka"),
+            Effect::ResistNormalWeapons => f.write_str("Resist Normal Weapons"),
             Effect::ResistParalysis => f.write_str("Resist Paralysis"),
             Effect::ResistPoison => f.write_str("Resist Poison"),
             Effect::ResistShock => f.write_str("Resist Shock"),
@@ -308,8 +472,9 @@ impl Display for Effect {
             Effect::RestoreHealth => f.write_str("Restore Health"),
             Effect::RestoreMagicka => f.write_str("Restore Magicka"),
             Effect::RestoreFatigue => f.write_str("Restore Fatigue"),
-            // Effect::Shield => f.write_str("Shield"),
-            // Effect::ShockShield => f.write_str("Shock Shield"),
+            Effect::RestoreStamina => f.write_str("Restore Stamina"),
+            Effect::Shield => f.write_str("Shield"),
+            Effect::ShockShield => f.write_str("Shock Shield"),
             Effect::SpellAbsorption => f.write_str("Spell Absorption"),
             Effect::SwiftSwim => f.write_str("Swift Swim"),
             Effect::Telekinesis => f.write_str("Telekinesis"),
@@ -317,17 +482,20 @@ impl Display for Effect {
             Effect::WaterWalking => f.write_str("Water Walking"),
             Effect::Blind => f.write_str("Blind"),
             Effect::Burden => f.write_str("Burden"),
-            // Effect::DamageStrength => f.write_str("Damage Strength"),
+            Effect::DamageStrength => f.write_str("Damage Strength"),
             Effect::DamageIntelligence => f.write_str("Damage Intelligence"),
-            // Effect::DamageWillpower => f.write_str("Damage Willpower"),
-            // Effect::DamageAgility => f.write_str("Damage Agility"),
-            // Effect::DamageSpeed => f.write_str("Damage Speed"),
-            // Effect::DamageEndurance => f.write_str("Damage Endurance"),
-            // Effect::DamagePersonality => f.write_str("Damage Personality"),
-            // Effect::DamageLuck => f.write_str("Damage Luck"),
+            Effect::DamageWillpower => f.write_str("Damage Willpower"),
+            Effect::DamageAgility => f.write_str("Damage Agility"),
+            Effect::DamageSpeed => f.write_str("Damage Speed"),
+            Effect::DamageEndurance => f.write_str("Damage Endurance"),
+            Effect::DamagePersonality => f.write_str("Damage Personality"),
+            Effect::DamageLuck => f.write_str("Damage Luck"),
             Effect::DamageHealth => f.write_str("Damage Health"),
             Effect::DamageMagicka => f.write_str("Damage Magicka"),
+            Effect::DamageMagickaRegen => f.write_str("Damage Magicka Regen"),
             Effect::DamageFatigue => f.write_str("Damage Fatigue"),
+            Effect::DamageStamina => f.write_str("Damage Stamina"),
+            Effect::DamageStaminaRegen => f.write_str("Damage Stamina Regen"),
             Effect::DrainAlteration => f.write_str("Drain Alteration"),
             Effect::DrainStrength => f.write_str("Drain Strength"),
             Effect::DrainIntelligence => f.write_str("Drain Intelligence"),
@@ -340,15 +508,28 @@ impl Display for Effect {
             Effect::DrainHealth => f.write_str("Drain Health"),
             Effect::DrainMagicka => f.write_str("Drain Magicka"),
             Effect::DrainFatigue => f.write_str("Drain Fatigue"),
-            // Effect::FireDamage => f.write_str("Fire Damage"),
+            Effect::Fear => f.write_str("Fear"),
+            Effect::FireDamage => f.write_str("Fire Damage"),
+            Effect::Frenzy => f.write_str("Frenzy"),
             Effect::FrostDamage => f.write_str("Frost Damage"),
+            Effect::LingeringDamageHealth => f.write_str("Lingering Damage Health"),
+            Effect::LingeringDamageMagicka => f.write_str("Lingering Damage Magicka"),
+            Effect::LingeringDamageStamina => f.write_str("Lingering Damage Stamina"),
+            Effect::Paralysis => f.write_str("Paralysis"),
             Effect::Paralyze => f.write_str("Paralyze"),
             Effect::Poison => f.write_str("Poison"),
-            // Effect::ShockDamage => f.write_str("Shock Damage"),
-            // Effect::Silence => f.write_str("Silence"),
+            Effect::RavageHealth => f.write_str("Ravage Health"),
+            Effect::RavageMagicka => f.write_str("Ravage Magicka"),
+            Effect::RavageStamina => f.write_str("Ravage Stamina"),
+            Effect::ShockDamage => f.write_str("Shock Damage"),
+            Effect::Silence => f.write_str("Silence"),
+            Effect::Slow => f.write_str("Slow"),
             Effect::Vampirism => f.write_str("Vampirism"),
             Effect::WeaknessToFire => f.write_str("Weakness To Fire"),
+            Effect::WeaknessToFrost => f.write_str("Weakness To Frost"),
+            Effect::WeaknessToMagic => f.write_str("Weakness To Magic"),
             Effect::WeaknessToPoison => f.write_str("Weakness To Poison"),
+            Effect::WeaknessToShock => f.write_str("Weakness To Shock"),
         }
     }
 }
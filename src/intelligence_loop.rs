@@ -0,0 +1,175 @@
+use std::{cell::RefCell, cmp::Reverse, collections::BTreeMap, rc::Rc};
+
+use itertools::Itertools;
+
+use super::{Character, Effect, Engine, Ingredient, Potion};
+
+/// Most potions to simulate, as a strong enough alchemist never stops gaining
+const MAX_ITERATIONS: usize = 1000;
+
+/// Why a Fortify Intelligence loop stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntelligenceLoopEnd {
+    /// No two different ingredients with Fortify Intelligence are left
+    OutOfIngredients,
+    /// Potion strength rose by less than the minimum gain since the last potion was brewed, or the
+    /// character is too weak an alchemist to brew Fortify Intelligence at all
+    Flattened,
+    /// Still gaining after the most potions this simulates
+    IterationLimit,
+}
+
+/// One Fortify Intelligence potion brewed and drunk
+#[derive(Debug, Clone)]
+pub struct IntelligenceLoopStep {
+    pub recipe: Potion,
+    /// Intelligence while brewing, before drinking this potion
+    pub intelligence: u16,
+    /// Potion strength while brewing
+    pub strength: f32,
+    /// Points of Intelligence the potion adds
+    pub magnitude: u32,
+    /// Seconds the potion lasts before its Intelligence wears off
+    pub duration: u32,
+    pub success_chance: f32,
+}
+
+/// The outcome of a Fortify Intelligence loop
+#[derive(Debug, Clone)]
+pub struct IntelligenceLoopResult {
+    pub steps: Vec<IntelligenceLoopStep>,
+    /// Intelligence after drinking every potion, less any that wore off
+    pub final_intelligence: u16,
+    pub end: IntelligenceLoopEnd,
+    /// Ingredients left over, by ingredient ID
+    pub leftover: BTreeMap<String, u32>,
+}
+
+/// The Morrowind exploit of brewing Fortify Intelligence potions and drinking them, so that each
+/// potion is brewed stronger than the last
+///
+/// Each step brews one potion from two ingredients with Fortify Intelligence, which is all a
+/// potion needs as more ingredients don't make it stronger, and drinks it straight away
+/// Every brew is assumed to succeed, with the chance of that shown for each step
+/// Potions wear off once their duration has passed, so a loop that takes too long between
+/// potions stops gaining
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntelligenceLoop {
+    pub engine: Engine,
+    pub character: Character,
+    /// Count of each available ingredient, by ingredient ID
+    pub stock: BTreeMap<String, u32>,
+    /// Stop once potion strength rises by less than this from one potion to the next
+    pub min_strength_gain: f32,
+    /// Game seconds from drinking one potion to drinking the next
+    /// Menus pause the game, so this is only the time spent outside of them
+    pub seconds_per_potion: u32,
+}
+
+impl IntelligenceLoop {
+    /// Brew and drink potions from the stock of `ingredients` until the loop stops paying off
+    pub fn simulate(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> IntelligenceLoopResult {
+        let fortify_ingredients: Vec<(String, &Rc<RefCell<Ingredient>>)> = ingredients
+            .iter()
+            .filter_map(|ingredient| {
+                let borrowed_ingredient = ingredient.try_borrow().ok()?;
+                borrowed_ingredient
+                    .effects
+                    .contains(&Some(Effect::FortifyIntelligence))
+                    .then(|| (borrowed_ingredient.id(), ingredient))
+            })
+            .collect();
+
+        let mut stock = self.stock.clone();
+        let mut character = self.character.clone();
+        let mut steps: Vec<IntelligenceLoopStep> = Vec::new();
+        // How much each potion drunk changes Intelligence by, and the game second it wears off
+        let mut active_effects: Vec<(i64, u64)> = Vec::new();
+        let mut time: u64 = 0;
+        let end = loop {
+            if let Some(last_step) = steps.last() {
+                time += u64::from(self.seconds_per_potion);
+                active_effects.retain(|(_, wears_off)| *wears_off > time);
+                character.intelligence = intelligence_with(&self.character, &active_effects);
+                if self.engine.potion_strength(&character) - last_step.strength
+                    < self.min_strength_gain
+                {
+                    break IntelligenceLoopEnd::Flattened;
+                }
+            }
+            if steps.len() >= MAX_ITERATIONS {
+                break IntelligenceLoopEnd::IterationLimit;
+            }
+            // Pair the two most plentiful ingredients, which leaves the most pairs for later
+            let Some((ingredient_1, ingredient_2)) = fortify_ingredients
+                .iter()
+                .filter(|(id, _)| stock.get(id).copied().unwrap_or(0) > 0)
+                .sorted_by_key(|(id, _)| (Reverse(stock.get(id).copied().unwrap_or(0)), id.clone()))
+                .next_tuple()
+            else {
+                break IntelligenceLoopEnd::OutOfIngredients;
+            };
+
+            let recipe = Potion::new_potion_from_ingredients(&[ingredient_1.1, ingredient_2.1]);
+            let brewed_potion = self.engine.brew(&recipe, &character);
+            let Some(fortify_intelligence) = brewed_potion
+                .effects
+                .iter()
+                .find(|effect| effect.effect == Effect::FortifyIntelligence)
+            else {
+                break IntelligenceLoopEnd::Flattened;
+            };
+            // A potion that also drains Intelligence gives back less, for as long as that lasts
+            let lost_intelligence = brewed_potion.effects.iter().filter(|effect| {
+                matches!(
+                    effect.effect,
+                    Effect::DrainIntelligence | Effect::DamageIntelligence
+                )
+            });
+
+            for (id, _) in [ingredient_1, ingredient_2] {
+                if let Some(count) = stock.get_mut(id) {
+                    *count -= 1;
+                }
+            }
+            let strength = self.engine.potion_strength(&character);
+            steps.push(IntelligenceLoopStep {
+                recipe,
+                intelligence: character.intelligence,
+                strength,
+                magnitude: fortify_intelligence.magnitude,
+                duration: fortify_intelligence.duration,
+                success_chance: brewed_potion.success_chance,
+            });
+
+            active_effects.push((
+                i64::from(fortify_intelligence.magnitude),
+                time + u64::from(fortify_intelligence.duration),
+            ));
+            for effect in lost_intelligence {
+                active_effects.push((
+                    -i64::from(effect.magnitude),
+                    time + u64::from(effect.duration),
+                ));
+            }
+            character.intelligence = intelligence_with(&self.character, &active_effects);
+        };
+
+        stock.retain(|_, count| *count > 0);
+        IntelligenceLoopResult {
+            steps,
+            final_intelligence: character.intelligence,
+            end,
+            leftover: stock,
+        }
+    }
+}
+
+/// The character's Intelligence while under the effects of the potions still active
+fn intelligence_with(character: &Character, active_effects: &[(i64, u64)]) -> u16 {
+    let change: i64 = active_effects.iter().map(|(change, _)| change).sum();
+    (i64::from(character.intelligence) + change)
+        .clamp(0, i64::from(u16::MAX))
+        .try_into()
+        .unwrap_or(u16::MAX)
+}
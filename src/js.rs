@@ -0,0 +1,58 @@
+//! Functions exported to JavaScript, so other pages can use the solver without the GUI
+//!
+//! Every function works on the bundled ingredients and takes and returns plain JavaScript
+//! objects in the same shapes as the JSON API. Errors are thrown as `Error`s.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use super::{api::Api, bundled_ingredients};
+
+thread_local! {
+    static API: Api = Api::new(bundled_ingredients());
+}
+
+/// Every effect name, e.g. "Restore Health"
+#[wasm_bindgen]
+pub fn effects() -> Result<JsValue, JsValue> {
+    API.with(|api| to_js(&api.effects()))
+}
+
+/// Every bundled ingredient
+#[wasm_bindgen]
+pub fn ingredients() -> Result<JsValue, JsValue> {
+    API.with(|api| to_js(&api.ingredients()))
+}
+
+/// The potions with all of the desired effects, e.g.
+/// `findPotions({ effects: ["Restore Health"], allow_extra_effects: true, max_ingredients: 3 })`
+#[wasm_bindgen(js_name = findPotions)]
+pub fn find_potions(query: JsValue) -> Result<JsValue, JsValue> {
+    let query = from_js(&query)?;
+    API.with(|api| to_js(&api.potions(&query).map_err(js_error)?))
+}
+
+/// The potion made from exactly these ingredients, by name or ID, e.g.
+/// `mixIngredients(["ash_yam", "Daedra Skin"])`
+#[wasm_bindgen(js_name = mixIngredients)]
+pub fn mix_ingredients(ingredients: JsValue) -> Result<JsValue, JsValue> {
+    let ingredients = from_js(&ingredients)?;
+    let request = format!("{{\"ingredients\": {}}}", ingredients);
+    API.with(|api| to_js(&api.mix(&request).map_err(js_error)?))
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(value).map_err(|error| js_error(error.to_string()))?;
+    js_sys::JSON::parse(&json)
+}
+
+fn from_js(value: &JsValue) -> Result<String, JsValue> {
+    if value.is_undefined() {
+        return Err(js_error("Missing argument".to_string()));
+    }
+    js_sys::JSON::stringify(value).map(String::from)
+}
+
+fn js_error(message: String) -> JsValue {
+    js_sys::Error::new(&message).into()
+}
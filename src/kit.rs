@@ -0,0 +1,236 @@
+use std::{cell::RefCell, collections::BTreeSet, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::{create_potential_potions, get_potential_ingredients, Effect, Ingredient, Potion};
+
+/// Most partial kits to try before settling for the best kit found so far
+const MAX_SEARCH_STEPS: usize = 200_000;
+
+/// What makes one kit better than another
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum KitObjective {
+    /// Fewest ingredient types, the cheapest of those
+    #[default]
+    FewestIngredients,
+    /// Lowest combined base value of one of each ingredient, the fewest of those
+    Cheapest,
+}
+
+impl Display for KitObjective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KitObjective::FewestIngredients => f.write_str("Fewest Ingredients"),
+            KitObjective::Cheapest => f.write_str("Cheapest"),
+        }
+    }
+}
+
+/// A fixed set of potions to brew from as few or as cheap ingredient types as possible
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KitQuery {
+    /// The effects each potion must have
+    pub targets: Vec<[Option<Effect>; 4]>,
+    pub objective: KitObjective,
+    pub max_ingredients: usize,
+    /// Whether a potion may have effects beyond those of its target
+    pub allow_extra_effects: bool,
+}
+
+/// The ingredient types to carry and the recipe for each target
+#[derive(Debug, Clone)]
+pub struct Kit {
+    /// Sorted by name
+    pub ingredients: Vec<Rc<RefCell<Ingredient>>>,
+    /// One recipe for each target with any effects, in the order of the targets
+    pub recipes: Vec<Potion>,
+    /// Whether every kit was considered, as a huge search settles for the best kit found so far
+    pub exhaustive: bool,
+}
+
+impl KitQuery {
+    /// Find the best kit made from `ingredients`, or name a target that no recipe makes
+    pub fn solve(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Result<Kit, String> {
+        let targets: Vec<&[Option<Effect>; 4]> = self
+            .targets
+            .iter()
+            .filter(|target| target.iter().flatten().next().is_some())
+            .collect();
+        let costs: Vec<(u32, u32)> = ingredients
+            .iter()
+            .map(|ingredient| {
+                let value = ingredient
+                    .try_borrow()
+                    .map_or(0, |ingredient| u32::from(ingredient.value));
+                match self.objective {
+                    KitObjective::FewestIngredients => (1, value),
+                    KitObjective::Cheapest => (value, 1),
+                }
+            })
+            .collect();
+
+        let mut candidates = Vec::new();
+        for target in targets.iter() {
+            let target_candidates = self.candidates(target, ingredients);
+            if target_candidates.is_empty() {
+                return Err(format!(
+                    "No recipe makes {}",
+                    target.iter().flatten().join(" + ")
+                ));
+            }
+            candidates.push(target_candidates);
+        }
+
+        // Targets with the fewest recipes first, as they narrow the search most
+        let order: Vec<usize> = (0..candidates.len())
+            .sorted_by_key(|index| candidates[*index].len())
+            .collect();
+        let mut search = Search {
+            candidates: &candidates,
+            order: &order,
+            costs: &costs,
+            chosen: Vec::with_capacity(order.len()),
+            best: None,
+            steps: 0,
+        };
+        search.run(&BTreeSet::new(), (0, 0));
+        let exhaustive = search.steps <= MAX_SEARCH_STEPS;
+        let Some((_, chosen)) = search.best else {
+            return Err("No kit found".to_string());
+        };
+
+        let mut recipes: Vec<Option<Potion>> = vec![None; candidates.len()];
+        for (&target_index, &candidate_index) in order.iter().zip(chosen.iter()) {
+            let recipe_ingredients: Vec<&Rc<RefCell<Ingredient>>> = candidates[target_index]
+                [candidate_index]
+                .iter()
+                .map(|&index| &ingredients[index])
+                .collect();
+            recipes[target_index] = Some(Potion::new_potion_from_ingredients(&recipe_ingredients));
+        }
+        let kit_ingredients: BTreeSet<usize> = order
+            .iter()
+            .zip(chosen.iter())
+            .flat_map(|(&target_index, &candidate_index)| {
+                candidates[target_index][candidate_index].iter().copied()
+            })
+            .collect();
+
+        Ok(Kit {
+            ingredients: kit_ingredients
+                .into_iter()
+                .map(|index| ingredients[index].clone())
+                .collect(),
+            recipes: recipes.into_iter().flatten().collect(),
+            exhaustive,
+        })
+    }
+
+    /// The ingredient sets, as indices into `ingredients`, that brew `target`, leaving out any
+    /// that only add ingredients to another set
+    fn candidates(
+        &self,
+        target: &[Option<Effect>; 4],
+        ingredients: &[Rc<RefCell<Ingredient>>],
+    ) -> Vec<Vec<usize>> {
+        let target_effects = target.iter().flatten().unique().count();
+        let potential_ingredients = get_potential_ingredients(target, ingredients);
+        let recipes: Vec<Vec<usize>> =
+            create_potential_potions(target, &potential_ingredients, self.max_ingredients)
+                .into_iter()
+                .filter(|potion| self.allow_extra_effects || potion.effects.len() == target_effects)
+                .map(|potion| {
+                    potion
+                        .ingredients
+                        .iter()
+                        .flatten()
+                        .filter_map(|ingredient| {
+                            ingredients
+                                .iter()
+                                .position(|other| Rc::ptr_eq(ingredient, other))
+                        })
+                        .sorted()
+                        .collect()
+                })
+                .collect();
+
+        recipes
+            .iter()
+            .filter(|recipe| {
+                !recipes.iter().any(|other| {
+                    other.len() < recipe.len() && other.iter().all(|index| recipe.contains(index))
+                })
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Branch and bound over one recipe per target
+struct Search<'a> {
+    /// Recipes for each target, as indices into the ingredients
+    candidates: &'a [Vec<Vec<usize>>],
+    /// The order to choose recipes for the targets in
+    order: &'a [usize],
+    costs: &'a [(u32, u32)],
+    /// The recipe chosen for each target so far, in `order`
+    chosen: Vec<usize>,
+    best: Option<((u32, u32), Vec<usize>)>,
+    steps: usize,
+}
+
+impl Search<'_> {
+    fn run(&mut self, kit: &BTreeSet<usize>, cost: (u32, u32)) {
+        self.steps += 1;
+        if self.steps > MAX_SEARCH_STEPS {
+            return;
+        }
+        if self
+            .best
+            .as_ref()
+            .is_some_and(|(best_cost, _)| cost >= *best_cost)
+        {
+            return;
+        }
+        let Some(&target_index) = self.order.get(self.chosen.len()) else {
+            self.best = Some((cost, self.chosen.clone()));
+            return;
+        };
+        let candidates = &self.candidates[target_index];
+
+        // A target the kit already brews needs nothing more, and any other recipe only adds cost
+        if let Some(covered) = candidates
+            .iter()
+            .position(|recipe| recipe.iter().all(|index| kit.contains(index)))
+        {
+            self.chosen.push(covered);
+            self.run(kit, cost);
+            self.chosen.pop();
+            return;
+        }
+
+        let mut options: Vec<(usize, (u32, u32))> = candidates
+            .iter()
+            .enumerate()
+            .map(|(candidate_index, recipe)| {
+                let added_cost = recipe
+                    .iter()
+                    .filter(|index| !kit.contains(index))
+                    .fold((0, 0), |total, &index| {
+                        (total.0 + self.costs[index].0, total.1 + self.costs[index].1)
+                    });
+                (candidate_index, added_cost)
+            })
+            .collect();
+        options.sort_by_key(|(_, added_cost)| *added_cost);
+        for (candidate_index, added_cost) in options {
+            let mut next_kit = kit.clone();
+            next_kit.extend(candidates[candidate_index].iter().copied());
+            self.chosen.push(candidate_index);
+            self.run(&next_kit, (cost.0 + added_cost.0, cost.1 + added_cost.1));
+            self.chosen.pop();
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::{Barter, Character, Engine, Ingredient, Potion};
+
+/// fMajorSkillBonus as a percentage: share of the usual skill progress a major skill needs to level
+/// Percentages keep the arithmetic exact, so a level never needs an extra brew from rounding
+const MAJOR_SKILL_BONUS: u32 = 75;
+/// fMinorSkillBonus as a percentage
+const MINOR_SKILL_BONUS: u32 = 100;
+/// fMiscSkillBonus as a percentage
+const MISC_SKILL_BONUS: u32 = 125;
+/// fSpecialSkillBonus as a percentage: further share for skills of the class's specialization,
+/// which is Magic for Alchemy
+const SPECIAL_SKILL_BONUS: u32 = 80;
+/// Skill progress from each potion brewed successfully, the Alchemy skill's first use value
+const POTION_SKILL_GAIN: u32 = 2;
+/// How many of the cheapest recipes to suggest
+const SUGGESTED_RECIPES: usize = 3;
+
+/// How Alchemy fits into the character's class, which decides how quickly it levels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum SkillType {
+    Major,
+    Minor,
+    #[default]
+    Miscellaneous,
+}
+
+impl SkillType {
+    fn bonus(&self) -> u32 {
+        match self {
+            SkillType::Major => MAJOR_SKILL_BONUS,
+            SkillType::Minor => MINOR_SKILL_BONUS,
+            SkillType::Miscellaneous => MISC_SKILL_BONUS,
+        }
+    }
+}
+
+impl Display for SkillType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkillType::Major => f.write_str("Major"),
+            SkillType::Minor => f.write_str("Minor"),
+            SkillType::Miscellaneous => f.write_str("Miscellaneous"),
+        }
+    }
+}
+
+/// Everything needed to plan raising a character's Alchemy by brewing potions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelingQuery {
+    pub engine: Engine,
+    /// Starts from `character.alchemy`
+    pub character: Character,
+    pub target_alchemy: u16,
+    /// Gold available to buy ingredients
+    pub gold: u32,
+    /// The merchant the ingredients are bought from
+    pub barter: Barter,
+    pub skill_type: SkillType,
+    /// Whether the character's class specializes in Magic
+    pub specialized: bool,
+}
+
+/// The cheapest way to raise Alchemy as far as the gold allows, up to the target
+#[derive(Debug, Clone)]
+pub struct LevelingPlan {
+    /// The cheapest recipes that brew a potion at the starting Alchemy, cheapest first
+    /// Any of them levels Alchemy equally quickly, as every potion counts the same
+    pub recipes: Vec<Potion>,
+    /// What buying the ingredients of the cheapest recipe costs
+    pub cost_per_brew: u32,
+    /// Brews to attempt, counting failures, which use up their ingredients all the same
+    pub brews: u32,
+    /// Expected number of failed brews among `brews`
+    pub failures: u32,
+    pub total_cost: u32,
+    /// The target, or less if the gold runs out first
+    pub reached_alchemy: u16,
+}
+
+impl LevelingQuery {
+    /// Plan to level Alchemy with the cheapest recipe made from any two of `ingredients`, or None
+    /// if none of them brews a potion at the character's current Alchemy
+    pub fn plan(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Option<LevelingPlan> {
+        let recipes = self.cheapest_recipes(ingredients);
+        let cost_per_brew = self.barter.recipe_cost(&self.character, recipes.first()?);
+
+        let mut plan = LevelingPlan {
+            recipes,
+            cost_per_brew,
+            brews: 0,
+            failures: 0,
+            total_cost: 0,
+            reached_alchemy: self.character.alchemy,
+        };
+        while plan.reached_alchemy < self.target_alchemy {
+            let successes = self.successful_brews_for_level(plan.reached_alchemy);
+            let success_chance = self.engine.success_chance(&Character {
+                alchemy: plan.reached_alchemy,
+                ..self.character.clone()
+            });
+            let brews = (successes as f32 / success_chance).ceil() as u32;
+            let cost = brews * cost_per_brew;
+            if plan.total_cost + cost > self.gold {
+                break;
+            }
+
+            plan.brews += brews;
+            plan.failures += brews - successes;
+            plan.total_cost += cost;
+            plan.reached_alchemy += 1;
+        }
+
+        Some(plan)
+    }
+
+    /// Potions that need to be brewed successfully to raise Alchemy from `alchemy` to the next
+    /// level, as progress towards a level starts again from nothing after each one
+    pub fn successful_brews_for_level(&self, alchemy: u16) -> u32 {
+        let special_skill_bonus = if self.specialized {
+            SPECIAL_SKILL_BONUS
+        } else {
+            100
+        };
+        // In ten thousandths of a point, from the two percentages
+        let progress_needed =
+            u32::from(alchemy + 1) * self.skill_type.bonus() * special_skill_bonus;
+
+        progress_needed.div_ceil(POTION_SKILL_GAIN * 100 * 100)
+    }
+
+    /// Two ingredient recipes that brew at least one effect at the character's current Alchemy,
+    /// cheapest first
+    fn cheapest_recipes(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Vec<Potion> {
+        ingredients
+            .iter()
+            .tuple_combinations()
+            .map(|(ingredient_1, ingredient_2)| {
+                Potion::new_potion_from_ingredients(&[ingredient_1, ingredient_2])
+            })
+            .filter(|potion| {
+                !potion.effects.is_empty()
+                    && !self.engine.brew(potion, &self.character).effects.is_empty()
+            })
+            .sorted_by_cached_key(|potion| {
+                (
+                    self.barter.recipe_cost(&self.character, potion),
+                    potion.recipe_key(),
+                )
+            })
+            .take(SUGGESTED_RECIPES)
+            .collect()
+    }
+}
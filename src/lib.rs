@@ -0,0 +1,361 @@
+//! The potion solver behind the Morrowind Alchemy Tool, without any of its user interface
+//!
+//! Load a set of ingredients, describe the potion you want with a [`PotionQuery`] and get back
+//! every potion that can be made:
+//!
+//! ```
+//! use morrowind_alchemy::{bundled_ingredients, Effect, PotionQuery};
+//!
+//! let ingredients = bundled_ingredients();
+//! let query = PotionQuery {
+//!     desired_effects: [Some(Effect::RestoreHealth), Some(Effect::RestoreFatigue), None, None],
+//!     allow_extra_effects: true,
+//!     max_ingredients: 3,
+//!     ..Default::default()
+//! };
+//!
+//! for potion in query.search(&ingredients) {
+//!     println!("{}: {}", potion.ingredient_names().join(", "), potion.total_value());
+//! }
+//! ```
+//!
+//! The graphical tool is behind the default `gui` feature. Depend on this crate with
+//! `default-features = false` to leave out egui and eframe.
+
+#![warn(clippy::all, rust_2018_idioms)]
+
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, io::BufReader, path::Path};
+
+// The original tests build their expected effects with `vec!`, keep them as written
+#[cfg(test)]
+#[allow(clippy::useless_vec)]
+mod tests;
+
+mod ingredient;
+pub use ingredient::*;
+mod potion;
+use itertools::Itertools;
+pub use potion::*;
+mod recipe_code;
+pub use recipe_code::*;
+mod deep_link;
+pub use deep_link::*;
+#[cfg(feature = "gui")]
+mod persistence;
+#[cfg(feature = "gui")]
+pub use persistence::*;
+mod recipe_book;
+pub use recipe_book::*;
+mod export;
+pub use export::*;
+mod query;
+pub use query::*;
+mod character;
+pub use character::*;
+mod ruleset;
+pub use ruleset::*;
+mod engine;
+pub use engine::*;
+mod leveling;
+pub use leveling::*;
+mod intelligence_loop;
+pub use intelligence_loop::*;
+mod barter;
+pub use barter::*;
+mod merchant;
+pub use merchant::*;
+mod shopping_list;
+pub use shopping_list::*;
+mod kit;
+pub use kit::*;
+mod batch;
+pub use batch::*;
+mod substitution;
+pub use substitution::*;
+mod completion;
+pub use completion::*;
+mod effect_matrix;
+pub use effect_matrix::*;
+mod esm;
+#[cfg(not(target_arch = "wasm32"))]
+mod game_data;
+#[cfg(not(target_arch = "wasm32"))]
+pub use game_data::*;
+mod save_file;
+pub use save_file::*;
+pub mod api;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(target_arch = "wasm32")]
+mod js;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+/// Names the ingredient lists to load, e.g. config.yaml
+pub struct Config {
+    #[serde(default)]
+    pub ingredient_lists: Vec<PathBuf>,
+    /// Optional lists of merchants, who they are, where they are and what they sell
+    #[serde(default)]
+    pub merchant_lists: Vec<PathBuf>,
+    /// Read the ingredients from the plugins this openmw.cfg activates, instead of the lists
+    #[serde(default)]
+    pub openmw_cfg: Option<PathBuf>,
+    /// Read the ingredients from the plugins this Morrowind.ini activates, instead of the lists
+    #[serde(default)]
+    pub morrowind_ini: Option<PathBuf>,
+    /// Look for an openmw.cfg or Morrowind.ini in the usual places when neither is given,
+    /// using the lists if none is found. Off unless asked for, so the lists are used by default
+    #[serde(default)]
+    pub detect_game_data: bool,
+}
+
+/// The base game, Tribunal and Bloodmoon ingredients built into the library, sorted by name
+pub fn bundled_ingredients() -> Vec<Rc<RefCell<Ingredient>>> {
+    Ruleset::Morrowind.bundled_ingredients()
+}
+
+/// Load and sort the ingredients named in the config file, falling back to the bundled
+/// Morrowind lists if the config file is missing or invalid
+/// Ingredients come from the game's own data files when the config points at an openmw.cfg or
+/// Morrowind.ini, or when detect_game_data is set and one is found in the usual places, and
+/// otherwise from the lists. Ingredients from the game files keep the description and harvest
+/// chance of the listed ingredient with the same ID
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_ingredients(config_path: &Path) -> Result<Vec<Rc<RefCell<Ingredient>>>, String> {
+    let data = std::fs::read_to_string(config_path).unwrap_or_else(|_| {
+        r#"ingredient_lists:
+  - res/Morrowind Base Game Ingredients.yaml
+  - res/Morrowind Tribunal Ingredients.yaml
+  - res/Morrowind Bloodmoon Ingredients.yaml
+"#
+        .to_string()
+    });
+    let config = serde_yaml::from_str(&data).unwrap_or_else(|_| Config {
+        ingredient_lists: vec![
+            PathBuf::from("res/Morrowind Base Game Ingredients.yaml"),
+            PathBuf::from("res/Morrowind Tribunal Ingredients.yaml"),
+            PathBuf::from("res/Morrowind Bloodmoon Ingredients.yaml"),
+        ],
+        merchant_lists: Vec::new(),
+        openmw_cfg: None,
+        morrowind_ini: None,
+        detect_game_data: false,
+    });
+
+    let game_ingredients = if let Some(openmw_cfg) = &config.openmw_cfg {
+        Some(GameFiles::from_openmw_cfg(openmw_cfg)?.load_ingredients()?)
+    } else if let Some(morrowind_ini) = &config.morrowind_ini {
+        Some(GameFiles::from_morrowind_ini(morrowind_ini)?.load_ingredients()?)
+    } else if config.detect_game_data {
+        // A game install that can't be read is no reason not to start, so use the lists instead
+        GameFiles::detect()
+            .map(|game_files| game_files?.load_ingredients())
+            .and_then(Result::ok)
+            .filter(|ingredients| !ingredients.is_empty())
+    } else {
+        None
+    };
+
+    if let Some(mut game_ingredients) = game_ingredients {
+        // The game files have no descriptions or harvest chances, so take them from the lists,
+        // which only add details here and so aren't needed
+        let listed_ingredients =
+            read_ingredient_lists(&config.ingredient_lists).unwrap_or_default();
+        for ingredient in game_ingredients.iter_mut() {
+            let id = ingredient.id();
+            if let Some(listed_ingredient) = listed_ingredients
+                .iter()
+                .find(|listed_ingredient| listed_ingredient.id() == id)
+            {
+                ingredient.description = listed_ingredient.description.clone();
+                ingredient.harvest_chance = listed_ingredient.harvest_chance.clone();
+            }
+        }
+        return Ok(sort_ingredients(game_ingredients));
+    }
+
+    Ok(sort_ingredients(read_ingredient_lists(
+        &config.ingredient_lists,
+    )?))
+}
+
+/// Read the ingredients of each YAML list in turn
+#[cfg(not(target_arch = "wasm32"))]
+fn read_ingredient_lists(ingredient_list_paths: &[PathBuf]) -> Result<Vec<Ingredient>, String> {
+    let mut ingredients = Vec::new();
+
+    for ingredient_list_path in ingredient_list_paths {
+        let ingredient_list = File::open(ingredient_list_path).map_err(|error| {
+            format!(
+                "Unable to open ingredient list {}: {}",
+                ingredient_list_path.display(),
+                error
+            )
+        })?;
+        let mut ingredient_list: Vec<Ingredient> =
+            serde_yaml::from_reader(BufReader::new(ingredient_list)).map_err(|error| {
+                format!(
+                    "Unable to deserialize ingredient list {}: {}",
+                    ingredient_list_path.display(),
+                    error
+                )
+            })?;
+        ingredients.append(&mut ingredient_list);
+    }
+
+    Ok(ingredients)
+}
+
+/// Load the merchants named in the config file, or none if the config file is missing or invalid
+/// or names no merchant lists
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_merchants(config_path: &Path) -> Result<Vec<Merchant>, String> {
+    let Some(config) = std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|data| serde_yaml::from_str::<Config>(&data).ok())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut merchants = Vec::new();
+    for merchant_list_path in config.merchant_lists {
+        let merchant_list = File::open(&merchant_list_path).map_err(|error| {
+            format!(
+                "Unable to open merchant list {}: {}",
+                merchant_list_path.display(),
+                error
+            )
+        })?;
+        let mut merchant_list: Vec<Merchant> =
+            serde_yaml::from_reader(BufReader::new(merchant_list)).map_err(|error| {
+                format!(
+                    "Unable to deserialize merchant list {}: {}",
+                    merchant_list_path.display(),
+                    error
+                )
+            })?;
+        merchants.append(&mut merchant_list);
+    }
+
+    Ok(merchants)
+}
+
+/// Share the ingredients out and sort them by name
+fn sort_ingredients(ingredients: Vec<Ingredient>) -> Vec<Rc<RefCell<Ingredient>>> {
+    let mut ingredients: Vec<Rc<RefCell<Ingredient>>> = ingredients
+        .into_iter()
+        .map(|ingredient| Rc::new(RefCell::new(ingredient)))
+        .collect();
+    ingredients.sort_by(|ingredient_1, ingredient_2| {
+        // Although this never crashed with the simpler .borrow() calls, let's remove the chance
+        if let Ok(ingredient_1) = ingredient_1.try_borrow() {
+            if let Ok(ingredient_2) = ingredient_2.try_borrow() {
+                ingredient_1.name.cmp(&ingredient_2.name)
+            } else {
+                ingredient_1.name.cmp(&"".to_string())
+            }
+        } else {
+            "".cmp("")
+        }
+    });
+
+    ingredients
+}
+
+/// Every ingredient with at least one of the desired effects
+pub fn get_potential_ingredients(
+    desired_effects: &[Option<Effect>; 4],
+    ingredients: &[Rc<RefCell<Ingredient>>],
+) -> Vec<Rc<RefCell<Ingredient>>> {
+    let desired_effects: Vec<&Effect> = desired_effects.iter().flatten().collect();
+    let potential_ingredients: Vec<Rc<RefCell<Ingredient>>> = ingredients
+        .iter()
+        .filter(|ingredient| {
+            // filter the ingredients iterator
+            let Ok(ingredient) = ingredient.try_borrow() else {
+                // Skip ingredient by returning false if we are unable to borrow
+                return false;
+            };
+            ingredient
+                .effects // get the effects array
+                .iter() // and grab an iterator to that
+                .flatten() // flatten to get a new iterator, removing any None variant, and ripping out the Effect from Option<Effect>
+                .filter(|ingredient_effect| { // filter the flattened iterator of the ingredient's effects
+                    desired_effects.contains(ingredient_effect) // If the current ingredient_effect is contained in the desired_effects, we have a match for the filter
+                })
+                .count() // Count the number of effects
+                > 0 // If we have more than 0 matched effects, this ingredient can be used to make a potion with at least one desired effect
+        })
+        .cloned()
+        .collect();
+    potential_ingredients
+}
+
+/// Look up ingredients by ID or by name, ignoring case
+pub fn find_ingredients(
+    ingredients: &[Rc<RefCell<Ingredient>>],
+    names: &[String],
+) -> Result<Vec<Rc<RefCell<Ingredient>>>, String> {
+    names
+        .iter()
+        .map(|name| {
+            ingredients
+                .iter()
+                .find(|ingredient| match ingredient.try_borrow() {
+                    Ok(ingredient) => {
+                        ingredient.id() == *name || ingredient.name.eq_ignore_ascii_case(name)
+                    }
+                    Err(_) => false,
+                })
+                .cloned()
+                .ok_or_else(|| format!("Unknown ingredient: {}", name))
+        })
+        .collect()
+}
+
+/// Every combination of 2 up to `max_ingredients` ingredients whose potion has all of the
+/// desired effects, without any of the filtering done by `PotionQuery::find_potions`
+pub fn create_potential_potions(
+    desired_effects: &[Option<Effect>; 4],
+    potential_ingredients: &[Rc<RefCell<Ingredient>>],
+    max_ingredients: usize,
+) -> Vec<Potion> {
+    // Convert the user input, containing possible None variants, into a Vector of &Effect (removing None variants)
+    let desired_effects: Vec<&Effect> = desired_effects.iter().flatten().collect();
+    let mut potions: Vec<Potion> = Vec::new();
+
+    // For combinations of 2, 3, and 4 ingredients, up to max_ingredients
+    for i in 2..=max_ingredients.clamp(2, 4) {
+        let mut potential_potions: Vec<Potion> = potential_ingredients
+            .iter() // iterate over the ingredients // Clone to remove the reference
+            .combinations(i) // Create combinations of ingredients
+            .filter_map(|ingredient_combo| {
+                // create a new potion from the ingredient_combo
+                let potential_potion =
+                    Potion::new_potion_from_ingredients(ingredient_combo.as_slice());
+
+                // Get the resulting effects of the potential_potion
+                let potential_potion_effects = &potential_potion.effects;
+
+                // if all of the desired effects are contained within the potential_potion_effects
+                if desired_effects
+                    .iter()
+                    .all(|desired_effect| potential_potion_effects.contains(desired_effect))
+                {
+                    // return the potential_potion
+                    Some(potential_potion)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        potions.append(&mut potential_potions);
+    }
+
+    potions
+}
@@ -1,31 +1,35 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
 
 #[cfg(not(target_arch = "wasm32"))]
-use std::{fs::File, io::BufReader};
+use std::path::Path;
 
-#[cfg(test)]
-mod tests;
-
-mod ingredient;
-use egui::{style::{Widgets, Selection, WidgetVisuals}, Color32, Rounding, Stroke, Visuals};
-use ingredient::*;
-mod potion;
+use egui::{
+    plot::{Legend, Line, Plot, PlotPoints},
+    style::{Selection, WidgetVisuals, Widgets},
+    Color32, Rounding, Stroke, Visuals,
+};
 use itertools::Itertools;
-use potion::*;
-use serde::{Deserialize, Serialize};
+use morrowind_alchemy::*;
+mod file_transfer;
+use file_transfer::*;
+#[cfg(target_arch = "wasm32")]
+mod web;
+use strum::IntoEnumIterator;
 
-use eframe::{egui::{self, Widget}, epaint::Shadow};
+use eframe::{
+    egui::{self, Widget},
+    epaint::Shadow,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 use eframe::epaint::Vec2;
 
-#[derive(Serialize, Deserialize)]
-struct Config {
-    ingredient_lists: Vec<PathBuf>,
-}
+/// Where the web build is hosted, used to build shareable links from the native build
+#[cfg(not(target_arch = "wasm32"))]
+const HOSTED_URL: &str = "https://atabor89.github.io/morrowind_alchemy_tool/";
 
 // Native
 #[cfg(not(target_arch = "wasm32"))]
@@ -68,16 +72,94 @@ fn main() {
     .expect("failed to start eframe");
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 struct App {
+    ruleset: Ruleset,
     ingredients: Vec<Rc<RefCell<Ingredient>>>,
+    /// Why the configured ingredients couldn't be loaded, if they couldn't
+    ingredient_error: Option<String>,
     desired_effects: [Option<Effect>; 4],
     previous_effects: [Option<Effect>; 4],
+    excluded_effects: [Option<Effect>; 4],
     potential_ingredients: Vec<Rc<RefCell<Ingredient>>>,
     filtered_ingredients: Vec<Rc<RefCell<Ingredient>>>,
     potential_potions: Vec<Potion>,
+    /// The recipe code of each potential potion, encoded once when the potions are found
+    potion_recipe_codes: Vec<String>,
     allow_extra_effects: bool,
     visuals: Visuals,
+    recipe_code_input: String,
+    recipe_code_error: Option<String>,
+    recipe_book: RecipeBook,
+    show_recipe_book: bool,
+    recipe_book_path: String,
+    recipe_book_opener: FileOpener,
+    recipe_book_status: Option<Result<String, String>>,
+    export_format: ExportFormat,
+    export_status: Option<Result<String, String>>,
+    /// Where to save exported potions, without the extension
+    potion_export_path: String,
+    /// Where to save exported ingredients, without the extension
+    ingredient_export_path: String,
+    character: Character,
+    engine: Engine,
+    barter: Barter,
+    /// Harmful side effects weaker than this are allowed without allowing extra effects, 0 for never
+    min_side_effect_magnitude: u32,
+    /// Hide the effects the character's Alchemy is too low to see, as the games do
+    only_known_effects: bool,
+    show_leveling: bool,
+    skill_type: SkillType,
+    specialized: bool,
+    target_alchemy: u16,
+    leveling_gold: u32,
+    /// The last plan made, or None inside if no recipe brews at the character's Alchemy
+    leveling_plan: Option<Option<LevelingPlan>>,
+    show_intelligence_loop: bool,
+    /// Morrowind merchants from config.yaml, or why they couldn't be loaded
+    merchants: Result<Vec<Merchant>, String>,
+    show_selling: bool,
+    show_shopping_list: bool,
+    show_kit: bool,
+    show_batch: bool,
+    show_completion: bool,
+    /// IDs of the ingredients every completion keeps
+    completion_locked: [Option<String>; 2],
+    completion_ranking: CompletionRanking,
+    completions: Option<Vec<Potion>>,
+    show_effect_matrix: bool,
+    /// Worked out from the ingredients when first shown
+    effect_matrix: Option<EffectMatrix>,
+    batch_objective: BatchObjective,
+    /// Most weight of ingredients to brew, if limited
+    batch_max_weight: Option<f32>,
+    batch_plan: Option<BatchPlan>,
+    /// The effects of each potion the kit must brew
+    kit_targets: Vec<[Option<Effect>; 4]>,
+    kit_objective: KitObjective,
+    kit_allow_extra_effects: bool,
+    kit: Option<Result<Kit, String>>,
+    /// Chosen recipes and how many potions to brew of each
+    shopping_recipes: Vec<(Potion, u32)>,
+    /// Index of the potion to sell among the potential potions
+    selling_potion: usize,
+    selling_count: u32,
+    /// Count of each ingredient to use in the Fortify Intelligence loop, by ingredient ID
+    intelligence_loop_stock: BTreeMap<String, u32>,
+    min_strength_gain: f32,
+    /// Game seconds spent outside of menus from one Fortify Intelligence potion to the next
+    seconds_per_potion: u32,
+    intelligence_loop_result: Option<IntelligenceLoopResult>,
+    /// Count of each owned ingredient, by ingredient ID
+    inventory: BTreeMap<String, u32>,
+    show_character: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    save_game_path: String,
+    save_game_opener: FileOpener,
+    save_game_status: Option<Result<String, String>>,
+    /// The URL fragment that was last read from or written to the address bar
+    #[cfg(target_arch = "wasm32")]
+    deep_link_fragment: String,
 }
 
 impl App {
@@ -86,121 +168,177 @@ impl App {
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
-        cc.egui_ctx.set_visuals(Self::dark());
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        // TODO: Resolve this storage after figuring out WASM hosting issue
-        // if let Some(storage) = cc.storage {
-        //     return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
-        // }
+        // Missing, corrupt or outdated state all fall back to a fresh start
+        let persisted_state = cc
+            .storage
+            .and_then(PersistedState::load)
+            .unwrap_or_default();
+        let visuals = if persisted_state.dark_mode {
+            Self::dark()
+        } else {
+            Self::light()
+        };
+        cc.egui_ctx.set_visuals(visuals.clone());
 
-        App {
-            ingredients: { create_ingredients() },
+        let mut app = App {
+            ruleset: persisted_state.ruleset,
+            ingredients: Vec::new(),
+            ingredient_error: None,
             desired_effects: [None, None, None, None],
             previous_effects: [None, None, None, None],
+            excluded_effects: [None, None, None, None],
             potential_ingredients: Vec::new(),
             filtered_ingredients: Vec::new(),
             potential_potions: Vec::new(),
+            potion_recipe_codes: Vec::new(),
             allow_extra_effects: false,
-            visuals: Self::dark(),
+            visuals,
+            recipe_code_input: String::new(),
+            recipe_code_error: None,
+            recipe_book: cc.storage.map(RecipeBook::load).unwrap_or_default(),
+            show_recipe_book: false,
+            recipe_book_path: String::from("recipe_book.yaml"),
+            recipe_book_opener: FileOpener::default(),
+            recipe_book_status: None,
+            export_format: ExportFormat::Csv,
+            export_status: None,
+            potion_export_path: String::from("potions"),
+            ingredient_export_path: String::from("ingredients"),
+            character: persisted_state.character.clone(),
+            engine: persisted_state.engine,
+            barter: persisted_state.barter,
+            min_side_effect_magnitude: persisted_state.min_side_effect_magnitude,
+            only_known_effects: persisted_state.only_known_effects,
+            show_leveling: false,
+            skill_type: persisted_state.skill_type,
+            specialized: persisted_state.specialized,
+            target_alchemy: 100,
+            leveling_gold: 1000,
+            leveling_plan: None,
+            show_intelligence_loop: false,
+            merchants: create_merchants(),
+            show_selling: false,
+            show_shopping_list: false,
+            show_kit: false,
+            show_batch: false,
+            show_completion: false,
+            completion_locked: [None, None],
+            completion_ranking: CompletionRanking::default(),
+            completions: None,
+            show_effect_matrix: false,
+            effect_matrix: None,
+            batch_objective: BatchObjective::default(),
+            batch_max_weight: None,
+            batch_plan: None,
+            kit_targets: vec![[None; 4]],
+            kit_objective: KitObjective::default(),
+            kit_allow_extra_effects: true,
+            kit: None,
+            shopping_recipes: Vec::new(),
+            selling_potion: 0,
+            selling_count: 10,
+            intelligence_loop_stock: persisted_state.inventory.clone(),
+            min_strength_gain: 1.0,
+            seconds_per_potion: 5,
+            intelligence_loop_result: None,
+            inventory: persisted_state.inventory.clone(),
+            show_character: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            save_game_path: String::new(),
+            save_game_opener: FileOpener::default(),
+            save_game_status: None,
+            #[cfg(target_arch = "wasm32")]
+            deep_link_fragment: String::new(),
+        };
+        // Only the choices are stored, so recompute the ingredients and potions from them
+        app.reload_ingredients();
+        app.apply_deep_link(&persisted_state.deep_link());
+
+        // Open straight to the query in the URL, e.g. ".../#effects=RestoreHealth,RestoreFatigue"
+        // A link takes priority over whatever was open last time
+        #[cfg(target_arch = "wasm32")]
+        {
+            web::repaint_on_hash_change(&cc.egui_ctx);
+            let fragment = cc
+                .integration_info
+                .web_info
+                .location
+                .hash
+                .trim_start_matches('#');
+            if !fragment.is_empty() {
+                app.apply_deep_link(&DeepLink::from_fragment(fragment));
+            }
+            app.deep_link_fragment = fragment.to_string();
         }
+
+        app
     }
 }
 
 #[cfg(target_arch = "wasm32")]
-fn create_ingredients() -> Vec<Rc<RefCell<Ingredient>>> {
-    let morrowind_base_game_ingredients: Vec<Ingredient> =
-        serde_yaml::from_str(include_str!("../res/Morrowind Base Game Ingredients.yaml"))
-            .unwrap_or_else(|_| Vec::new());
-    let mut morrowind_tribunal_ingredients: Vec<Ingredient> =
-        serde_yaml::from_str(include_str!("../res/Morrowind Tribunal Ingredients.yaml"))
-            .unwrap_or_else(|_| Vec::new());
-    let mut morrowind_bloodmoon_ingredients: Vec<Ingredient> =
-        serde_yaml::from_str(include_str!("../res/Morrowind Bloodmoon Ingredients.yaml"))
-            .unwrap_or_else(|_| Vec::new());
-    let mut ingredients = morrowind_base_game_ingredients;
-    ingredients.append(&mut morrowind_tribunal_ingredients);
-    ingredients.append(&mut morrowind_bloodmoon_ingredients);
-
-    let mut ingredients: Vec<Rc<RefCell<Ingredient>>> = ingredients
-        .iter()
-        .cloned()
-        .map(|ingredient| Rc::new(RefCell::new(ingredient)))
-        .collect();
-    ingredients.sort_by(|ingredient_1, ingredient_2| {
-        // Although this never crashed with the simpler .borrow() calls, let's remove the chance
-        if let Ok(ingredient_1) = ingredient_1.try_borrow() {
-            if let Ok(ingredient_2) = ingredient_2.try_borrow() {
-                ingredient_1.name.cmp(&ingredient_2.name)
-            } else {
-                ingredient_1.name.cmp(&"".to_string())
-            }
-        } else {
-            "".cmp("")
-        }
-    });
+fn create_ingredients(ruleset: Ruleset) -> Result<Vec<Rc<RefCell<Ingredient>>>, String> {
+    Ok(ruleset.bundled_ingredients())
+}
 
-    ingredients
+/// Morrowind ingredients come from config.yaml, the other games only have their bundled lists
+#[cfg(not(target_arch = "wasm32"))]
+fn create_ingredients(ruleset: Ruleset) -> Result<Vec<Rc<RefCell<Ingredient>>>, String> {
+    match ruleset {
+        Ruleset::Morrowind => load_ingredients(Path::new("config.yaml")),
+        ruleset => Ok(ruleset.bundled_ingredients()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn create_merchants() -> Result<Vec<Merchant>, String> {
+    Ok(Vec::new())
 }
 
+/// Merchants come from the merchant lists in config.yaml, if it names any
 #[cfg(not(target_arch = "wasm32"))]
-fn create_ingredients() -> Vec<Rc<RefCell<Ingredient>>> {
-    let config_path: PathBuf = PathBuf::from("config.yaml");
-    let data = std::fs::read_to_string(&config_path).unwrap_or_else(|_| {
-        r#"ingredient_lists:
-  - res/Morrowind Base Game Ingredients.yaml
-  - res/Morrowind Tribunal Ingredients.yaml
-  - res/Morrowind Bloodmoon Ingredients.yaml
-"#
-        .to_string()
-    });
-    let config = serde_yaml::from_str(&data).unwrap_or_else(|_| Config {
-        ingredient_lists: vec![
-            PathBuf::from("res/Morrowind Base Game Ingredients.yaml"),
-            PathBuf::from("res/Morrowind Tribunal Ingredients.yaml"),
-            PathBuf::from("res/Morrowind Bloodmoon Ingredients.yaml"),
-        ],
-    });
+fn create_merchants() -> Result<Vec<Merchant>, String> {
+    load_merchants(Path::new("config.yaml"))
+}
 
-    let mut ingredients = Vec::new();
-
-    for ingredient_list in config.ingredient_lists {
-        let ingredient_list = File::open(ingredient_list).expect("Unable to open ingredient list");
-        let mut ingredient_list: Vec<Ingredient> =
-            serde_yaml::from_reader(BufReader::new(ingredient_list))
-                .expect("Unable to deserialize ingredient list");
-        ingredients.append(&mut ingredient_list);
-    }
-
-    let mut ingredients: Vec<Rc<RefCell<Ingredient>>> = ingredients
-        .iter()
-        .cloned()
-        .map(|ingredient| Rc::new(RefCell::new(ingredient)))
-        .collect();
-    ingredients.sort_by(|ingredient_1, ingredient_2| {
-        // Although this never crashed with the simpler .borrow() calls, let's remove the chance
-        if let Ok(ingredient_1) = ingredient_1.try_borrow() {
-            if let Ok(ingredient_2) = ingredient_2.try_borrow() {
-                ingredient_1.name.cmp(&ingredient_2.name)
-            } else {
-                ingredient_1.name.cmp(&"".to_string())
-            }
-        } else {
-            "".cmp("")
-        }
-    });
+/// Most substitutes to suggest for each ingredient of a potion
+const MAX_SUBSTITUTES: usize = 5;
+/// Most ways of completing a potion to list
+const MAX_COMPLETIONS: usize = 20;
 
-    ingredients
+/// "Buy from: ..." listing the merchants who restock the ingredient, if any do
+fn where_to_buy(
+    merchants: &Result<Vec<Merchant>, String>,
+    ingredient: &Ingredient,
+) -> Option<String> {
+    let merchants = merchants_selling(merchants.as_deref().ok()?, ingredient);
+    (!merchants.is_empty()).then(|| format!("Buy from: {}", merchants.iter().join(", ")))
 }
 
 impl eframe::App for App {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+        PersistedState {
+            character: self.character.clone(),
+            engine: self.engine,
+            barter: self.barter,
+            min_side_effect_magnitude: self.min_side_effect_magnitude,
+            only_known_effects: self.only_known_effects,
+            skill_type: self.skill_type,
+            specialized: self.specialized,
+            inventory: self.inventory.clone(),
+            ..PersistedState::from_deep_link(self.current_deep_link(), self.visuals.dark_mode)
+        }
+        .save(storage);
+        self.recipe_book.save(storage);
     }
 
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        #[cfg(target_arch = "wasm32")]
+        self.sync_deep_link();
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
             #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
@@ -213,16 +351,153 @@ impl eframe::App for App {
             });
             ui.horizontal(|ui| {
                 ui.heading("Morrowind Alchemy Tool");
+                let mut ruleset = self.ruleset;
+                egui::ComboBox::from_id_source("ruleset")
+                    .selected_text(ruleset.to_string())
+                    .show_ui(ui, |ui| {
+                        for game in Ruleset::iter() {
+                            ui.selectable_value(&mut ruleset, game, game.to_string());
+                        }
+                    });
+                if ruleset != self.ruleset {
+                    // Effects and ingredients differ between games, so start a fresh query
+                    self.apply_deep_link(&DeepLink {
+                        ruleset,
+                        ..Default::default()
+                    });
+                }
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
                     // custom_global_dark_light_mode_buttons(ui);
                     self.custom_global_dark_light_mode_buttons(ui);
                     // egui::widgets::global_dark_light_mode_buttons(ui);
+                    ui.separator();
+                    ui.toggle_value(
+                        &mut self.show_recipe_book,
+                        format!(
+                            "📖 Recipe Book ({})",
+                            self.recipe_book.favourites(self.ruleset).count()
+                        ),
+                    );
+                    ui.toggle_value(&mut self.show_character, "🧪 Character");
+                    ui.toggle_value(&mut self.show_kit, "🧰 Kit");
+                    ui.toggle_value(&mut self.show_completion, "🔒 Complete Potion");
+                    ui.toggle_value(&mut self.show_effect_matrix, "▦ Effect Matrix");
+                    if self.ruleset == Ruleset::Morrowind {
+                        ui.toggle_value(&mut self.show_leveling, "📈 Leveling");
+                        ui.toggle_value(&mut self.show_intelligence_loop, "🔁 Intelligence Loop");
+                        ui.toggle_value(&mut self.show_selling, "💰 Selling");
+                        ui.toggle_value(
+                            &mut self.show_shopping_list,
+                            format!("🛒 Shopping List ({})", self.shopping_recipes.len()),
+                        );
+                        ui.toggle_value(&mut self.show_batch, "⚗ Batch");
+                    }
                 });
             });
-
         });
 
+        if self.show_character {
+            egui::SidePanel::left("character_panel")
+                .resizable(true)
+                .default_width(300.0)
+                .show(ctx, |ui| {
+                    self.create_character_panel(ui);
+                });
+        }
+
+        if self.show_leveling && self.ruleset == Ruleset::Morrowind {
+            let mut show_leveling = self.show_leveling;
+            egui::Window::new("Alchemy Leveling Planner")
+                .open(&mut show_leveling)
+                .show(ctx, |ui| {
+                    self.create_leveling_planner(ui);
+                });
+            self.show_leveling = show_leveling;
+        }
+
+        if self.show_intelligence_loop && self.ruleset == Ruleset::Morrowind {
+            let mut show_intelligence_loop = self.show_intelligence_loop;
+            egui::Window::new("Fortify Intelligence Loop")
+                .open(&mut show_intelligence_loop)
+                .show(ctx, |ui| {
+                    self.create_intelligence_loop(ui);
+                });
+            self.show_intelligence_loop = show_intelligence_loop;
+        }
+
+        if self.show_selling && self.ruleset == Ruleset::Morrowind {
+            let mut show_selling = self.show_selling;
+            egui::Window::new("Selling Planner")
+                .open(&mut show_selling)
+                .show(ctx, |ui| {
+                    self.create_selling_planner(ui);
+                });
+            self.show_selling = show_selling;
+        }
+
+        if self.show_shopping_list && self.ruleset == Ruleset::Morrowind {
+            let mut show_shopping_list = self.show_shopping_list;
+            egui::Window::new("Shopping List")
+                .open(&mut show_shopping_list)
+                .show(ctx, |ui| {
+                    self.create_shopping_list(ui);
+                });
+            self.show_shopping_list = show_shopping_list;
+        }
+
+        if self.show_batch && self.ruleset == Ruleset::Morrowind {
+            let mut show_batch = self.show_batch;
+            egui::Window::new("Batch Brewing")
+                .open(&mut show_batch)
+                .show(ctx, |ui| {
+                    self.create_batch_planner(ui);
+                });
+            self.show_batch = show_batch;
+        }
+
+        if self.show_completion {
+            let mut show_completion = self.show_completion;
+            egui::Window::new("Complete a Potion")
+                .open(&mut show_completion)
+                .show(ctx, |ui| {
+                    self.create_completion(ui);
+                });
+            self.show_completion = show_completion;
+        }
+
+        if self.show_effect_matrix {
+            let mut show_effect_matrix = self.show_effect_matrix;
+            egui::Window::new("Effect Matrix")
+                .open(&mut show_effect_matrix)
+                .show(ctx, |ui| {
+                    self.create_effect_matrix(ui);
+                });
+            self.show_effect_matrix = show_effect_matrix;
+        }
+
+        if self.show_kit {
+            let mut show_kit = self.show_kit;
+            egui::Window::new("Kit Builder")
+                .open(&mut show_kit)
+                .show(ctx, |ui| {
+                    self.create_kit_builder(ui);
+                });
+            self.show_kit = show_kit;
+        }
+
+        if self.show_recipe_book {
+            egui::SidePanel::right("recipe_book_panel")
+                .resizable(true)
+                .default_width(400.0)
+                .show(ctx, |ui| {
+                    self.create_recipe_book_panel(ui);
+                });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(ingredient_error) = &self.ingredient_error {
+                ui.colored_label(ui.visuals().error_fg_color, ingredient_error);
+            }
             self.create_effect_dropdown(ui, "Desired Effect 1", 0);
             self.create_effect_dropdown(ui, "Desired Effect 2", 1);
             self.create_effect_dropdown(ui, "Desired Effect 3", 2);
@@ -231,17 +506,45 @@ impl eframe::App for App {
                 // We have changed this modifier so we should generate potions
                 self.generate_potions();
             }
+            if self.ruleset == Ruleset::Morrowind && !self.allow_extra_effects {
+                ui.horizontal(|ui| {
+                    ui.label("Still allow harmful side effects weaker than");
+                    let response = ui.add(egui::DragValue::new(&mut self.min_side_effect_magnitude).clamp_range(0..=100).suffix(" pts"));
+                    ui.label("when brewed by your character (0 to never allow them)");
+                    if response.changed() {
+                        self.generate_potions();
+                    }
+                });
+            }
+            let previous_excluded_effects = self.excluded_effects;
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                ui.heading("Excluded Effects: ");
+                for effect_index in 0..self.excluded_effects.len() {
+                    self.create_excluded_effect_dropdown(ui, effect_index);
+                }
+            });
+            if self.excluded_effects != previous_excluded_effects {
+                // Exclusions only remove potions, the potential ingredients stay the same
+                self.generate_potions();
+            }
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                ui.heading("Recipe Code: ");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.recipe_code_input).hint_text("Paste a recipe code or link"));
+                if ui.button("Load").clicked() || (response.lost_focus() && ui.input().key_pressed(egui::Key::Enter)) {
+                    self.load_recipe_code();
+                }
+                if ui.button("Copy Link To This Query").clicked() {
+                    ui.output().copied_text = format!("{}#{}", Self::deep_link_base_url(), self.current_deep_link().to_fragment());
+                }
+            });
+            if let Some(recipe_code_error) = &self.recipe_code_error {
+                ui.colored_label(ui.visuals().error_fg_color, recipe_code_error);
+            }
             if !self.desired_effects.iter().zip(self.previous_effects.iter()).all(|(current_effect, previous_effect)| current_effect == previous_effect) {
                 // Some effect changed, reset values
                 self.potential_ingredients = get_potential_ingredients(&self.desired_effects, &self.ingredients);
-                // Unselect ingredients
-                for ingredient in self.potential_ingredients.iter_mut() {
-                    match ingredient.try_borrow_mut() {
-                        Ok(mut ingredient) => ingredient.selected = false,
-                        Err(_) => continue, // Unable to borrow ingredient so continuing is better than crashing
-                    }
-                }
-                self.potential_potions.clear();
+                // Unselect ingredients, other than any we own
+                self.select_owned_ingredients();
                 self.previous_effects = self.desired_effects;
             }
             ui.separator();
@@ -258,6 +561,9 @@ impl eframe::App for App {
                         // We have changed the selected ingredients, so let's generate potions
                         self.generate_potions();
                     };
+                    if !self.inventory.is_empty() && ui.button("Select Owned").clicked() {
+                        self.select_owned_ingredients();
+                    }
                     if ui.button("Select None").clicked() {
                         for ingredient in self.potential_ingredients.iter_mut() {
                             match ingredient.try_borrow_mut() {
@@ -271,18 +577,42 @@ impl eframe::App for App {
                 });
                 ui.separator();
             }
-            ui.heading("Potential Ingredients");
+            ui.horizontal(|ui| {
+                ui.heading("Potential Ingredients");
+                if let Some(export_action) = create_export_controls(ui, "ingredient_export", &mut self.export_format, &mut self.ingredient_export_path, !self.potential_ingredients.is_empty()) {
+                    let exported = export_ingredients(&self.potential_ingredients, self.export_format);
+                    let path = self.ingredient_export_path.clone();
+                    self.finish_export(ui, export_action, "ingredients", &path, exported);
+                }
+            });
             ui.separator();
             ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
                 self.create_ingredient_area(ui);
                 ui.add_space(10.0);
                 ui.separator();
-                ui.heading("Generated Potions");
+                ui.horizontal(|ui| {
+                    ui.heading("Generated Potions");
+                    if let Some(export_action) = create_export_controls(ui, "potion_export", &mut self.export_format, &mut self.potion_export_path, !self.potential_potions.is_empty()) {
+                        // Only Morrowind potions are brewed, so only they get magnitudes and prices
+                        let exported = if self.ruleset == Ruleset::Morrowind {
+                            let brewing = BrewingDetails {
+                                engine: self.engine,
+                                character: self.character.clone(),
+                                barter: self.barter,
+                            };
+                            export_brewed_potions(&self.potential_potions, &brewing, self.export_format)
+                        } else {
+                            export_potions(&self.potential_potions, self.export_format)
+                        };
+                        let path = self.potion_export_path.clone();
+                        self.finish_export(ui, export_action, "potions", &path, exported);
+                    }
+                });
                 ui.separator();
                 self.create_potion_area(ui);
-            });    
+            });
         });
-        
+
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
                 let taco_button = ui.button("Buy Me A Taco");
@@ -292,11 +622,64 @@ impl eframe::App for App {
                         new_tab: true,
                     });
                 }
+                match &self.export_status {
+                    Some(Ok(status)) => {
+                        ui.label(status);
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    None => {}
+                }
             });
         });
     }
 }
 
+enum ExportAction {
+    Save,
+    Copy,
+}
+
+/// Format picker with Save and Copy buttons, returning which button was pressed
+/// Natively the file to save to is typed in without its extension, which follows the format
+fn create_export_controls(
+    ui: &mut egui::Ui,
+    id: &str,
+    export_format: &mut ExportFormat,
+    path: &mut String,
+    enabled: bool,
+) -> Option<ExportAction> {
+    let mut export_action = None;
+    ui.add_space(20.0);
+    ui.label("Export as");
+    egui::ComboBox::from_id_source(id)
+        .selected_text(export_format.to_string())
+        .width(100.0)
+        .show_ui(ui, |ui| {
+            for format in ExportFormat::iter() {
+                ui.selectable_value(export_format, format, format.to_string());
+            }
+        });
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        ui.add(egui::TextEdit::singleline(path).desired_width(160.0));
+        ui.label(format!(".{}", export_format.file_extension()));
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = path; // Downloads are named after it, with nothing to type
+    ui.add_enabled_ui(enabled, |ui| {
+        if ui.button("Save").clicked() {
+            export_action = Some(ExportAction::Save);
+        }
+        if ui.button("Copy").clicked() {
+            export_action = Some(ExportAction::Copy);
+        }
+    });
+
+    export_action
+}
+
 impl App {
     fn custom_global_dark_light_mode_buttons(&mut self, ui: &mut egui::Ui) {
         let visuals = &mut ui.ctx().style().visuals.clone();
@@ -305,6 +688,7 @@ impl App {
             ui.selectable_value(visuals, Self::dark(), "🌙 Dark");
         });
         ui.ctx().set_visuals(visuals.clone());
+        self.visuals = visuals.clone();
     }
 
     /// Default dark theme.
@@ -380,7 +764,7 @@ impl App {
                 noninteractive: WidgetVisuals {
                     bg_fill: Color32::from_gray(248), // window background - should be distinct from TextEdit background
                     bg_stroke: Stroke::new(1.0, Color32::from_gray(190)), // separators, indentation lines, windows outlines
-                    fg_stroke: Stroke::new(1.0, Color32::BLACK),  // normal text color; original color: Color32::from_gray(80)
+                    fg_stroke: Stroke::new(1.0, Color32::BLACK), // normal text color; original color: Color32::from_gray(80)
                     rounding: Rounding::same(2.0),
                     expansion: 0.0,
                 },
@@ -433,50 +817,59 @@ impl App {
         if self.potential_ingredients.is_empty() {
             ui.group(|ui| {
                 egui::ScrollArea::vertical()
-                .id_source("no_ingredient_area")
-                .max_height(if self.potential_potions.is_empty() {
-                    ui.available_height() - 120.0
-                } else {
-                    ui.available_height() / 3.0 })
-                .show(ui, |ui| {
-                    ui.heading("No Ingredients Found - Select One or More Desired Effects");
-                });
-                });
-            } else {
-                ui.group(|ui| {
-                    egui::ScrollArea::vertical()
+                    .id_source("no_ingredient_area")
                     .max_height(if self.potential_potions.is_empty() {
                         ui.available_height() - 120.0
                     } else {
-                        ui.available_height() / 3.0 })
-                        .id_source("ingredient_scroll_area")
-                        .show(ui, |ui| {
+                        ui.available_height() / 3.0
+                    })
+                    .show(ui, |ui| {
+                        ui.heading("No Ingredients Found - Select One or More Desired Effects");
+                    });
+            });
+        } else {
+            ui.group(|ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(if self.potential_potions.is_empty() {
+                        ui.available_height() - 120.0
+                    } else {
+                        ui.available_height() / 3.0
+                    })
+                    .id_source("ingredient_scroll_area")
+                    .show(ui, |ui| {
                         let num_ingredients = self.potential_ingredients.len();
-                    let mut ingredient_selection_changed = false;
-                    for (index, ingredient) in self.potential_ingredients.iter_mut().enumerate() {
-                        let Ok(mut ingredient) = ingredient.try_borrow_mut() else {
-                            // Unable to borrow ingredient so continue to the next one
-                            // It's better than crashing
-                            continue;  
-                        };
-                        if ingredient.ui(ui)
-                            .clicked()
+                        let mut ingredient_selection_changed = false;
+                        for (index, ingredient) in self.potential_ingredients.iter_mut().enumerate()
                         {
-                            ingredient.selected = !ingredient.selected;
-    
-                            ingredient_selection_changed = true;
+                            let Ok(mut ingredient) = ingredient.try_borrow_mut() else {
+                                // Unable to borrow ingredient so continue to the next one
+                                // It's better than crashing
+                                continue;
+                            };
+                            let mut response = ingredient.ui(ui);
+                            if self.ruleset == Ruleset::Morrowind {
+                                if let Some(where_to_buy) =
+                                    where_to_buy(&self.merchants, &ingredient)
+                                {
+                                    response = response.on_hover_text(where_to_buy);
+                                }
+                            }
+                            if response.clicked() {
+                                ingredient.selected = !ingredient.selected;
+
+                                ingredient_selection_changed = true;
+                            }
+
+                            if index != num_ingredients - 1 {
+                                ui.separator();
+                            }
                         }
-        
-                        if index != num_ingredients - 1 {
-                            ui.separator();
+
+                        if ingredient_selection_changed {
+                            // We have changed the selected ingredients, so let's generate potions
+                            self.generate_potions();
                         }
-                    }
-    
-                    if ingredient_selection_changed {
-                        // We have changed the selected ingredients, so let's generate potions
-                        self.generate_potions();
-                    }
-                });
+                    });
             });
         }
     }
@@ -491,16 +884,123 @@ impl App {
                         ui.heading("No Potions Found - Add More Ingredients, Change Desired Effects, or Allow Extra Effects");
                     });
             });
-        }
-        else {
+        } else {
             ui.group(|ui| {
                 egui::ScrollArea::vertical()
                     .id_source("potion_scroll_area")
                     .max_height(ui.available_height() - 10.0)
                     .show(ui, |ui| {
                         let num_potions = self.potential_potions.len();
-                        for (index, potion) in self.potential_potions.iter_mut().enumerate() {
-                            potion.ui(ui);
+                        for (index, (potion, recipe_code)) in self.potential_potions.iter_mut().zip(self.potion_recipe_codes.iter()).enumerate() {
+                            let known_effects = self.only_known_effects.then(|| self.ruleset.known_potion_effects(self.character.alchemy, potion));
+                            match &known_effects {
+                                Some(known_effects) => {
+                                    Potion { effects: known_effects.clone(), ..potion.clone() }.ui(ui);
+                                    let unknown_effects = potion.effects.len() - known_effects.len();
+                                    if unknown_effects > 0 {
+                                        ui.label(format!("Plus {} effects this character can't see yet", unknown_effects));
+                                    }
+                                }
+                                None => {
+                                    potion.ui(ui);
+                                }
+                            }
+                            if self.ruleset == Ruleset::Morrowind {
+                                let mut brewed_potion = self.engine.brew(potion, &self.character);
+                                let cost = self.barter.recipe_cost(&self.character, potion);
+                                if brewed_potion.effects.is_empty() {
+                                    ui.label("Brews as: nothing, this character is too weak an alchemist");
+                                    ui.label(format!("Ingredients cost {} gold", cost));
+                                } else {
+                                    if let Some(known_effects) = &known_effects {
+                                        brewed_potion.effects.retain(|effect| known_effects.contains(&effect.effect));
+                                    }
+                                    let (harmful_effects, beneficial_effects): (Vec<&EffectStrength>, Vec<&EffectStrength>) =
+                                        brewed_potion.effects.iter().partition(|effect| effect.effect.is_harmful());
+                                    ui.horizontal_wrapped(|ui| {
+                                        ui.label(format!(
+                                            "Brews as: {} ({} gold)",
+                                            beneficial_effects.iter().map(|effect| effect.to_string()).join(", "),
+                                            brewed_potion.value
+                                        ));
+                                        if !harmful_effects.is_empty() {
+                                            ui.colored_label(
+                                                ui.visuals().warn_fg_color,
+                                                format!("Harmful: {}", harmful_effects.iter().map(|effect| effect.to_string()).join(", ")),
+                                            );
+                                        }
+                                    });
+                                    let price = self.barter.sell_price(&self.character, brewed_potion.value);
+                                    ui.label(format!(
+                                        "Ingredients cost {} gold, sells for {} gold ({:+} gold profit)",
+                                        cost,
+                                        price,
+                                        i64::from(price) - i64::from(cost)
+                                    ));
+                                }
+                                for ingredient in potion.ingredients.iter().flatten() {
+                                    let Ok(ingredient) = ingredient.try_borrow() else {
+                                        continue; // Unable to borrow ingredient so continuing is better than crashing
+                                    };
+                                    if let Some(where_to_buy) = where_to_buy(&self.merchants, &ingredient) {
+                                        ui.label(format!("{}: {}", ingredient.name, where_to_buy));
+                                    }
+                                }
+                            }
+                            egui::CollapsingHeader::new("⇄ Substitutes")
+                                .id_source(("substitutes", potion.recipe_key()))
+                                .show(ui, |ui| {
+                                    for missing in potion.ingredients.iter().flatten() {
+                                        let Ok(missing_ingredient) = missing.try_borrow() else {
+                                            continue; // Unable to borrow ingredient so continuing is better than crashing
+                                        };
+                                        let owned = |ingredient: &Ingredient| self.inventory.get(&ingredient.id()).is_some_and(|count| *count > 0);
+                                        ui.strong(format!("Instead of {}{}", missing_ingredient.name, if owned(&missing_ingredient) { " (owned)" } else { "" }));
+                                        let substitutions = suggest_substitutes(potion, missing, &self.ingredients);
+                                        if substitutions.is_empty() {
+                                            ui.label("No ingredient adds the same effects");
+                                        }
+                                        // Owned substitutes first, as they're the point of substituting
+                                        let substitutions = substitutions.into_iter().sorted_by_key(|substitution| {
+                                            !substitution.substitute.try_borrow().is_ok_and(|substitute| owned(&substitute))
+                                        });
+                                        for substitution in substitutions.take(MAX_SUBSTITUTES) {
+                                            let Ok(substitute) = substitution.substitute.try_borrow() else {
+                                                continue; // Unable to borrow ingredient so continuing is better than crashing
+                                            };
+                                            let mut changes = vec![format!("keeps {}", substitution.kept.iter().join(", "))];
+                                            if !substitution.lost.is_empty() {
+                                                changes.push(format!("loses {}", substitution.lost.iter().join(", ")));
+                                            }
+                                            if !substitution.new.is_empty() {
+                                                changes.push(format!("adds {}", substitution.new.iter().join(", ")));
+                                            }
+                                            let label = format!("{}{}: {}", substitute.name, if owned(&substitute) { " (owned)" } else { "" }, changes.join("; "));
+                                            if substitution.new.iter().any(|effect| effect.is_harmful()) {
+                                                ui.colored_label(ui.visuals().warn_fg_color, label);
+                                            } else {
+                                                ui.label(label);
+                                            }
+                                        }
+                                    }
+                                });
+                            ui.horizontal(|ui| {
+                                let favourite_text = if self.recipe_book.is_favourite(self.ruleset, potion) { "★ Favourite" } else { "☆ Favourite" };
+                                if ui.button(favourite_text).clicked() {
+                                    self.recipe_book.toggle(self.ruleset, potion);
+                                }
+                                if self.ruleset == Ruleset::Morrowind {
+                                    let on_shopping_list = self.shopping_recipes.iter().any(|(recipe, _)| recipe == &*potion);
+                                    let shopping_text = if on_shopping_list { "🛒 On Shopping List" } else { "🛒 Add to Shopping List" };
+                                    if ui.add_enabled(!on_shopping_list, egui::Button::new(shopping_text)).clicked() {
+                                        self.shopping_recipes.push((potion.clone(), 1));
+                                    }
+                                }
+                                ui.label(format!("Recipe Code: {}", recipe_code));
+                                if ui.button("Copy Code").clicked() {
+                                    ui.output().copied_text = recipe_code.clone();
+                                }
+                            });
                             if index != num_potions - 1 {
                                 ui.separator();
                             }
@@ -510,77 +1010,1204 @@ impl App {
         }
     }
 
-    fn generate_potions(&mut self) {
-        self.filtered_ingredients = self
-            .potential_ingredients
-            .iter()
-            .filter(|potential_ingredient| {
-                let Ok(potential_ingredient) = potential_ingredient.try_borrow() else {
-                    // Unable to borrow ingredient so just return false
-                    // It's better than crashing
-                    return false;  
-                };
-                potential_ingredient.selected
-            })
-            .cloned()
-            .collect();
-        self.potential_potions = create_potential_potions(&self.desired_effects, &self.filtered_ingredients).iter().filter(|potential_potion| {
-                if self.allow_extra_effects {
-                    true
-                } else {
-                        potential_potion.effects.iter().all(|&effect| self.desired_effects.contains(&Some(effect)))
+    /// Decode the pasted recipe code and show the recipe in the ingredient and potion areas
+    /// Links to a query are also accepted and load that query instead
+    fn load_recipe_code(&mut self) {
+        if let Some((_, fragment)) = self.recipe_code_input.trim().split_once('#') {
+            self.recipe_code_error = None;
+            self.apply_deep_link(&DeepLink::from_fragment(fragment));
+            return;
+        }
+
+        match RecipeCode::decode(&self.recipe_code_input, &self.ingredients) {
+            Ok(recipe_code) => {
+                self.recipe_code_error = None;
+                self.load_recipe(recipe_code);
+            }
+            Err(error) => self.recipe_code_error = Some(error.to_string()),
+        }
+    }
+
+    /// Show a recipe in the ingredient and potion areas, selecting only its ingredients
+    fn load_recipe(&mut self, recipe_code: RecipeCode) {
+        let recipe_ingredients = recipe_code.ingredients(&self.ingredients);
+        match recipe_code.query {
+            Some(query) => {
+                self.desired_effects = query.desired_effects;
+                self.allow_extra_effects = query.allow_extra_effects;
+            }
+            None => {
+                // Without a query, ask for the effects of the recipe itself
+                let potion = Potion::new_potion_from_ingredients(
+                    &recipe_ingredients.iter().collect::<Vec<_>>(),
+                );
+                self.desired_effects = [None, None, None, None];
+                for (desired_effect, effect) in
+                    self.desired_effects.iter_mut().zip(potion.effects.iter())
+                {
+                    *desired_effect = Some(*effect);
+                }
+                self.allow_extra_effects = potion.effects.len() > self.desired_effects.len();
+            }
+        }
+        self.previous_effects = self.desired_effects;
+
+        self.potential_ingredients =
+            get_potential_ingredients(&self.desired_effects, &self.ingredients);
+        for recipe_ingredient in recipe_ingredients.iter() {
+            // An ingredient may be part of the recipe without contributing a desired effect
+            if !self
+                .potential_ingredients
+                .iter()
+                .any(|ingredient| Rc::ptr_eq(ingredient, recipe_ingredient))
+            {
+                self.potential_ingredients.push(recipe_ingredient.clone());
+            }
+        }
+        // Select only the ingredients of the recipe
+        for ingredient in self.potential_ingredients.iter() {
+            let is_recipe_ingredient = recipe_ingredients
+                .iter()
+                .any(|recipe_ingredient| Rc::ptr_eq(recipe_ingredient, ingredient));
+            match ingredient.try_borrow_mut() {
+                Ok(mut ingredient) => ingredient.selected = is_recipe_ingredient,
+                Err(_) => continue, // Unable to borrow ingredient so continuing is better than crashing
+            }
+        }
+        // Show the recipe itself, as searching its ingredients would prefer any smaller recipe within it
+        let potion =
+            Potion::new_potion_from_ingredients(&recipe_ingredients.iter().collect::<Vec<_>>());
+        self.filtered_ingredients = recipe_ingredients;
+        self.set_potential_potions(vec![potion]);
+    }
+
+    /// Save `exported` to `path` with the format's extension, or copy it, reporting where it went
+    fn finish_export(
+        &mut self,
+        ui: &mut egui::Ui,
+        export_action: ExportAction,
+        name: &str,
+        path: &str,
+        exported: Result<String, String>,
+    ) {
+        self.export_status = Some(exported.and_then(|exported| match export_action {
+            ExportAction::Save => save_text_file(
+                &format!("{}.{}", path, self.export_format.file_extension()),
+                &exported,
+            ),
+            ExportAction::Copy => {
+                ui.output().copied_text = exported;
+                Ok(format!("Copied {} to the clipboard", name))
+            }
+        }));
+    }
+
+    fn create_recipe_book_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Recipe Book");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.add(egui::TextEdit::singleline(&mut self.recipe_book_path).desired_width(160.0));
+            if ui.button("Export").clicked() {
+                self.recipe_book_status = Some(
+                    self.recipe_book
+                        .export()
+                        .and_then(|exported| save_text_file(&self.recipe_book_path, &exported)),
+                );
+            }
+            if ui.button("Import").clicked() {
+                #[cfg(not(target_arch = "wasm32"))]
+                self.recipe_book_opener.open(&self.recipe_book_path);
+                #[cfg(target_arch = "wasm32")]
+                self.recipe_book_opener.open(ui.ctx(), ".yaml,.yml");
+            }
+        });
+        if let Some(loaded_file) = self.recipe_book_opener.take() {
+            self.recipe_book_status = Some(loaded_file.and_then(|loaded_file| {
+                let data = String::from_utf8_lossy(&loaded_file.bytes);
+                self.recipe_book.import(&data).map(|new_favourites| {
+                    format!(
+                        "Imported {} new favourites from {}",
+                        new_favourites, loaded_file.name
+                    )
+                })
+            }));
+        }
+        match &self.recipe_book_status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+        ui.separator();
+
+        if self.recipe_book.favourites(self.ruleset).next().is_none() {
+            ui.label("Star a generated potion to add it to your recipe book.");
+            return;
+        }
+
+        let mut recipe_to_load = None;
+        let mut recipe_to_remove = None;
+        egui::ScrollArea::vertical()
+            .id_source("recipe_book_scroll_area")
+            .show(ui, |ui| {
+                let ruleset = self.ruleset;
+                for favourite in self.recipe_book.favourites.iter_mut().filter(|favourite| favourite.ruleset == ruleset) {
+                    ui.group(|ui| {
+                        ui.set_width(ui.available_width());
+                        match favourite.potion(&self.ingredients) {
+                            Some(potion) => {
+                                ui.strong(
+                                    potion
+                                        .ingredients
+                                        .iter()
+                                        .flatten()
+                                        .filter_map(|ingredient| ingredient.try_borrow().ok().map(|ingredient| ingredient.name.clone()))
+                                        .join(", "),
+                                );
+                                ui.label(potion.effects.iter().map(|effect| effect.to_string()).join(", "));
+                            }
+                            None => {
+                                ui.strong(favourite.ingredient_ids.join(", "));
+                                ui.colored_label(ui.visuals().warn_fg_color, "Some of these ingredients are not in the loaded ingredient lists");
+                            }
+                        }
+                        ui.add(
+                            egui::TextEdit::multiline(&mut favourite.note)
+                                .desired_rows(1)
+                                .hint_text("Add a note, e.g. \"sell to Ajira\""),
+                        );
+                        ui.horizontal(|ui| {
+                            if ui.button("Load").clicked() {
+                                recipe_to_load = Some(RecipeCode {
+                                    ingredient_ids: favourite.ingredient_ids.clone(),
+                                    query: None,
+                                });
+                            }
+                            if ui.button("Copy Code").clicked() {
+                                ui.output().copied_text = RecipeCode {
+                                    ingredient_ids: favourite.ingredient_ids.clone(),
+                                    query: None,
+                                }
+                                .encode();
+                            }
+                            if ui.button("Remove").clicked() {
+                                recipe_to_remove = Some(favourite.recipe_key());
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(recipe_code) = recipe_to_load {
+            self.load_recipe(recipe_code);
+        }
+        if let Some(recipe_key) = recipe_to_remove {
+            self.recipe_book.remove(self.ruleset, &recipe_key);
+        }
+    }
+
+    fn create_character_panel(&mut self, ui: &mut egui::Ui) {
+        let previous_character = (self.character.clone(), self.engine);
+        self.create_character_stats(ui);
+        if self.min_side_effect_magnitude > 0
+            && previous_character != (self.character.clone(), self.engine)
+        {
+            // How strong the side effects are depends on the character, so some potions may come or go
+            self.generate_potions();
+        }
+    }
+
+    fn create_character_stats(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Character");
+        ui.separator();
+
+        // Only Morrowind saves can be read
+        ui.add_enabled_ui(self.ruleset == Ruleset::Morrowind, |ui| {
+            ui.horizontal(|ui| {
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.save_game_path)
+                        .hint_text("Path to a .ess or .omwsave")
+                        .desired_width(160.0),
+                );
+                if ui.button("Import Save").clicked() {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    self.save_game_opener.open(&self.save_game_path);
+                    #[cfg(target_arch = "wasm32")]
+                    self.save_game_opener.open(ui.ctx(), ".ess,.omwsave");
                 }
             })
-            .sorted_by(|potion_a, potion_b| {
-                potion_a.ingredients.len().cmp(&potion_b.ingredients.len())
-            })
-            .cloned()
-            .collect();
-        let two_ingredient_potions: Vec<&Potion> = self.potential_potions.iter().filter(|potion| potion.ingredients.iter().flatten().count() == 2).collect();
-        let mut three_ingredient_potions: Vec<&Potion> = self.potential_potions.iter().filter(|potion| potion.ingredients.iter().flatten().count() == 3).collect();
-        three_ingredient_potions.retain(|three_ingredient_potion| {
-                // If we find any exact match, it's an old potion
-                for two_ingredient_potion in two_ingredient_potions.iter() {
-                    if three_ingredient_potion.effects.iter().all(|effect| {
-                        two_ingredient_potion.effects.contains(effect)
-                    }) {
-                        return false;
+        });
+        if let Some(loaded_file) = self.save_game_opener.take() {
+            self.save_game_status = Some(loaded_file.and_then(|loaded_file| {
+                let save_game = SaveGame::parse(&loaded_file.bytes)?;
+                Ok(self.import_save_game(&save_game))
+            }));
+        }
+        match &self.save_game_status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+        ui.separator();
+
+        egui::Grid::new("character_stats").num_columns(2).show(ui, |ui| {
+            ui.label("Level");
+            ui.add(egui::DragValue::new(&mut self.character.level).clamp_range(1..=1000));
+            ui.end_row();
+            ui.label("Alchemy");
+            if ui.add(egui::DragValue::new(&mut self.character.alchemy).clamp_range(0..=1000)).changed() && self.only_known_effects {
+                self.generate_potions();
+            }
+            ui.end_row();
+            ui.label("Intelligence");
+            ui.add(egui::DragValue::new(&mut self.character.intelligence).clamp_range(0..=1000));
+            ui.end_row();
+            ui.label("Luck");
+            ui.add(egui::DragValue::new(&mut self.character.luck).clamp_range(0..=1000));
+            ui.end_row();
+            if self.ruleset == Ruleset::Morrowind {
+                // Only Morrowind prices are modelled
+                ui.label("Personality");
+                ui.add(egui::Slider::new(&mut self.character.personality, 0..=100));
+                ui.end_row();
+                ui.label("Mercantile");
+                ui.add(egui::Slider::new(&mut self.character.mercantile, 0..=100));
+                ui.end_row();
+                ui.label("Merchant Disposition");
+                ui.add(egui::Slider::new(&mut self.barter.disposition, 0..=100));
+                ui.end_row();
+            }
+            for apparatus_type in ApparatusType::iter() {
+                ui.label(apparatus_type.to_string());
+                let apparatus = self.character.apparatus.get_mut(apparatus_type);
+                egui::ComboBox::from_id_source(apparatus_type)
+                    .selected_text(apparatus.map_or(String::from("None"), |quality| quality.to_string()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(apparatus, None, "None");
+                        for quality in ApparatusQuality::iter() {
+                            ui.selectable_value(apparatus, Some(quality), quality.to_string());
+                        }
+                    });
+                ui.end_row();
+            }
+            ui.label("Potion Strength");
+            let potion_strength = format!("{:.1}", self.ruleset.potion_strength(&self.character));
+            if self.ruleset == Ruleset::Morrowind {
+                ui.label(potion_strength);
+            } else {
+                ui.label(format!("{} (guide only)", potion_strength))
+                    .on_hover_text("Magnitudes, durations and values are only worked out for Morrowind potions, so this only shows how strongly the character brews");
+            }
+            ui.end_row();
+            ui.label("Visible Effects");
+            ui.label(self.ruleset.visible_effects(self.character.alchemy).to_string());
+            ui.end_row();
+            ui.label("Hide Unknown Effects");
+            if ui.checkbox(&mut self.only_known_effects, "").on_hover_text("Only offer and show the effects this character can see on an ingredient").changed() {
+                self.generate_potions();
+            }
+            ui.end_row();
+            if self.ruleset == Ruleset::Morrowind {
+                // OpenMW and Morrowind.exe round and price potions differently
+                ui.label("Engine");
+                egui::ComboBox::from_id_source("engine")
+                    .selected_text(self.engine.to_string())
+                    .show_ui(ui, |ui| {
+                        for engine in Engine::iter() {
+                            ui.selectable_value(&mut self.engine, engine, engine.to_string());
+                        }
+                    });
+                ui.end_row();
+                ui.label("Success Chance");
+                ui.label(format!("{:.0}%", 100.0 * self.engine.success_chance(&self.character)));
+                ui.end_row();
+            }
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.strong(format!("Owned Ingredients ({})", self.inventory.len()));
+            if !self.inventory.is_empty() && ui.button("Clear").clicked() {
+                self.inventory.clear();
+            }
+        });
+        if self.inventory.is_empty() {
+            ui.label("Import a save to see which ingredients you own. Owned ingredients are selected automatically.");
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .id_source("inventory_scroll_area")
+            .show(ui, |ui| {
+                for ingredient in self.ingredients.iter() {
+                    let Ok(ingredient) = ingredient.try_borrow() else {
+                        continue; // Unable to borrow ingredient so continuing is better than crashing
+                    };
+                    if let Some(count) = self.inventory.get(&ingredient.id()) {
+                        ui.label(format!("{} × {}", ingredient.name, count));
                     }
                 }
+            });
+    }
+
+    fn create_completion(&mut self, ui: &mut egui::Ui) {
+        ui.label("Lock the ingredients already decided on, then find the best one or two to add for the desired effects.");
+        egui::Grid::new("completion_inputs")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (index, locked) in self.completion_locked.iter_mut().enumerate() {
+                    ui.label(format!("Locked Ingredient {}", index + 1));
+                    let selected_name = self
+                        .ingredients
+                        .iter()
+                        .filter_map(|ingredient| ingredient.try_borrow().ok())
+                        .find(|ingredient| locked.as_ref() == Some(&ingredient.id()))
+                        .map_or(String::from("None"), |ingredient| ingredient.name.clone());
+                    egui::ComboBox::from_id_source(("completion_locked", index))
+                        .selected_text(selected_name)
+                        .width(180.0)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(locked, None, "None");
+                            for ingredient in self.ingredients.iter() {
+                                let Ok(ingredient) = ingredient.try_borrow() else {
+                                    continue; // Unable to borrow ingredient so continuing is better than crashing
+                                };
+                                ui.selectable_value(
+                                    locked,
+                                    Some(ingredient.id()),
+                                    &ingredient.name,
+                                );
+                            }
+                        });
+                    ui.end_row();
+                }
+                ui.label("Rank By");
+                egui::ComboBox::from_id_source("completion_ranking")
+                    .selected_text(self.completion_ranking.to_string())
+                    .show_ui(ui, |ui| {
+                        for ranking in CompletionRanking::iter() {
+                            // Only Morrowind potions are brewed to find their value
+                            if ranking != CompletionRanking::Value
+                                || self.ruleset == Ruleset::Morrowind
+                            {
+                                ui.selectable_value(
+                                    &mut self.completion_ranking,
+                                    ranking,
+                                    ranking.to_string(),
+                                );
+                            }
+                        }
+                    });
+                ui.end_row();
+            });
+        if ui.button("Complete").clicked() {
+            let locked = self
+                .ingredients
+                .iter()
+                .filter(|ingredient| {
+                    ingredient.try_borrow().is_ok_and(|ingredient| {
+                        self.completion_locked.contains(&Some(ingredient.id()))
+                    })
+                })
+                .cloned()
+                .collect();
+            self.completions = Some(
+                CompletionQuery {
+                    locked,
+                    desired_effects: self.desired_effects,
+                    max_ingredients: self.ruleset.max_ingredients(),
+                    ranking: self.completion_ranking,
+                    engine: self.engine,
+                    character: self.character.clone(),
+                    barter: self.barter,
+                }
+                .complete(&self.ingredients),
+            );
+        }
+        ui.separator();
+
+        let Some(completions) = &self.completions else {
+            return;
+        };
+        if completions.is_empty() {
+            ui.label("No one or two ingredients complete a potion with the desired effects.");
+            return;
+        }
+        let mut shown_recipe = None;
+        egui::ScrollArea::vertical()
+            .id_source("completion_scroll_area")
+            .max_height(400.0)
+            .show(ui, |ui| {
+                egui::Grid::new("completions")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Recipe");
+                        ui.strong("Effects");
+                        // Only Morrowind prices are modelled, elsewhere the ingredients' base value is all there is
+                        ui.strong(if self.ruleset == Ruleset::Morrowind {
+                            "Cost"
+                        } else {
+                            "Base Value"
+                        });
+                        ui.label("");
+                        ui.end_row();
+                        for potion in completions.iter().take(MAX_COMPLETIONS) {
+                            ui.label(potion.ingredient_names().join(" + "));
+                            ui.label(potion.effects.iter().join(", "));
+                            if self.ruleset == Ruleset::Morrowind {
+                                ui.label(format!(
+                                    "{} gold",
+                                    self.barter.recipe_cost(&self.character, potion)
+                                ));
+                            } else {
+                                ui.label(potion.total_value().to_string());
+                            }
+                            if ui.button("Show").clicked() {
+                                shown_recipe = Some(RecipeCode::from_potion(potion, None));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(recipe_code) = shown_recipe {
+            self.load_recipe(recipe_code);
+        }
+    }
 
-                true
+    fn create_kit_builder(&mut self, ui: &mut egui::Ui) {
+        ui.label("Find the fewest or cheapest ingredient types that brew every potion of a kit.");
+        let effects = self.selectable_effects();
+        let mut removed_target = None;
+        egui::Grid::new("kit_targets")
+            .num_columns(5)
+            .show(ui, |ui| {
+                for (target_index, target) in self.kit_targets.iter_mut().enumerate() {
+                    for (effect_index, effect) in target.iter_mut().enumerate() {
+                        egui::ComboBox::from_id_source(("kit_target", target_index, effect_index))
+                            .selected_text(
+                                effect.map_or(String::from("None"), |effect| effect.to_string()),
+                            )
+                            .width(140.0)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(effect, None, "None");
+                                for available_effect in effects.iter() {
+                                    ui.selectable_value(
+                                        effect,
+                                        Some(*available_effect),
+                                        available_effect.to_string(),
+                                    );
+                                }
+                            });
+                    }
+                    if ui.button("Remove").clicked() {
+                        removed_target = Some(target_index);
+                    }
+                    ui.end_row();
+                }
             });
-        let mut four_ingredient_potions: Vec<&Potion> = self.potential_potions.iter().filter(|potion| potion.ingredients.iter().flatten().count() == 4).collect();
-        four_ingredient_potions.retain(|four_ingredient_potion| {
-                for two_ingredient_potion in two_ingredient_potions.iter() {
-                    if four_ingredient_potion.effects.iter().all(|effect| {
-                        two_ingredient_potion.effects.contains(effect)
-                    }) {
-                        return false;
+        if let Some(target_index) = removed_target {
+            self.kit_targets.remove(target_index);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add Potion").clicked() {
+                self.kit_targets.push([None; 4]);
+            }
+            egui::ComboBox::from_id_source("kit_objective")
+                .selected_text(self.kit_objective.to_string())
+                .show_ui(ui, |ui| {
+                    for objective in KitObjective::iter() {
+                        ui.selectable_value(
+                            &mut self.kit_objective,
+                            objective,
+                            objective.to_string(),
+                        );
+                    }
+                });
+            ui.checkbox(&mut self.kit_allow_extra_effects, "Allow Extra Effects");
+            if ui.button("Find Kit").clicked() {
+                self.kit = Some(
+                    KitQuery {
+                        targets: self.kit_targets.clone(),
+                        objective: self.kit_objective,
+                        max_ingredients: self.ruleset.max_ingredients(),
+                        allow_extra_effects: self.kit_allow_extra_effects,
+                    }
+                    .solve(&self.ingredients),
+                );
+            }
+        });
+        ui.separator();
+
+        let mut shown_recipe = None;
+        match &self.kit {
+            None => {}
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            Some(Ok(kit)) => {
+                let names: Vec<String> = kit
+                    .ingredients
+                    .iter()
+                    .filter_map(|ingredient| Some(ingredient.try_borrow().ok()?.name.clone()))
+                    .collect();
+                ui.strong(format!("{} ingredients: {}", names.len(), names.join(", ")));
+                if !kit.exhaustive {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "Too many possible kits to try them all, so a better kit may exist",
+                    );
+                }
+                egui::Grid::new("kit_recipes")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for recipe in kit.recipes.iter() {
+                            ui.label(recipe.effects.iter().join(", "));
+                            ui.label(recipe.ingredient_names().join(" + "));
+                            if ui.button("Show").clicked() {
+                                shown_recipe = Some(RecipeCode::from_potion(recipe, None));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+        }
+        if let Some(recipe_code) = shown_recipe {
+            self.load_recipe(recipe_code);
+        }
+    }
+
+    fn create_shopping_list(&mut self, ui: &mut egui::Ui) {
+        if self.shopping_recipes.is_empty() {
+            ui.label("Add potions to the shopping list to see what to buy for them.");
+            return;
+        }
+
+        ui.strong("Potions to brew");
+        let mut removed_recipe = None;
+        egui::Grid::new("shopping_recipes")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (index, (recipe, count)) in self.shopping_recipes.iter_mut().enumerate() {
+                    ui.label(recipe.ingredient_names().join(" + "));
+                    ui.add(egui::DragValue::new(count).clamp_range(1..=1000));
+                    if ui.button("Remove").clicked() {
+                        removed_recipe = Some(index);
                     }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = removed_recipe {
+            self.shopping_recipes.remove(index);
+        }
+        ui.separator();
+
+        let merchants = self.merchants.as_deref().unwrap_or_default();
+        let shopping_list = ShoppingList::new(
+            &self.shopping_recipes,
+            &self.inventory,
+            &self.barter,
+            &self.character,
+            merchants,
+        );
+        egui::ScrollArea::vertical()
+            .id_source("shopping_list_scroll_area")
+            .max_height(400.0)
+            .show(ui, |ui| {
+                egui::Grid::new("shopping_list")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Ingredient");
+                        ui.strong("To Buy");
+                        ui.strong("Cost");
+                        ui.strong("Weight");
+                        ui.strong("Where");
+                        ui.end_row();
+                        for item in shopping_list.items.iter() {
+                            ui.label(&item.name);
+                            ui.label(format!(
+                                "{} (have {} of {})",
+                                item.to_buy, item.owned, item.needed
+                            ));
+                            ui.label(format!("{} gold", item.cost));
+                            ui.label(format!("{:.1}", item.weight));
+                            let mut places = item.merchants.clone();
+                            if !item.found.is_empty() {
+                                places.push(item.found.clone());
+                            }
+                            ui.label(places.join("; "));
+                            ui.end_row();
+                        }
+                    });
+            });
+        ui.separator();
+        ui.strong(format!(
+            "Total: {} gold, {:.1} weight",
+            shopping_list.total_cost, shopping_list.total_weight
+        ));
+        ui.horizontal(|ui| {
+            if ui.button("Save as Text").clicked() {
+                self.export_status = Some(save_text_file(
+                    "shopping_list.txt",
+                    &shopping_list.to_string(),
+                ));
+            }
+            if ui.button("Copy as Text").clicked() {
+                ui.output().copied_text = shopping_list.to_string();
+                self.export_status =
+                    Some(Ok(String::from("Copied shopping list to the clipboard")));
+            }
+        });
+    }
+
+    fn create_selling_planner(&mut self, ui: &mut egui::Ui) {
+        ui.label("Check which merchants can afford a batch of potions, at the prices from the Character panel.");
+        let merchants = match &self.merchants {
+            Ok(merchants) if merchants.is_empty() => {
+                ui.label("No merchants loaded. List them in a merchant list named under merchant_lists in config.yaml.");
+                return;
+            }
+            Ok(merchants) => merchants,
+            Err(error) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+                return;
+            }
+        };
+        if self.potential_potions.is_empty() {
+            ui.label("Find some potions first.");
+            return;
+        }
+
+        self.selling_potion = self.selling_potion.min(self.potential_potions.len() - 1);
+        egui::Grid::new("selling_inputs")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Potion");
+                egui::ComboBox::from_id_source("selling_potion")
+                    .selected_text(
+                        self.potential_potions[self.selling_potion]
+                            .ingredient_names()
+                            .join(" + "),
+                    )
+                    .show_ui(ui, |ui| {
+                        for (index, potion) in self.potential_potions.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut self.selling_potion,
+                                index,
+                                potion.ingredient_names().join(" + "),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.label("Potions");
+                ui.add(egui::DragValue::new(&mut self.selling_count).clamp_range(1..=10000));
+                ui.end_row();
+            });
+        ui.separator();
+
+        let brewed_potion = self.engine.brew(
+            &self.potential_potions[self.selling_potion],
+            &self.character,
+        );
+        let batch_value =
+            self.barter.sell_price(&self.character, brewed_potion.value) * self.selling_count;
+        ui.strong(format!("The batch sells for {} gold", batch_value));
+        egui::Grid::new("selling_merchants")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for merchant in merchants
+                    .iter()
+                    .sorted_by_key(|merchant| std::cmp::Reverse(merchant.barter_gold))
+                {
+                    ui.label(merchant.to_string());
+                    ui.label(format!("{} gold", merchant.barter_gold));
+                    match merchant.shortfall(batch_value) {
+                        Some(shortfall) => ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            format!("{} gold short, sell over several visits", shortfall),
+                        ),
+                        None => ui.label("Can pay for it all"),
+                    };
+                    ui.end_row();
                 }
+            });
+    }
 
-                true
+    fn create_leveling_planner(&mut self, ui: &mut egui::Ui) {
+        ui.label("Plan the cheapest way to raise Alchemy by brewing, using the stats and apparatus from the Character panel.");
+        egui::Grid::new("leveling_inputs")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Current Alchemy");
+                ui.add(egui::DragValue::new(&mut self.character.alchemy).clamp_range(0..=1000));
+                ui.end_row();
+                ui.label("Target Alchemy");
+                ui.add(egui::DragValue::new(&mut self.target_alchemy).clamp_range(0..=100));
+                ui.end_row();
+                ui.label("Gold");
+                ui.add(egui::DragValue::new(&mut self.leveling_gold).speed(10.0));
+                ui.end_row();
+                ui.label("Skill Type");
+                egui::ComboBox::from_id_source("skill_type")
+                    .selected_text(self.skill_type.to_string())
+                    .show_ui(ui, |ui| {
+                        for skill_type in SkillType::iter() {
+                            ui.selectable_value(
+                                &mut self.skill_type,
+                                skill_type,
+                                skill_type.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.label("Magic Specialization");
+                ui.checkbox(&mut self.specialized, "");
+                ui.end_row();
             });
-        four_ingredient_potions.retain(|four_ingredient_potion| {
-                for three_ingredient_potion in three_ingredient_potions.iter() {
-                    if four_ingredient_potion.effects.iter().all(|effect| {
-                        three_ingredient_potion.effects.contains(effect)
-                    }) {
-                        return false;
+        if ui.button("Plan").clicked() {
+            self.leveling_plan = Some(
+                LevelingQuery {
+                    engine: self.engine,
+                    character: self.character.clone(),
+                    target_alchemy: self.target_alchemy,
+                    gold: self.leveling_gold,
+                    barter: self.barter,
+                    skill_type: self.skill_type,
+                    specialized: self.specialized,
+                }
+                .plan(&self.ingredients),
+            );
+        }
+        ui.separator();
+
+        match &self.leveling_plan {
+            None => {}
+            Some(None) => {
+                ui.colored_label(ui.visuals().error_fg_color, "No recipe brews a potion at this Alchemy. Check the mortar and pestle in the Character panel.");
+            }
+            Some(Some(plan)) => {
+                ui.strong("Cheapest recipes");
+                for recipe in plan.recipes.iter() {
+                    ui.label(format!(
+                        "{} ({} gold)",
+                        recipe.ingredient_names().join(" + "),
+                        self.barter.recipe_cost(&self.character, recipe)
+                    ));
+                }
+                ui.separator();
+                egui::Grid::new("leveling_plan")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Brews");
+                        ui.label(plan.brews.to_string());
+                        ui.end_row();
+                        ui.label("Expected Failures");
+                        ui.label(plan.failures.to_string());
+                        ui.end_row();
+                        ui.label("Ingredient Cost");
+                        ui.label(format!("{} gold", plan.total_cost));
+                        ui.end_row();
+                        ui.label("Reaches Alchemy");
+                        ui.label(plan.reached_alchemy.to_string());
+                        ui.end_row();
+                    });
+                if plan.reached_alchemy < self.target_alchemy {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("The gold runs out at Alchemy {}", plan.reached_alchemy),
+                    );
+                }
+            }
+        }
+    }
+
+    fn create_batch_planner(&mut self, ui: &mut egui::Ui) {
+        ui.label("Plan how many of which potions to brew from all of your owned ingredients, using the stats, apparatus and prices from the Character panel.");
+        if self.inventory.is_empty() {
+            ui.label("Import a save to see which ingredients you own.");
+            return;
+        }
+        egui::Grid::new("batch_inputs")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Most");
+                let mut effect = match self.batch_objective {
+                    BatchObjective::SaleValue => None,
+                    BatchObjective::EffectCount(effect) => Some(effect),
+                };
+                egui::ComboBox::from_id_source("batch_objective")
+                    .selected_text(self.batch_objective.to_string())
+                    .width(180.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut effect,
+                            None,
+                            BatchObjective::SaleValue.to_string(),
+                        );
+                        for available_effect in self.selectable_effects() {
+                            ui.selectable_value(
+                                &mut effect,
+                                Some(available_effect),
+                                BatchObjective::EffectCount(available_effect).to_string(),
+                            );
+                        }
+                    });
+                self.batch_objective =
+                    effect.map_or(BatchObjective::SaleValue, BatchObjective::EffectCount);
+                ui.end_row();
+                ui.label("Weight Limit");
+                ui.horizontal(|ui| {
+                    let mut limit_weight = self.batch_max_weight.is_some();
+                    ui.checkbox(&mut limit_weight, "");
+                    match (limit_weight, &mut self.batch_max_weight) {
+                        (true, Some(max_weight)) => {
+                            ui.add(egui::DragValue::new(max_weight).clamp_range(0.0..=10000.0));
+                        }
+                        (true, None) => self.batch_max_weight = Some(100.0),
+                        (false, _) => self.batch_max_weight = None,
                     }
+                });
+                ui.end_row();
+            });
+        if ui.button("Plan").clicked() {
+            self.batch_plan = Some(
+                BatchQuery {
+                    engine: self.engine,
+                    character: self.character.clone(),
+                    barter: self.barter,
+                    stock: self.inventory.clone(),
+                    objective: self.batch_objective,
+                    max_weight: self.batch_max_weight,
+                    max_ingredients: self.ruleset.max_ingredients(),
                 }
+                .plan(&self.ingredients),
+            );
+        }
+        ui.separator();
+
+        let Some(plan) = &self.batch_plan else {
+            return;
+        };
+        ui.strong(format!(
+            "{} potions selling for {} gold, from {:.1} weight of ingredients",
+            plan.potions, plan.total_value, plan.weight
+        ));
+        egui::ScrollArea::vertical()
+            .id_source("batch_plan_scroll_area")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("batch_brews")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Recipe");
+                        ui.strong("Potions");
+                        ui.strong("Sells For");
+                        ui.end_row();
+                        for brew in plan.brews.iter() {
+                            ui.label(brew.recipe.ingredient_names().join(" + "));
+                            ui.label(brew.count.to_string());
+                            ui.label(format!("{} gold each", brew.price));
+                            ui.end_row();
+                        }
+                    });
+            });
+        if !plan.leftover.is_empty() {
+            ui.separator();
+            ui.strong("Left Over");
+            let leftover: Vec<String> = self
+                .ingredients
+                .iter()
+                .filter_map(|ingredient| {
+                    let ingredient = ingredient.try_borrow().ok()?;
+                    let count = plan.leftover.get(&ingredient.id())?;
+                    Some(format!("{} × {}", ingredient.name, count))
+                })
+                .collect();
+            ui.label(leftover.join(", "));
+        }
+    }
+
+    fn create_intelligence_loop(&mut self, ui: &mut egui::Ui) {
+        ui.label("Brew Fortify Intelligence potions and drink each one to brew the next one stronger, using the stats and apparatus from the Character panel.");
+        ui.horizontal(|ui| {
+            ui.strong("Ingredients");
+            if !self.inventory.is_empty() && ui.button("Use Owned").clicked() {
+                self.intelligence_loop_stock = self.inventory.clone();
+            }
+        });
+        egui::Grid::new("intelligence_loop_stock").num_columns(2).show(ui, |ui| {
+            for ingredient in self.ingredients.iter() {
+                let Ok(ingredient) = ingredient.try_borrow() else {
+                    continue; // Unable to borrow ingredient so continuing is better than crashing
+                };
+                if !ingredient.effects.contains(&Some(Effect::FortifyIntelligence)) {
+                    continue;
+                }
+                ui.label(&ingredient.name);
+                ui.add(egui::DragValue::new(self.intelligence_loop_stock.entry(ingredient.id()).or_insert(0)).clamp_range(0..=10000));
+                ui.end_row();
+            }
+            ui.label("Stop when strength gains less than");
+            ui.add(egui::DragValue::new(&mut self.min_strength_gain).speed(0.1).clamp_range(0.0..=100.0));
+            ui.end_row();
+            ui.label("Seconds between potions");
+            ui.add(egui::DragValue::new(&mut self.seconds_per_potion).clamp_range(0..=3600))
+                .on_hover_text("Game time outside of menus from drinking one potion to drinking the next, as menus pause the game");
+            ui.end_row();
+        });
+        if ui.button("Simulate").clicked() {
+            self.intelligence_loop_result = Some(
+                IntelligenceLoop {
+                    engine: self.engine,
+                    character: self.character.clone(),
+                    stock: self.intelligence_loop_stock.clone(),
+                    min_strength_gain: self.min_strength_gain,
+                    seconds_per_potion: self.seconds_per_potion,
+                }
+                .simulate(&self.ingredients),
+            );
+        }
+        ui.separator();
+
+        let Some(result) = &self.intelligence_loop_result else {
+            return;
+        };
+        let end = match result.end {
+            IntelligenceLoopEnd::OutOfIngredients => "Ran out of ingredients",
+            IntelligenceLoopEnd::Flattened => {
+                "Stopped as each potion barely adds to the next, or wears off before it"
+            }
+            IntelligenceLoopEnd::IterationLimit => {
+                "Still gaining, stopped after the most potions simulated"
+            }
+        };
+        ui.label(format!(
+            "{} after {} potions, with Intelligence {}",
+            end,
+            result.steps.len(),
+            result.final_intelligence
+        ));
+        if result.steps.is_empty() {
+            return;
+        }
+
+        let intelligence: PlotPoints = result
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| [index as f64, f64::from(step.intelligence)])
+            .collect();
+        let strength: PlotPoints = result
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| [index as f64, f64::from(step.strength)])
+            .collect();
+        Plot::new("intelligence_loop_plot")
+            .height(200.0)
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(intelligence).name("Intelligence"));
+                plot_ui.line(Line::new(strength).name("Potion Strength"));
+            });
+        egui::ScrollArea::vertical()
+            .id_source("intelligence_loop_steps")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                egui::Grid::new("intelligence_loop_steps_grid")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Recipe");
+                        ui.strong("Intelligence");
+                        ui.strong("Strength");
+                        ui.strong("Fortify");
+                        ui.strong("Success");
+                        ui.end_row();
+                        for step in result.steps.iter() {
+                            ui.label(step.recipe.ingredient_names().join(" + "));
+                            ui.label(step.intelligence.to_string());
+                            ui.label(format!("{:.1}", step.strength));
+                            ui.label(format!("{} pts for {} secs", step.magnitude, step.duration));
+                            ui.label(format!("{:.0}%", 100.0 * step.success_chance));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn create_effect_matrix(&mut self, ui: &mut egui::Ui) {
+        ui.label("How many raw ingredient combinations make both effects of each pair. Click a count to search for potions with the two effects, which leaves out larger combinations whose effects a smaller recipe already has.");
+        let matrix = self.effect_matrix.get_or_insert_with(|| {
+            EffectMatrix::new(&self.ingredients, self.ruleset.max_ingredients())
+        });
+        let mut clicked_pair = None;
+        egui::ScrollArea::both()
+            .id_source("effect_matrix_scroll_area")
+            .max_height(500.0)
+            .show(ui, |ui| {
+                egui::Grid::new("effect_matrix")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        // Effect names are too long for column headers, so columns are numbered
+                        ui.label("");
+                        for (index, effect) in matrix.effects.iter().enumerate() {
+                            ui.strong(format!("{}", index + 1))
+                                .on_hover_text(effect.to_string());
+                        }
+                        ui.end_row();
+                        for (index, &effect_1) in matrix.effects.iter().enumerate() {
+                            ui.strong(format!("{} {}", index + 1, effect_1));
+                            for &effect_2 in matrix.effects.iter() {
+                                let count = matrix.count(effect_1, effect_2);
+                                if count == 0 {
+                                    ui.weak("·");
+                                    continue;
+                                }
+                                let hover_text = if effect_1 == effect_2 {
+                                    format!("{}: {} raw ingredient combinations", effect_1, count)
+                                } else {
+                                    format!(
+                                        "{} + {}: {} raw ingredient combinations",
+                                        effect_1, effect_2, count
+                                    )
+                                };
+                                if ui
+                                    .small_button(count.to_string())
+                                    .on_hover_text(hover_text)
+                                    .clicked()
+                                {
+                                    clicked_pair = Some((effect_1, effect_2));
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
 
-                true
+        if let Some((effect_1, effect_2)) = clicked_pair {
+            // Select every ingredient and allow extra effects, so the query can use any combination that was counted
+            self.apply_deep_link(&DeepLink {
+                ruleset: self.ruleset,
+                desired_effects: [
+                    Some(effect_1),
+                    (effect_1 != effect_2).then_some(effect_2),
+                    None,
+                    None,
+                ],
+                allow_extra_effects: true,
+                selected_ingredient_ids: self
+                    .ingredients
+                    .iter()
+                    .filter_map(|ingredient| ingredient.try_borrow().ok())
+                    .map(|ingredient| ingredient.id())
+                    .collect(),
+                ..Default::default()
             });
-        self.potential_potions = {
-                let mut t: Vec<Potion> = Vec::new();
-                t.append(&mut two_ingredient_potions.iter().cloned().cloned().collect());
-                t.append(&mut three_ingredient_potions.iter().cloned().cloned().collect());
-                t.append(&mut four_ingredient_potions.iter().cloned().cloned().collect());
+        }
+    }
+
+    /// Use the stats, apparatus and ingredients from a save game
+    /// Returns a message describing what was imported
+    fn import_save_game(&mut self, save_game: &SaveGame) -> String {
+        let ingredient_counts = save_game.ingredient_counts(&self.ingredients);
+        self.character = save_game.character.clone();
+        self.inventory = ingredient_counts.counts;
+        self.select_owned_ingredients();
+
+        let mut status = format!(
+            "Imported {} with {} kinds of ingredient",
+            save_game.player_name,
+            self.inventory.len()
+        );
+        if !ingredient_counts.unrecognised.is_empty() {
+            status.push_str(&format!(
+                ". Not in the loaded ingredient lists: {}",
+                ingredient_counts.unrecognised.join(", ")
+            ));
+        }
+        status
+    }
 
-                t
+    /// Select the potential ingredients we own and unselect the rest
+    fn select_owned_ingredients(&mut self) {
+        for ingredient in self.potential_ingredients.iter() {
+            match ingredient.try_borrow_mut() {
+                Ok(mut ingredient) => {
+                    ingredient.selected = self.inventory.contains_key(&ingredient.id())
+                }
+                Err(_) => continue, // Unable to borrow ingredient so continuing is better than crashing
             }
+        }
+        // We have changed the selected ingredients, so let's generate potions
+        self.generate_potions();
+    }
+
+    fn generate_potions(&mut self) {
+        self.filtered_ingredients = self
+            .potential_ingredients
+            .iter()
+            .filter(|potential_ingredient| {
+                let Ok(potential_ingredient) = potential_ingredient.try_borrow() else {
+                    // Unable to borrow ingredient so just return false
+                    // It's better than crashing
+                    return false;
+                };
+                potential_ingredient.selected
+            })
+            .cloned()
+            .collect();
+        let mut potions = PotionQuery {
+            desired_effects: self.desired_effects,
+            excluded_effects: self.excluded_effects,
+            allow_extra_effects: self.allow_extra_effects,
+            max_ingredients: self.ruleset.max_ingredients(),
+            side_effect_threshold: (self.ruleset == Ruleset::Morrowind
+                && self.min_side_effect_magnitude > 0)
+                .then(|| SideEffectThreshold {
+                    engine: self.engine,
+                    character: self.character.clone(),
+                    min_magnitude: self.min_side_effect_magnitude,
+                }),
+        }
+        .find_potions(&self.filtered_ingredients);
+        if self.only_known_effects {
+            // A character can't aim for an effect they can't see, even when the potion would have it
+            potions.retain(|potion| {
+                let known_effects = self
+                    .ruleset
+                    .known_potion_effects(self.character.alchemy, potion);
+                self.desired_effects
+                    .iter()
+                    .flatten()
+                    .all(|effect| known_effects.contains(effect))
+            });
+        }
+        self.set_potential_potions(potions);
+    }
+
+    fn set_potential_potions(&mut self, potions: Vec<Potion>) {
+        let query = RecipeQuery {
+            desired_effects: self.desired_effects,
+            allow_extra_effects: self.allow_extra_effects,
+        };
+        self.potion_recipe_codes = potions
+            .iter()
+            .map(|potion| RecipeCode::from_potion(potion, Some(query.clone())).encode())
+            .collect();
+        self.potential_potions = potions;
     }
 
     fn create_effect_dropdown(&mut self, ui: &mut egui::Ui, label: &str, effect_index: usize) {
@@ -595,7 +2222,7 @@ impl App {
                 .width(160.0)
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut self.desired_effects[effect_index], None, "None");
-                    for effect in Effect::effects_list().iter() {
+                    for effect in self.selectable_effects().iter() {
                         ui.selectable_value(
                             &mut self.desired_effects[effect_index],
                             Some(*effect),
@@ -606,72 +2233,136 @@ impl App {
         });
         ui.end_row();
     }
-}
 
-fn get_potential_ingredients(
-    desired_effects: &[Option<Effect>; 4],
-    ingredients: &[Rc<RefCell<Ingredient>>],
-) -> Vec<Rc<RefCell<Ingredient>>> {
-    let desired_effects: Vec<&Effect> = desired_effects.iter().flatten().collect();
-    let potential_ingredients: Vec<Rc<RefCell<Ingredient>>> = ingredients
-        .iter()
-        .filter(|ingredient| {
-            // filter the ingredients iterator
-            let Ok(ingredient) = ingredient.try_borrow() else {
-                // Skip ingredient by returning false if we are unable to borrow
-                return false;
-            };
-            ingredient
-                .effects // get the effects array
-                .iter() // and grab an iterator to that
-                .flatten() // flatten to get a new iterator, removing any None variant, and ripping out the Effect from Option<Effect>
-                .filter(|ingredient_effect| { // filter the flattened iterator of the ingredient's effects
-                    desired_effects.contains(ingredient_effect) // If the current ingredient_effect is contained in the desired_effects, we have a match for the filter
-                })
-                .count() // Count the number of effects
-                > 0 // If we have more than 0 matched effects, this ingredient can be used to make a potion with at least one desired effect
-        })
-        .cloned()
-        .collect();
-    potential_ingredients
-}
+    /// The effects to offer in dropdowns, only those the character can see if unknown ones are hidden
+    fn selectable_effects(&self) -> Vec<Effect> {
+        if self.only_known_effects {
+            self.ruleset
+                .known_effects(self.character.alchemy, &self.ingredients)
+        } else {
+            self.ruleset.effects()
+        }
+    }
 
-fn create_potential_potions(
-    desired_effects: &[Option<Effect>; 4],
-    potential_ingredients: &[Rc<RefCell<Ingredient>>],
-) -> Vec<Potion> {
-    // Convert the user input, containing possible None variants, into a Vector of &Effect (removing None variants)
-    let desired_effects: Vec<&Effect> = desired_effects.iter().flatten().collect();
-    let mut potions: Vec<Potion> = Vec::new();
-
-    // For combinations of 2, 3, and 4 ingredients
-    for i in 2..=4 {
-        let mut potential_potions: Vec<Potion> = potential_ingredients
-            .iter() // iterate over the ingredients // Clone to remove the reference
-            .combinations(i) // Create combinations of ingredients
-            .filter_map(|ingredient_combo| {
-                // create a new potion from the ingredient_combo
-                let potential_potion =
-                    Potion::new_potion_from_ingredients(ingredient_combo.as_slice());
-
-                // Get the resulting effects of the potential_potion
-                let potential_potion_effects = &potential_potion.effects;
-
-                // if all of the desired effects are contained within the potential_potion_effects
-                if desired_effects
-                    .iter()
-                    .all(|desired_effect| potential_potion_effects.contains(desired_effect))
-                {
-                    // return the potential_potion
-                    Some(potential_potion)
-                } else {
-                    None
-                }
+    fn create_excluded_effect_dropdown(&mut self, ui: &mut egui::Ui, effect_index: usize) {
+        egui::ComboBox::from_id_source(format!("Excluded Effect {}", effect_index + 1))
+            .selected_text(if let Some(effect) = self.excluded_effects[effect_index] {
+                effect.to_string()
+            } else {
+                String::from("None")
             })
-            .collect();
+            .width(160.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.excluded_effects[effect_index], None, "None");
+                for effect in self.selectable_effects().iter() {
+                    ui.selectable_value(
+                        &mut self.excluded_effects[effect_index],
+                        Some(*effect),
+                        effect.to_string(),
+                    );
+                }
+            });
+    }
+
+    /// Load the current game's ingredients
+    /// If the configured lists or game files can't be read, use the bundled Morrowind ingredients
+    /// and say why, rather than stopping the tool
+    fn reload_ingredients(&mut self) {
+        match create_ingredients(self.ruleset) {
+            Ok(ingredients) => {
+                self.ingredients = ingredients;
+                self.ingredient_error = None;
+            }
+            Err(error) => {
+                self.ingredients = Ruleset::Morrowind.bundled_ingredients();
+                self.ingredient_error = Some(format!(
+                    "{}. Using the bundled Morrowind ingredients instead.",
+                    error
+                ));
+            }
+        }
+    }
+
+    /// Replace the current query with the one described by a deep link
+    fn apply_deep_link(&mut self, deep_link: &DeepLink) {
+        if deep_link.ruleset != self.ruleset {
+            self.ruleset = deep_link.ruleset;
+            self.reload_ingredients();
+            // The recipes were made from the other game's ingredients
+            self.shopping_recipes.clear();
+            self.kit = None;
+            self.batch_plan = None;
+            self.completion_locked = [None, None];
+            self.completions = None;
+            if self.ruleset != Ruleset::Morrowind {
+                // Only Morrowind potions are brewed to find their value
+                self.completion_ranking = CompletionRanking::default();
+            }
+            self.effect_matrix = None;
+        }
+        self.desired_effects = deep_link.desired_effects;
+        self.excluded_effects = deep_link.excluded_effects;
+        self.allow_extra_effects = deep_link.allow_extra_effects;
+        self.previous_effects = self.desired_effects;
 
-        potions.append(&mut potential_potions);
+        self.potential_ingredients =
+            get_potential_ingredients(&self.desired_effects, &self.ingredients);
+        for ingredient in self.potential_ingredients.iter() {
+            match ingredient.try_borrow_mut() {
+                Ok(mut ingredient) => {
+                    ingredient.selected =
+                        deep_link.selected_ingredient_ids.contains(&ingredient.id())
+                }
+                Err(_) => continue, // Unable to borrow ingredient so continuing is better than crashing
+            }
+        }
+        self.generate_potions();
+    }
+
+    /// Describe the current query as a deep link
+    fn current_deep_link(&self) -> DeepLink {
+        DeepLink {
+            ruleset: self.ruleset,
+            desired_effects: self.desired_effects,
+            excluded_effects: self.excluded_effects,
+            allow_extra_effects: self.allow_extra_effects,
+            selected_ingredient_ids: self
+                .potential_ingredients
+                .iter()
+                .filter_map(|ingredient| ingredient.try_borrow().ok())
+                .filter(|ingredient| ingredient.selected)
+                .map(|ingredient| ingredient.id())
+                .collect(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn deep_link_base_url() -> String {
+        HOSTED_URL.to_string()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn deep_link_base_url() -> String {
+        web::location_url_without_hash().unwrap_or_default()
     }
 
-    potions
+    /// Keep the query and the URL fragment in step
+    /// If the URL changed (back/forward navigation), load its query, otherwise if the query changed,
+    /// write it to the URL which adds a browser history entry
+    #[cfg(target_arch = "wasm32")]
+    fn sync_deep_link(&mut self) {
+        let location_hash = web::location_hash().unwrap_or_default();
+        let location_fragment = location_hash.trim_start_matches('#');
+        if location_fragment != self.deep_link_fragment {
+            self.apply_deep_link(&DeepLink::from_fragment(location_fragment));
+            self.deep_link_fragment = location_fragment.to_string();
+            return;
+        }
+
+        let current_fragment = self.current_deep_link().to_fragment();
+        if current_fragment != DeepLink::from_fragment(&self.deep_link_fragment).to_fragment() {
+            web::set_location_hash(&current_fragment);
+            self.deep_link_fragment = current_fragment;
+        }
+    }
 }
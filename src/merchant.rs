@@ -0,0 +1,52 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::Ingredient;
+
+/// A vendor from a merchant list, e.g. the merchant_lists in config.yaml
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Merchant {
+    pub name: String,
+    /// Where to find the merchant, e.g. "Balmora, Mages Guild"
+    pub location: String,
+    /// Ingredients the merchant restocks, by name or ID
+    #[serde(default)]
+    pub ingredients: Vec<String>,
+    /// Most gold the merchant can pay for what the character sells
+    pub barter_gold: u32,
+}
+
+impl Merchant {
+    /// Whether the merchant restocks this ingredient, matching its ID or its name ignoring case
+    pub fn sells(&self, ingredient: &Ingredient) -> bool {
+        let id = ingredient.id();
+        self.ingredients
+            .iter()
+            .any(|name| *name == id || name.eq_ignore_ascii_case(&ingredient.name))
+    }
+
+    /// Gold the merchant is short of to pay `value` in one sale, or None if they can afford it
+    pub fn shortfall(&self, value: u32) -> Option<u32> {
+        value
+            .checked_sub(self.barter_gold)
+            .filter(|shortfall| *shortfall > 0)
+    }
+}
+
+impl Display for Merchant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.location)
+    }
+}
+
+/// The merchants who restock `ingredient`
+pub fn merchants_selling<'a>(
+    merchants: &'a [Merchant],
+    ingredient: &Ingredient,
+) -> Vec<&'a Merchant> {
+    merchants
+        .iter()
+        .filter(|merchant| merchant.sells(ingredient))
+        .collect()
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Barter, Character, DeepLink, Effect, Engine, RecipeBook, Ruleset, SkillType};
+
+/// Storage key of the recipe book, which is kept apart from `PersistedState` so that discarding
+/// outdated state never takes the user's favourites with it
+pub const RECIPE_BOOK_KEY: &str = "recipe_book";
+
+/// Bump this whenever `PersistedState` changes in a way that older stored data can't be read into
+/// Stored data with any other version is discarded and the app starts fresh
+pub const PERSISTED_STATE_VERSION: u32 = 1;
+
+/// The part of the app that survives a restart, stored through `eframe::Storage`
+/// This is deliberately limited to user choices; anything derived from them, such as the potential
+/// ingredients and potions, is recomputed on load
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedState {
+    /// State saved before versioning has none, so it reads as 0 and is discarded
+    #[serde(default)]
+    pub version: u32,
+    pub ruleset: Ruleset,
+    pub desired_effects: [Option<Effect>; 4],
+    pub excluded_effects: [Option<Effect>; 4],
+    pub allow_extra_effects: bool,
+    pub dark_mode: bool,
+    pub selected_ingredient_ids: Vec<String>,
+    pub character: Character,
+    pub engine: Engine,
+    /// The merchant that ingredients are bought from and potions sold to
+    pub barter: Barter,
+    /// Harmful side effects weaker than this are allowed without allowing extra effects, 0 for never
+    pub min_side_effect_magnitude: u32,
+    /// Whether to hide the effects the character's Alchemy is too low to see
+    pub only_known_effects: bool,
+    /// How Alchemy fits into the character's class, for the leveling planner
+    pub skill_type: SkillType,
+    /// Whether the character's class specializes in Magic
+    pub specialized: bool,
+    /// Count of each owned ingredient, by ingredient ID
+    pub inventory: BTreeMap<String, u32>,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        PersistedState {
+            version: PERSISTED_STATE_VERSION,
+            ruleset: Ruleset::default(),
+            desired_effects: [None; 4],
+            excluded_effects: [None; 4],
+            allow_extra_effects: false,
+            dark_mode: true,
+            selected_ingredient_ids: Vec::new(),
+            character: Character::default(),
+            engine: Engine::default(),
+            barter: Barter::default(),
+            min_side_effect_magnitude: 0,
+            only_known_effects: false,
+            skill_type: SkillType::default(),
+            specialized: false,
+            inventory: BTreeMap::new(),
+        }
+    }
+}
+
+impl PersistedState {
+    /// Load the stored state, if there is one that this version of the tool understands
+    pub fn load(storage: &dyn eframe::Storage) -> Option<PersistedState> {
+        let persisted_state: PersistedState = eframe::get_value(storage, eframe::APP_KEY)?;
+        Self::validate(persisted_state)
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, self);
+    }
+
+    /// Reject state written by a different version of the tool
+    pub fn validate(persisted_state: PersistedState) -> Option<PersistedState> {
+        if persisted_state.version == PERSISTED_STATE_VERSION {
+            Some(persisted_state)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_deep_link(deep_link: DeepLink, dark_mode: bool) -> PersistedState {
+        PersistedState {
+            version: PERSISTED_STATE_VERSION,
+            ruleset: deep_link.ruleset,
+            desired_effects: deep_link.desired_effects,
+            excluded_effects: deep_link.excluded_effects,
+            allow_extra_effects: deep_link.allow_extra_effects,
+            dark_mode,
+            selected_ingredient_ids: deep_link.selected_ingredient_ids,
+            character: Character::default(),
+            engine: Engine::default(),
+            barter: Barter::default(),
+            min_side_effect_magnitude: 0,
+            only_known_effects: false,
+            skill_type: SkillType::default(),
+            specialized: false,
+            inventory: BTreeMap::new(),
+        }
+    }
+
+    /// The stored query, in the same form used to restore a query from a link
+    pub fn deep_link(&self) -> DeepLink {
+        DeepLink {
+            ruleset: self.ruleset,
+            desired_effects: self.desired_effects,
+            excluded_effects: self.excluded_effects,
+            allow_extra_effects: self.allow_extra_effects,
+            selected_ingredient_ids: self.selected_ingredient_ids.clone(),
+        }
+    }
+}
+
+impl RecipeBook {
+    /// Load the stored recipe book, or the one stored as part of `PersistedState` by older versions
+    /// of the tool
+    pub fn load(storage: &dyn eframe::Storage) -> RecipeBook {
+        if let Some(stored) = storage.get_string(RECIPE_BOOK_KEY) {
+            return RecipeBook::read_lenient(&stored);
+        }
+
+        #[derive(Deserialize)]
+        struct OlderPersistedState {
+            #[serde(default)]
+            recipe_book: RecipeBook,
+        }
+        eframe::get_value::<OlderPersistedState>(storage, eframe::APP_KEY)
+            .map(|older_persisted_state| older_persisted_state.recipe_book)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        if let Ok(exported) = self.export() {
+            storage.set_string(RECIPE_BOOK_KEY, exported);
+        }
+    }
+}
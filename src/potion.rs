@@ -1,10 +1,19 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
+#[cfg(feature = "gui")]
 use eframe::egui::{self, Widget};
+#[cfg(feature = "gui")]
 use egui::{
     text::LayoutJob, Color32, NumExt, Sense, TextFormat, TextStyle, WidgetInfo, WidgetText,
     WidgetType,
 };
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use super::{Effect, Ingredient};
@@ -34,6 +43,7 @@ impl Display for Potion {
     }
 }
 
+#[cfg(feature = "gui")]
 impl Widget for &mut Potion {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         ui.set_width(ui.available_width());
@@ -104,34 +114,52 @@ impl Widget for &mut Potion {
     }
 }
 
+impl PartialEq for Potion {
+    fn eq(&self, other: &Self) -> bool {
+        self.recipe_key() == other.recipe_key()
+    }
+}
+
+impl Eq for Potion {}
+
+impl Hash for Potion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.recipe_key().hash(state);
+    }
+}
+
 impl Potion {
     pub fn new_potion_from_ingredients(ingredients: &[&Rc<RefCell<Ingredient>>]) -> Potion {
-        let mut ingredients: Vec<Option<Rc<RefCell<Ingredient>>>> = ingredients
+        let ingredients: Vec<Option<Rc<RefCell<Ingredient>>>> = ingredients
             .iter()
             .map(|&potential_ingredient| Some(potential_ingredient.clone()))
             .collect();
-        // Resize if needed
-        while ingredients.len() < 4 {
-            ingredients.resize_with(4, || None);
-        }
-
-        let effects = Self::effects(&ingredients);
 
-        // Create a potion from the potential_ingredients
-        Potion {
-            ingredients: ingredients.try_into().unwrap_or([None, None, None, None]),
-            effects,
-        }
+        Self::new_canonical_potion(ingredients)
     }
 
     #[cfg(test)]
     pub fn new_potion_from_optional_ingredients(ingredients: &[Option<Ingredient>]) -> Potion {
-        let mut ingredients: Vec<Option<Rc<RefCell<Ingredient>>>> = ingredients
+        let ingredients: Vec<Option<Rc<RefCell<Ingredient>>>> = ingredients
             .iter()
             .flatten()
             .cloned()
             .map(|ingredient| Some(Rc::new(RefCell::new(ingredient))))
             .collect();
+
+        Self::new_canonical_potion(ingredients)
+    }
+
+    /// Create a potion in its canonical form: ingredients sorted by ID followed by any empty slots,
+    /// and effects sorted in the order defined by `Effect`
+    fn new_canonical_potion(mut ingredients: Vec<Option<Rc<RefCell<Ingredient>>>>) -> Potion {
+        ingredients.sort_by_cached_key(|ingredient| match ingredient {
+            Some(ingredient) => match ingredient.try_borrow() {
+                Ok(ingredient) => (false, ingredient.id()),
+                Err(_) => (false, String::new()),
+            },
+            None => (true, String::new()),
+        });
         // Resize if needed
         while ingredients.len() < 4 {
             ingredients.resize_with(4, || None);
@@ -146,6 +174,61 @@ impl Potion {
         }
     }
 
+    /// Combined base value of the ingredients, i.e. what it costs to buy them at base price
+    pub fn total_value(&self) -> u32 {
+        self.ingredients
+            .iter()
+            .flatten()
+            .filter_map(|ingredient| ingredient.try_borrow().ok())
+            .map(|ingredient| u32::from(ingredient.value))
+            .sum()
+    }
+
+    /// Combined weight of the ingredients
+    pub fn total_weight(&self) -> f32 {
+        self.ingredients
+            .iter()
+            .flatten()
+            .filter_map(|ingredient| ingredient.try_borrow().ok())
+            .map(|ingredient| ingredient.weight)
+            .sum()
+    }
+
+    /// The names of the ingredients in this potion, in canonical order
+    pub fn ingredient_names(&self) -> Vec<String> {
+        self.ingredients
+            .iter()
+            .flatten()
+            .filter_map(|ingredient| {
+                ingredient
+                    .try_borrow()
+                    .ok()
+                    .map(|ingredient| ingredient.name.clone())
+            })
+            .collect()
+    }
+
+    /// The IDs of the ingredients in this potion, in canonical order
+    pub fn ingredient_ids(&self) -> Vec<String> {
+        self.ingredients
+            .iter()
+            .flatten()
+            .filter_map(|ingredient| {
+                ingredient
+                    .try_borrow()
+                    .ok()
+                    .map(|ingredient| ingredient.id())
+            })
+            .collect()
+    }
+
+    /// Stable, human readable identity of this recipe e.g. "ash_yam+bread"
+    /// Two potions made from the same ingredients always have the same key, regardless of the
+    /// order the ingredients were chosen in
+    pub fn recipe_key(&self) -> String {
+        self.ingredient_ids().join("+")
+    }
+
     fn effects(ingredients: &[Option<Rc<RefCell<Ingredient>>>]) -> Vec<Effect> {
         let mut effects_map: HashMap<Effect, u8> = HashMap::new();
         for ingredient in ingredients.iter().flatten() {
@@ -171,6 +254,7 @@ impl Potion {
                     None
                 }
             })
+            .sorted()
             .collect()
     }
 }
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, rc::Rc};
+
+use itertools::Itertools;
+
+use super::{
+    create_potential_potions, get_potential_ingredients, Character, Effect, Engine, Ingredient,
+    Potion,
+};
+
+/// Everything needed to search a set of ingredients for potions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PotionQuery {
+    pub desired_effects: [Option<Effect>; 4],
+    pub excluded_effects: [Option<Effect>; 4],
+    pub allow_extra_effects: bool,
+    /// Largest number of ingredients to combine, from 2 to 4
+    pub max_ingredients: usize,
+    /// Harmful side effects too weak to matter, which are allowed even without
+    /// `allow_extra_effects`
+    pub side_effect_threshold: Option<SideEffectThreshold>,
+}
+
+/// How weak a harmful side effect must be to ignore it, when brewed by this character
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideEffectThreshold {
+    pub engine: Engine,
+    pub character: Character,
+    /// Side effects with fewer points than this are ignored
+    pub min_magnitude: u32,
+}
+
+impl Default for PotionQuery {
+    fn default() -> Self {
+        PotionQuery {
+            desired_effects: [None; 4],
+            excluded_effects: [None; 4],
+            allow_extra_effects: false,
+            max_ingredients: 4,
+            side_effect_threshold: None,
+        }
+    }
+}
+
+impl PotionQuery {
+    /// Find every potion that can be made from any of `ingredients`, such as a whole dataset
+    ///
+    /// Only the ingredients with at least one desired effect are combined
+    pub fn search(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Vec<Potion> {
+        self.find_potions(&get_potential_ingredients(
+            &self.desired_effects,
+            ingredients,
+        ))
+    }
+
+    /// Find every potion that can be made from `ingredients` with all of the desired effects
+    ///
+    /// Potions are sorted by ingredient count and then by recipe. Unnecessary potions, whose
+    /// effects are all already available from a potion with fewer ingredients, are left out
+    pub fn find_potions(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Vec<Potion> {
+        let potential_potions: Vec<Potion> =
+            create_potential_potions(&self.desired_effects, ingredients, self.max_ingredients)
+                .iter()
+                .filter(|potential_potion| {
+                    if self.allow_extra_effects {
+                        return true;
+                    }
+                    let brewed_potion = self.side_effect_threshold.as_ref().map(|threshold| {
+                        (
+                            threshold
+                                .engine
+                                .brew(potential_potion, &threshold.character),
+                            threshold.min_magnitude,
+                        )
+                    });
+                    potential_potion.effects.iter().all(|&effect| {
+                        self.desired_effects.contains(&Some(effect))
+                            || brewed_potion.as_ref().is_some_and(
+                                |(brewed_potion, min_magnitude)| {
+                                    brewed_potion.is_negligible(effect, *min_magnitude)
+                                },
+                            )
+                    })
+                })
+                .filter(|potential_potion| {
+                    !potential_potion
+                        .effects
+                        .iter()
+                        .any(|&effect| self.excluded_effects.contains(&Some(effect)))
+                })
+                .sorted_by_cached_key(|potion| {
+                    // Sort by ingredient count, then by recipe so the list is identical on every run
+                    (
+                        potion.ingredients.iter().flatten().count(),
+                        potion.recipe_key(),
+                    )
+                })
+                .cloned()
+                .collect();
+        let two_ingredient_potions: Vec<&Potion> = potential_potions
+            .iter()
+            .filter(|potion| potion.ingredients.iter().flatten().count() == 2)
+            .collect();
+        let mut three_ingredient_potions: Vec<&Potion> = potential_potions
+            .iter()
+            .filter(|potion| potion.ingredients.iter().flatten().count() == 3)
+            .collect();
+        three_ingredient_potions.retain(|three_ingredient_potion| {
+            // If we find any exact match, it's an old potion
+            for two_ingredient_potion in two_ingredient_potions.iter() {
+                if three_ingredient_potion
+                    .effects
+                    .iter()
+                    .all(|effect| two_ingredient_potion.effects.contains(effect))
+                {
+                    return false;
+                }
+            }
+
+            true
+        });
+        let mut four_ingredient_potions: Vec<&Potion> = potential_potions
+            .iter()
+            .filter(|potion| potion.ingredients.iter().flatten().count() == 4)
+            .collect();
+        four_ingredient_potions.retain(|four_ingredient_potion| {
+            for two_ingredient_potion in two_ingredient_potions.iter() {
+                if four_ingredient_potion
+                    .effects
+                    .iter()
+                    .all(|effect| two_ingredient_potion.effects.contains(effect))
+                {
+                    return false;
+                }
+            }
+
+            true
+        });
+        four_ingredient_potions.retain(|four_ingredient_potion| {
+            for three_ingredient_potion in three_ingredient_potions.iter() {
+                if four_ingredient_potion
+                    .effects
+                    .iter()
+                    .all(|effect| three_ingredient_potion.effects.contains(effect))
+                {
+                    return false;
+                }
+            }
+
+            true
+        });
+
+        let mut potions: Vec<Potion> = Vec::new();
+        potions.append(&mut two_ingredient_potions.into_iter().cloned().collect());
+        potions.append(&mut three_ingredient_potions.into_iter().cloned().collect());
+        potions.append(&mut four_ingredient_potions.into_iter().cloned().collect());
+
+        potions
+    }
+}
@@ -0,0 +1,145 @@
+use std::{cell::RefCell, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Ingredient, Potion, Ruleset};
+
+/// A starred recipe, stored by the stable IDs of its ingredients so it survives changes to the
+/// loaded ingredient lists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Favourite {
+    /// The game the recipe is for, as ingredient IDs can be shared between games
+    /// Favourites starred before there were other games are Morrowind recipes
+    #[serde(default)]
+    pub ruleset: Ruleset,
+    pub ingredient_ids: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Favourite {
+    pub fn from_potion(ruleset: Ruleset, potion: &Potion) -> Favourite {
+        Favourite {
+            ruleset,
+            ingredient_ids: potion.ingredient_ids(),
+            note: String::new(),
+        }
+    }
+
+    /// Same form as `Potion::recipe_key`
+    pub fn recipe_key(&self) -> String {
+        self.ingredient_ids.join("+")
+    }
+
+    /// Recreate the potion from the loaded ingredients
+    /// Returns None if any of its ingredients are no longer loaded
+    pub fn potion(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> Option<Potion> {
+        let favourite_ingredients = self
+            .ingredient_ids
+            .iter()
+            .map(|ingredient_id| {
+                ingredients
+                    .iter()
+                    .find(|ingredient| match ingredient.try_borrow() {
+                        Ok(ingredient) => ingredient.id() == *ingredient_id,
+                        Err(_) => false,
+                    })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Potion::new_potion_from_ingredients(&favourite_ingredients))
+    }
+}
+
+/// The user's personal collection of favourite recipes, for every game
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeBook {
+    pub favourites: Vec<Favourite>,
+}
+
+impl RecipeBook {
+    /// Read a recipe book, leaving out any favourites that can't be read rather than failing
+    /// Used for the stored recipe book, where one bad favourite shouldn't cost all the others
+    pub fn read_lenient(data: &str) -> RecipeBook {
+        #[derive(Deserialize)]
+        struct StoredRecipeBook {
+            #[serde(default)]
+            favourites: Vec<serde_yaml::Value>,
+        }
+
+        let Ok(stored) = serde_yaml::from_str::<StoredRecipeBook>(data) else {
+            return RecipeBook::default();
+        };
+        RecipeBook {
+            favourites: stored
+                .favourites
+                .into_iter()
+                .filter_map(|favourite| serde_yaml::from_value(favourite).ok())
+                .collect(),
+        }
+    }
+
+    /// The favourites for one game
+    pub fn favourites(&self, ruleset: Ruleset) -> impl Iterator<Item = &Favourite> {
+        self.favourites
+            .iter()
+            .filter(move |favourite| favourite.ruleset == ruleset)
+    }
+
+    pub fn is_favourite(&self, ruleset: Ruleset, potion: &Potion) -> bool {
+        let recipe_key = potion.recipe_key();
+        self.favourites(ruleset)
+            .any(|favourite| favourite.recipe_key() == recipe_key)
+    }
+
+    /// Star the potion, or unstar it (losing its note) if it is already a favourite
+    pub fn toggle(&mut self, ruleset: Ruleset, potion: &Potion) {
+        if self.is_favourite(ruleset, potion) {
+            self.remove(ruleset, &potion.recipe_key());
+        } else {
+            self.favourites
+                .push(Favourite::from_potion(ruleset, potion));
+        }
+    }
+
+    pub fn remove(&mut self, ruleset: Ruleset, recipe_key: &str) {
+        self.favourites.retain(|favourite| {
+            favourite.ruleset != ruleset || favourite.recipe_key() != recipe_key
+        });
+    }
+
+    pub fn export(&self) -> Result<String, String> {
+        serde_yaml::to_string(self)
+            .map_err(|error| format!("Unable to export recipe book: {}", error))
+    }
+
+    /// Merge the favourites of an exported recipe book into this one
+    /// Recipes that are already favourites keep their note, unless it is empty
+    /// Returns the number of new favourites
+    pub fn import(&mut self, data: &str) -> Result<usize, String> {
+        let imported: RecipeBook = serde_yaml::from_str(data)
+            .map_err(|error| format!("Not a valid recipe book: {}", error))?;
+
+        let mut new_favourites = 0;
+        for mut imported_favourite in imported.favourites {
+            imported_favourite.ingredient_ids.sort();
+            let recipe_key = imported_favourite.recipe_key();
+            match self.favourites.iter_mut().find(|favourite| {
+                favourite.ruleset == imported_favourite.ruleset
+                    && favourite.recipe_key() == recipe_key
+            }) {
+                Some(favourite) => {
+                    if favourite.note.is_empty() {
+                        favourite.note = imported_favourite.note;
+                    }
+                }
+                None => {
+                    self.favourites.push(imported_favourite);
+                    new_favourites += 1;
+                }
+            }
+        }
+
+        Ok(new_favourites)
+    }
+}
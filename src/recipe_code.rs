@@ -0,0 +1,250 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use itertools::Itertools;
+use strum::IntoEnumIterator;
+
+use super::{Effect, Ingredient, Potion};
+
+/// Version written into the first byte of every code
+/// Bump this if the layout below ever changes so old codes can be rejected cleanly
+const RECIPE_CODE_VERSION: u8 = 1;
+
+/// A short, URL-safe code describing a recipe and, optionally, the query that found it
+///
+/// Layout before base64 (URL-safe alphabet, no padding) encoding:
+/// - 1 byte: version
+/// - 1 byte: flags - ingredient count (bits 0-2), effect count (bits 3-5), has query (bit 6),
+///   allow extra effects (bit 7)
+/// - 4 bytes per ingredient: FNV-1a hash of the ingredient ID
+/// - 4 bytes per desired effect: FNV-1a hash of the effect name
+/// - 1 byte: checksum of everything before it
+///
+/// Ingredients are stored by a hash of their stable ID rather than their position in the data
+/// set, so codes keep working when new ingredient lists are added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipeCode {
+    pub ingredient_ids: Vec<String>,
+    pub query: Option<RecipeQuery>,
+}
+
+/// The desired effects and options used to find a recipe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipeQuery {
+    pub desired_effects: [Option<Effect>; 4],
+    pub allow_extra_effects: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipeCodeError {
+    Empty,
+    InvalidEncoding,
+    InvalidLength,
+    UnsupportedVersion(u8),
+    ChecksumMismatch,
+    InvalidIngredientCount(usize),
+    DuplicateIngredient,
+    UnknownIngredient,
+    UnknownEffect,
+}
+
+impl Display for RecipeCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeCodeError::Empty => f.write_str("No recipe code was entered"),
+            RecipeCodeError::InvalidEncoding => {
+                f.write_str("Recipe code contains characters that are not allowed")
+            }
+            RecipeCodeError::InvalidLength => {
+                f.write_str("Recipe code is too short or too long - was it copied completely?")
+            }
+            RecipeCodeError::UnsupportedVersion(version) => f.write_fmt(format_args!(
+                "Recipe code version {} is not supported by this version of the tool",
+                version
+            )),
+            RecipeCodeError::ChecksumMismatch => {
+                f.write_str("Recipe code is damaged - was it copied completely?")
+            }
+            RecipeCodeError::InvalidIngredientCount(count) => f.write_fmt(format_args!(
+                "Recipe code contains {} ingredients but a potion needs 2 to 4",
+                count
+            )),
+            RecipeCodeError::DuplicateIngredient => {
+                f.write_str("Recipe code uses the same ingredient more than once")
+            }
+            RecipeCodeError::UnknownIngredient => f.write_str(
+                "Recipe code uses an ingredient that is not in the loaded ingredient lists",
+            ),
+            RecipeCodeError::UnknownEffect => f.write_str(
+                "Recipe code uses an effect that is not known to this version of the tool",
+            ),
+        }
+    }
+}
+
+impl RecipeCode {
+    pub fn from_potion(potion: &Potion, query: Option<RecipeQuery>) -> RecipeCode {
+        RecipeCode {
+            ingredient_ids: potion.ingredient_ids(),
+            query,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let desired_effects: Vec<&Effect> = match &self.query {
+            Some(query) => query.desired_effects.iter().flatten().collect(),
+            None => Vec::new(),
+        };
+
+        let mut flags = (self.ingredient_ids.len() as u8 & 0b111)
+            | ((desired_effects.len() as u8 & 0b111) << 3);
+        if let Some(query) = &self.query {
+            flags |= 1 << 6;
+            if query.allow_extra_effects {
+                flags |= 1 << 7;
+            }
+        }
+
+        let mut bytes = vec![RECIPE_CODE_VERSION, flags];
+        for ingredient_id in self.ingredient_ids.iter() {
+            bytes.extend_from_slice(&fnv1a_32(ingredient_id).to_be_bytes());
+        }
+        for effect in desired_effects {
+            bytes.extend_from_slice(&fnv1a_32(&effect_name(effect)).to_be_bytes());
+        }
+        bytes.push(checksum(&bytes));
+
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decode a code, resolving the ingredient hashes against the loaded ingredients
+    pub fn decode(
+        code: &str,
+        ingredients: &[Rc<RefCell<Ingredient>>],
+    ) -> Result<RecipeCode, RecipeCodeError> {
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(RecipeCodeError::Empty);
+        }
+
+        let bytes = base64::decode_config(code, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| RecipeCodeError::InvalidEncoding)?;
+        let Some((&expected_checksum, bytes)) = bytes.split_last() else {
+            return Err(RecipeCodeError::InvalidLength);
+        };
+        if bytes.len() < 2 {
+            return Err(RecipeCodeError::InvalidLength);
+        }
+        if bytes[0] != RECIPE_CODE_VERSION {
+            return Err(RecipeCodeError::UnsupportedVersion(bytes[0]));
+        }
+        if checksum(bytes) != expected_checksum {
+            return Err(RecipeCodeError::ChecksumMismatch);
+        }
+
+        let flags = bytes[1];
+        let ingredient_count = (flags & 0b111) as usize;
+        let effect_count = ((flags >> 3) & 0b111) as usize;
+        let has_query = flags & (1 << 6) != 0;
+        let allow_extra_effects = flags & (1 << 7) != 0;
+
+        if !(2..=4).contains(&ingredient_count) {
+            return Err(RecipeCodeError::InvalidIngredientCount(ingredient_count));
+        }
+        if effect_count > 4 || (!has_query && effect_count > 0) {
+            return Err(RecipeCodeError::InvalidLength);
+        }
+
+        let hashes: Vec<u32> = bytes[2..]
+            .chunks(4)
+            .map(|chunk| match chunk.try_into() {
+                Ok(chunk) => Some(u32::from_be_bytes(chunk)),
+                Err(_) => None,
+            })
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(RecipeCodeError::InvalidLength)?;
+        if hashes.len() != ingredient_count + effect_count {
+            return Err(RecipeCodeError::InvalidLength);
+        }
+        if !hashes[..ingredient_count].iter().all_unique() {
+            return Err(RecipeCodeError::DuplicateIngredient);
+        }
+
+        let ingredient_ids = hashes[..ingredient_count]
+            .iter()
+            .map(|hash| {
+                ingredients
+                    .iter()
+                    .filter_map(|ingredient| ingredient.try_borrow().ok())
+                    .map(|ingredient| ingredient.id())
+                    .find(|ingredient_id| fnv1a_32(ingredient_id) == *hash)
+                    .ok_or(RecipeCodeError::UnknownIngredient)
+            })
+            .collect::<Result<Vec<String>, RecipeCodeError>>()?;
+
+        let query = if has_query {
+            let mut desired_effects = [None; 4];
+            for (desired_effect, hash) in desired_effects
+                .iter_mut()
+                .zip(hashes[ingredient_count..].iter())
+            {
+                *desired_effect = Some(
+                    Effect::iter()
+                        .find(|effect| fnv1a_32(&effect_name(effect)) == *hash)
+                        .ok_or(RecipeCodeError::UnknownEffect)?,
+                );
+            }
+            Some(RecipeQuery {
+                desired_effects,
+                allow_extra_effects,
+            })
+        } else {
+            None
+        };
+
+        Ok(RecipeCode {
+            ingredient_ids,
+            query,
+        })
+    }
+
+    /// Look up the ingredients of this code in the loaded ingredients
+    pub fn ingredients(
+        &self,
+        ingredients: &[Rc<RefCell<Ingredient>>],
+    ) -> Vec<Rc<RefCell<Ingredient>>> {
+        self.ingredient_ids
+            .iter()
+            .filter_map(|ingredient_id| {
+                ingredients
+                    .iter()
+                    .find(|ingredient| match ingredient.try_borrow() {
+                        Ok(ingredient) => ingredient.id() == *ingredient_id,
+                        Err(_) => false,
+                    })
+                    .cloned()
+            })
+            .collect()
+    }
+}
+
+/// The stable name of an effect, matching its serialized form e.g. "RestoreHealth"
+fn effect_name(effect: &Effect) -> String {
+    format!("{:?}", effect)
+}
+
+/// Stable 32-bit FNV-1a hash, identical across runs, machines and compiler versions unlike
+/// `std::hash`
+fn fnv1a_32(value: &str) -> u32 {
+    const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+    const FNV_PRIME: u32 = 0x01000193;
+
+    value.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |checksum, byte| checksum.rotate_left(1) ^ byte)
+}
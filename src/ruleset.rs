@@ -0,0 +1,441 @@
+use std::{cell::RefCell, collections::HashSet, fmt::Display, rc::Rc, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::{
+    sort_ingredients, ApparatusQuality, Character, Effect, Ingredient, Potion, POTION_STRENGTH_MULT,
+};
+
+/// Effects that Morrowind ingredients can have
+const MORROWIND_EFFECTS: &[Effect] = &[
+    Effect::Chameleon,
+    Effect::CureBlightDisease,
+    Effect::CureCommonDisease,
+    Effect::CureParalyzation,
+    Effect::CurePoison,
+    Effect::DetectAnimal,
+    Effect::DetectEnchantment,
+    Effect::DetectKey,
+    Effect::Dispel,
+    Effect::Feather,
+    Effect::FireShield,
+    Effect::FortifyAttack,
+    Effect::FortifyStrength,
+    Effect::FortifyIntelligence,
+    Effect::FortifyWillpower,
+    Effect::FortifyAgility,
+    Effect::FortifySpeed,
+    Effect::FortifyEndurance,
+    Effect::FortifyPersonality,
+    Effect::FortifyLuck,
+    Effect::FortifyHealth,
+    Effect::FortifyMagicka,
+    Effect::FortifyMaximumMagicka,
+    Effect::FortifyFatigue,
+    Effect::FrostShield,
+    Effect::Invisibility,
+    Effect::Levitate,
+    Effect::Light,
+    Effect::LightningShield,
+    Effect::NightEye,
+    Effect::Recall,
+    Effect::Reflect,
+    Effect::ResistCommonDisease,
+    Effect::ResistFire,
+    Effect::ResistFrost,
+    Effect::ResistMagicka,
+    Effect::ResistNormalWeapons,
+    Effect::ResistParalysis,
+    Effect::ResistPoison,
+    Effect::ResistShock,
+    Effect::RestoreStrength,
+    Effect::RestoreIntelligence,
+    Effect::RestoreWillpower,
+    Effect::RestoreAgility,
+    Effect::RestoreSpeed,
+    Effect::RestoreEndurance,
+    Effect::RestorePersonality,
+    Effect::RestoreLuck,
+    Effect::RestoreHealth,
+    Effect::RestoreMagicka,
+    Effect::RestoreFatigue,
+    Effect::Shield,
+    Effect::SpellAbsorption,
+    Effect::SwiftSwim,
+    Effect::Telekinesis,
+    Effect::WaterBreathing,
+    Effect::WaterWalking,
+    Effect::Blind,
+    Effect::Burden,
+    Effect::DamageStrength,
+    Effect::DamageIntelligence,
+    Effect::DamageWillpower,
+    Effect::DamageAgility,
+    Effect::DamageSpeed,
+    Effect::DamageEndurance,
+    Effect::DamagePersonality,
+    Effect::DamageLuck,
+    Effect::DamageHealth,
+    Effect::DamageMagicka,
+    Effect::DamageFatigue,
+    Effect::DrainAlteration,
+    Effect::DrainStrength,
+    Effect::DrainIntelligence,
+    Effect::DrainWillpower,
+    Effect::DrainAgility,
+    Effect::DrainSpeed,
+    Effect::DrainEndurance,
+    Effect::DrainPersonality,
+    Effect::DrainLuck,
+    Effect::DrainHealth,
+    Effect::DrainMagicka,
+    Effect::DrainFatigue,
+    Effect::FireDamage,
+    Effect::FrostDamage,
+    Effect::Paralyze,
+    Effect::Poison,
+    Effect::ShockDamage,
+    Effect::Silence,
+    Effect::Vampirism,
+    Effect::WeaknessToFire,
+    Effect::WeaknessToFrost,
+    Effect::WeaknessToPoison,
+    Effect::WeaknessToShock,
+];
+
+/// Effects that Oblivion ingredients can have
+const OBLIVION_EFFECTS: &[Effect] = &[
+    Effect::Chameleon,
+    Effect::CureDisease,
+    Effect::CureParalysis,
+    Effect::CurePoison,
+    Effect::DetectLife,
+    Effect::Dispel,
+    Effect::Feather,
+    Effect::FireShield,
+    Effect::FortifyStrength,
+    Effect::FortifyIntelligence,
+    Effect::FortifyWillpower,
+    Effect::FortifyAgility,
+    Effect::FortifySpeed,
+    Effect::FortifyEndurance,
+    Effect::FortifyPersonality,
+    Effect::FortifyLuck,
+    Effect::FortifyHealth,
+    Effect::FortifyMagicka,
+    Effect::FortifyFatigue,
+    Effect::FrostShield,
+    Effect::Invisibility,
+    Effect::Light,
+    Effect::NightEye,
+    Effect::ReflectDamage,
+    Effect::ReflectSpell,
+    Effect::ResistDisease,
+    Effect::ResistFire,
+    Effect::ResistFrost,
+    Effect::ResistNormalWeapons,
+    Effect::ResistParalysis,
+    Effect::ResistPoison,
+    Effect::ResistShock,
+    Effect::RestoreStrength,
+    Effect::RestoreIntelligence,
+    Effect::RestoreWillpower,
+    Effect::RestoreAgility,
+    Effect::RestoreSpeed,
+    Effect::RestoreEndurance,
+    Effect::RestorePersonality,
+    Effect::RestoreLuck,
+    Effect::RestoreHealth,
+    Effect::RestoreMagicka,
+    Effect::RestoreFatigue,
+    Effect::Shield,
+    Effect::ShockShield,
+    Effect::Telekinesis,
+    Effect::WaterBreathing,
+    Effect::WaterWalking,
+    Effect::Burden,
+    Effect::DamageStrength,
+    Effect::DamageIntelligence,
+    Effect::DamageWillpower,
+    Effect::DamageAgility,
+    Effect::DamageSpeed,
+    Effect::DamageEndurance,
+    Effect::DamagePersonality,
+    Effect::DamageLuck,
+    Effect::DamageHealth,
+    Effect::DamageMagicka,
+    Effect::DamageFatigue,
+    Effect::FireDamage,
+    Effect::FrostDamage,
+    Effect::Paralyze,
+    Effect::ShockDamage,
+    Effect::Silence,
+];
+
+/// Effects that Skyrim ingredients can have
+const SKYRIM_EFFECTS: &[Effect] = &[
+    Effect::CureDisease,
+    Effect::FortifyHealth,
+    Effect::FortifyMagicka,
+    Effect::FortifyStamina,
+    Effect::FortifyAlteration,
+    Effect::FortifyBarter,
+    Effect::FortifyBlock,
+    Effect::FortifyCarryWeight,
+    Effect::FortifyConjuration,
+    Effect::FortifyDestruction,
+    Effect::FortifyEnchanting,
+    Effect::FortifyHeavyArmor,
+    Effect::FortifyIllusion,
+    Effect::FortifyLightArmor,
+    Effect::FortifyLockpicking,
+    Effect::FortifyMarksman,
+    Effect::FortifyOneHanded,
+    Effect::FortifyPickpocket,
+    Effect::FortifyRestoration,
+    Effect::FortifySmithing,
+    Effect::FortifySneak,
+    Effect::FortifyTwoHanded,
+    Effect::Invisibility,
+    Effect::RegenerateHealth,
+    Effect::RegenerateMagicka,
+    Effect::RegenerateStamina,
+    Effect::ResistFire,
+    Effect::ResistFrost,
+    Effect::ResistMagic,
+    Effect::ResistPoison,
+    Effect::ResistShock,
+    Effect::RestoreHealth,
+    Effect::RestoreMagicka,
+    Effect::RestoreStamina,
+    Effect::WaterBreathing,
+    Effect::DamageHealth,
+    Effect::DamageMagicka,
+    Effect::DamageMagickaRegen,
+    Effect::DamageStamina,
+    Effect::DamageStaminaRegen,
+    Effect::Fear,
+    Effect::Frenzy,
+    Effect::LingeringDamageHealth,
+    Effect::LingeringDamageMagicka,
+    Effect::LingeringDamageStamina,
+    Effect::Paralysis,
+    Effect::RavageHealth,
+    Effect::RavageMagicka,
+    Effect::RavageStamina,
+    Effect::Slow,
+    Effect::WeaknessToFire,
+    Effect::WeaknessToFrost,
+    Effect::WeaknessToMagic,
+    Effect::WeaknessToPoison,
+    Effect::WeaknessToShock,
+];
+
+/// The alchemy rules of a game in the series
+///
+/// Every game makes a potion the same way, from the effects shared by at least two of its
+/// ingredients, but they differ in how many ingredients can be combined, which effects exist,
+/// how many effects of an ingredient a character can see and how strong the potions are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum Ruleset {
+    #[default]
+    Morrowind,
+    Oblivion,
+    Skyrim,
+}
+
+impl Ruleset {
+    /// Most ingredients that can go into one potion
+    pub fn max_ingredients(&self) -> usize {
+        match self {
+            Ruleset::Morrowind | Ruleset::Oblivion => 4,
+            Ruleset::Skyrim => 3,
+        }
+    }
+
+    /// Every effect in this game, sorted by name
+    pub fn effects(&self) -> Vec<Effect> {
+        let effects = match self {
+            Ruleset::Morrowind => MORROWIND_EFFECTS,
+            Ruleset::Oblivion => OBLIVION_EFFECTS,
+            Ruleset::Skyrim => SKYRIM_EFFECTS,
+        };
+
+        Effect::effects_list()
+            .into_iter()
+            .filter(|effect| effects.contains(effect))
+            .collect()
+    }
+
+    /// How many of each ingredient's effects a character with this Alchemy skill can see
+    /// Morrowind reveals one more effect every 15 points and Oblivion one per mastery level, while
+    /// Skyrim reveals effects by eating and brewing instead, so every effect is assumed known
+    pub fn visible_effects(&self, alchemy: u16) -> usize {
+        match self {
+            Ruleset::Morrowind => usize::from(alchemy / 15).min(4),
+            Ruleset::Oblivion => usize::from(1 + alchemy / 25).min(4),
+            Ruleset::Skyrim => 4,
+        }
+    }
+
+    /// The effects of `ingredient` that a character with this Alchemy skill can see, which are
+    /// always its first ones
+    pub fn visible_ingredient_effects(&self, alchemy: u16, ingredient: &Ingredient) -> Vec<Effect> {
+        ingredient
+            .effects
+            .iter()
+            .take(self.visible_effects(alchemy))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Every effect of this game that a character with this Alchemy skill can see on at least one
+    /// of `ingredients`, sorted by name
+    pub fn known_effects(
+        &self,
+        alchemy: u16,
+        ingredients: &[Rc<RefCell<Ingredient>>],
+    ) -> Vec<Effect> {
+        let visible_effects: HashSet<Effect> = ingredients
+            .iter()
+            .filter_map(|ingredient| ingredient.try_borrow().ok())
+            .flat_map(|ingredient| self.visible_ingredient_effects(alchemy, &ingredient))
+            .collect();
+
+        self.effects()
+            .into_iter()
+            .filter(|effect| visible_effects.contains(effect))
+            .collect()
+    }
+
+    /// The effects of `potion` that a character with this Alchemy skill can see on one of its
+    /// ingredients, in the potion's order
+    /// The potion still has its other effects, the character just can't tell what they are
+    pub fn known_potion_effects(&self, alchemy: u16, potion: &Potion) -> Vec<Effect> {
+        let ingredients: Vec<Rc<RefCell<Ingredient>>> =
+            potion.ingredients.iter().flatten().cloned().collect();
+        let known_effects = self.known_effects(alchemy, &ingredients);
+
+        potion
+            .effects
+            .iter()
+            .filter(|effect| known_effects.contains(effect))
+            .copied()
+            .collect()
+    }
+
+    /// How strongly this character brews, which each game turns into magnitudes differently
+    /// - Morrowind: (Alchemy + Intelligence / 10 + Luck / 10) × mortar and pestle quality / 2,
+    ///   divided by an effect's base cost to give its magnitude
+    /// - Oblivion: Alchemy adjusted by Luck, capped at 100, plus 25 × mortar and pestle quality
+    /// - Skyrim: 4 × (1 + (fAlchemySkillFactor - 1) × Alchemy / 100) with fAlchemySkillFactor at
+    ///   1.5, multiplying an effect's base magnitude, before perks and Fortify Alchemy
+    ///   (UESP, Skyrim:Alchemy, "Effect Magnitude and Duration")
+    ///
+    /// There is no potion at all without a mortar and pestle, except in Skyrim which has none
+    ///
+    /// Only Morrowind's strength goes on to give the magnitudes, durations and values of potions,
+    /// for Oblivion and Skyrim it is a guide to how strong the character's potions are
+    pub fn potion_strength(&self, character: &Character) -> f32 {
+        let alchemy = f32::from(character.alchemy);
+        let mortar_and_pestle_quality = character
+            .apparatus
+            .mortar_and_pestle
+            .map_or(0.0, |quality| self.apparatus_quality(quality));
+        match self {
+            Ruleset::Morrowind => {
+                character.alchemy_factor() * mortar_and_pestle_quality * POTION_STRENGTH_MULT
+            }
+            Ruleset::Oblivion => {
+                if mortar_and_pestle_quality == 0.0 {
+                    return 0.0;
+                }
+                let effective_alchemy =
+                    (alchemy + 0.4 * (f32::from(character.luck) - 50.0)).clamp(0.0, 100.0);
+                effective_alchemy + 25.0 * mortar_and_pestle_quality
+            }
+            Ruleset::Skyrim => {
+                const ALCHEMY_SKILL_FACTOR: f32 = 1.5;
+                4.0 * (1.0 + (ALCHEMY_SKILL_FACTOR - 1.0) * alchemy / 100.0)
+            }
+        }
+    }
+
+    /// The quality this game gives apparatus of the grade, which is entered with Morrowind's grades
+    /// - Morrowind: from 0.5 for Apprentice to 2.0 for Secret Master
+    /// - Oblivion: 0.25 for Apprentice, 0.5 for Journeyman and 1.0 for Master; Oblivion has
+    ///   nothing above Master, so Grandmaster and Secret Master count as Master
+    /// - Skyrim: 0, as it has no apparatus
+    pub fn apparatus_quality(&self, quality: ApparatusQuality) -> f32 {
+        match (self, quality) {
+            (Ruleset::Morrowind, quality) => quality.quality(),
+            (Ruleset::Oblivion, ApparatusQuality::Apprentice) => 0.25,
+            (Ruleset::Oblivion, ApparatusQuality::Journeyman) => 0.5,
+            (
+                Ruleset::Oblivion,
+                ApparatusQuality::Master
+                | ApparatusQuality::Grandmaster
+                | ApparatusQuality::SecretMaster,
+            ) => 1.0,
+            (Ruleset::Skyrim, _) => 0.0,
+        }
+    }
+
+    /// The ingredients of the base game and its expansions built into the library, sorted by name
+    /// Morrowind includes Tribunal and Bloodmoon, while Oblivion and Skyrim are the base games only
+    pub fn bundled_ingredients(&self) -> Vec<Rc<RefCell<Ingredient>>> {
+        let ingredient_lists: &[&str] = match self {
+            Ruleset::Morrowind => &[
+                include_str!("../res/Morrowind Base Game Ingredients.yaml"),
+                include_str!("../res/Morrowind Tribunal Ingredients.yaml"),
+                include_str!("../res/Morrowind Bloodmoon Ingredients.yaml"),
+            ],
+            Ruleset::Oblivion => &[include_str!("../res/Oblivion Ingredients.yaml")],
+            Ruleset::Skyrim => &[include_str!("../res/Skyrim Ingredients.yaml")],
+        };
+
+        let mut ingredients = Vec::new();
+        for ingredient_list in ingredient_lists {
+            let mut ingredient_list: Vec<Ingredient> =
+                serde_yaml::from_str(ingredient_list).unwrap_or_else(|_| Vec::new());
+            ingredients.append(&mut ingredient_list);
+        }
+
+        sort_ingredients(ingredients)
+    }
+
+    /// Short lowercase name used in links and on the command line, e.g. "skyrim"
+    pub fn key(&self) -> &'static str {
+        match self {
+            Ruleset::Morrowind => "morrowind",
+            Ruleset::Oblivion => "oblivion",
+            Ruleset::Skyrim => "skyrim",
+        }
+    }
+}
+
+impl FromStr for Ruleset {
+    type Err = String;
+
+    /// Parse a game from its name, ignoring case
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "morrowind" => Ok(Ruleset::Morrowind),
+            "oblivion" => Ok(Ruleset::Oblivion),
+            "skyrim" => Ok(Ruleset::Skyrim),
+            _ => Err(format!("Unknown game: {}", name)),
+        }
+    }
+}
+
+impl Display for Ruleset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ruleset::Morrowind => f.write_str("Morrowind"),
+            Ruleset::Oblivion => f.write_str("Oblivion"),
+            Ruleset::Skyrim => f.write_str("Skyrim"),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use super::{
+    esm::{read_records, read_string, read_subrecords},
+    Apparatus, ApparatusType, Character, Ingredient,
+};
+
+/// Index of Intelligence in the attributes of an NPC_ NPDT subrecord or an OpenMW player
+const INTELLIGENCE_INDEX: usize = 1;
+/// Index of Personality in the attributes of an NPC_ NPDT subrecord or an OpenMW player
+const PERSONALITY_INDEX: usize = 6;
+/// Index of Luck in the attributes of an NPC_ NPDT subrecord or an OpenMW player
+const LUCK_INDEX: usize = 7;
+/// Index of Alchemy in the skills of an NPC_ NPDT subrecord or an OpenMW player
+const ALCHEMY_INDEX: usize = 16;
+/// Index of Mercantile in the skills of an NPC_ NPDT subrecord or an OpenMW player
+const MERCANTILE_INDEX: usize = 24;
+/// Number of skills, which an OpenMW player record saves before the attributes
+const SKILL_COUNT: usize = 27;
+/// Number of attributes
+const ATTRIBUTE_COUNT: usize = 8;
+/// Length of an NPC_ NPDT subrecord with full stats, rather than auto-calculated ones
+const NPDT_LENGTH: usize = 52;
+/// Length of an NPCO subrecord: a count followed by a 32 byte item ID
+const NPCO_LENGTH: usize = 36;
+
+/// A stack of items carried by the player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryItem {
+    /// Editor ID, e.g. "ingred_ash_yam_01"
+    pub id: String,
+    pub count: u32,
+}
+
+/// The parts of a Morrowind or OpenMW save game this tool uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveGame {
+    pub player_name: String,
+    /// Stats, along with the best apparatus of each type carried
+    pub character: Character,
+    pub inventory: Vec<InventoryItem>,
+}
+
+/// The player's ingredients, matched against the loaded ingredient lists
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IngredientCounts {
+    /// Count of each ingredient, by `Ingredient::id`
+    pub counts: BTreeMap<String, u32>,
+    /// Editor IDs of carried ingredients that aren't in the loaded ingredient lists
+    pub unrecognised: Vec<String>,
+}
+
+impl SaveGame {
+    /// Read a Morrowind.exe save (.ess) or an OpenMW save (.omwsave)
+    pub fn parse(data: &[u8]) -> Result<SaveGame, String> {
+        let records = read_records(data)?;
+        match records.first() {
+            Some(("TES3", _)) => {}
+            _ => return Err("Not a Morrowind save file".to_string()),
+        }
+        if records.iter().any(|(name, _)| *name == "SAVE") {
+            return Self::parse_openmw(&records);
+        }
+
+        let mut player_name = String::new();
+        let mut character = Character::default();
+        let mut npc_items = None;
+        let mut changed_items = None;
+        for (name, data) in records.iter().skip(1) {
+            if !matches!(*name, "NPC_" | "NPCC") {
+                continue;
+            }
+            let subrecords = read_subrecords(data)?;
+            let is_player = subrecords.iter().any(|(subrecord_name, subrecord_data)| {
+                *subrecord_name == "NAME"
+                    && read_string(subrecord_data).eq_ignore_ascii_case("player")
+            });
+            if !is_player {
+                continue;
+            }
+
+            let items = read_items(&subrecords)?;
+            if *name == "NPCC" {
+                // Changes to the player's inventory since the game began, so this is what they carry
+                changed_items = Some(items);
+                continue;
+            }
+
+            npc_items = Some(items);
+            for (subrecord_name, subrecord_data) in subrecords {
+                match subrecord_name {
+                    "FNAM" => player_name = read_string(subrecord_data),
+                    "NPDT" if subrecord_data.len() == NPDT_LENGTH => {
+                        let attributes = &subrecord_data[2..10];
+                        let skills = &subrecord_data[10..37];
+                        character.level =
+                            u16::from_le_bytes([subrecord_data[0], subrecord_data[1]]);
+                        character.intelligence = attributes[INTELLIGENCE_INDEX].into();
+                        character.luck = attributes[LUCK_INDEX].into();
+                        character.personality = attributes[PERSONALITY_INDEX].into();
+                        character.alchemy = skills[ALCHEMY_INDEX].into();
+                        character.mercantile = skills[MERCANTILE_INDEX].into();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let Some(npc_items) = npc_items else {
+            return Err("No player found in the save file".to_string());
+        };
+        let inventory = changed_items.unwrap_or(npc_items);
+
+        character.apparatus = carried_apparatus(&inventory);
+
+        Ok(SaveGame {
+            player_name,
+            character,
+            inventory,
+        })
+    }
+
+    /// Read an OpenMW save, which describes the player with a SAVE record for the save menu and a
+    /// PLAY record holding the player's reference, then their inventory and then their stats
+    fn parse_openmw(records: &[(&str, &[u8])]) -> Result<SaveGame, String> {
+        let mut player_name = String::new();
+        let mut character = Character::default();
+        for (_, data) in records.iter().filter(|(name, _)| *name == "SAVE") {
+            for (subrecord_name, subrecord_data) in read_subrecords(data)? {
+                match subrecord_name {
+                    "PLNM" => player_name = read_string(subrecord_data),
+                    "PLLE" => {
+                        let level = read_i32(subrecord_data)?;
+                        character.level = level.clamp(1, u16::MAX.into()) as u16;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let Some((_, player)) = records.iter().find(|(name, _)| *name == "PLAY") else {
+            return Err("No player found in the save file".to_string());
+        };
+        let mut items = Vec::new();
+        let mut stats = Vec::new();
+        let mut in_inventory = true;
+        // The first NAME is the player's own reference, each later one starts a carried item
+        let subrecords = read_subrecords(player)?;
+        let subrecords = subrecords
+            .iter()
+            .skip_while(|(subrecord_name, _)| *subrecord_name != "NAME")
+            .skip(1);
+        for (subrecord_name, subrecord_data) in subrecords {
+            match *subrecord_name {
+                "NAME" if in_inventory => items.push(InventoryItem {
+                    id: read_string(subrecord_data),
+                    count: 1,
+                }),
+                // Stacks of one have no count, and older versions of OpenMW call it COUN
+                "NAM9" | "COUN" if in_inventory => {
+                    let count = read_i32(subrecord_data)?.unsigned_abs();
+                    if let Some(item) = items.last_mut() {
+                        item.count = count;
+                    }
+                }
+                // The rest of the inventory: levelled items, equipment and the selected item
+                "LEVM" | "MAGI" | "EQUI" | "SELE" => in_inventory = false,
+                "STBA" => {
+                    in_inventory = false;
+                    stats.push(read_stat(subrecord_data)?);
+                }
+                _ => {}
+            }
+        }
+
+        // Skills come before attributes, and both before health, magicka and fatigue
+        if stats.len() < SKILL_COUNT + ATTRIBUTE_COUNT {
+            return Err("No player stats found in the save file".to_string());
+        }
+        let (skills, attributes) = stats.split_at(SKILL_COUNT);
+        character.intelligence = attributes[INTELLIGENCE_INDEX];
+        character.luck = attributes[LUCK_INDEX];
+        character.personality = attributes[PERSONALITY_INDEX];
+        character.alchemy = skills[ALCHEMY_INDEX];
+        character.mercantile = skills[MERCANTILE_INDEX];
+
+        let inventory = merge_items(items);
+        character.apparatus = carried_apparatus(&inventory);
+
+        Ok(SaveGame {
+            player_name,
+            character,
+            inventory,
+        })
+    }
+
+    /// Count the carried ingredients, matching editor IDs to the loaded ingredients
+    pub fn ingredient_counts(&self, ingredients: &[Rc<RefCell<Ingredient>>]) -> IngredientCounts {
+        let ingredient_ids: Vec<String> = ingredients
+            .iter()
+            .filter_map(|ingredient| {
+                ingredient
+                    .try_borrow()
+                    .ok()
+                    .map(|ingredient| ingredient.id())
+            })
+            .collect();
+
+        let editor_ids = editor_ids();
+        let mut ingredient_counts = IngredientCounts::default();
+        for item in self.inventory.iter() {
+            let editor_id = item.id.to_lowercase();
+            match editor_ids
+                .get(&editor_id)
+                .map(|name| Ingredient::id_from_name(name))
+            {
+                Some(ingredient_id) if ingredient_ids.contains(&ingredient_id) => {
+                    *ingredient_counts.counts.entry(ingredient_id).or_insert(0) += item.count;
+                }
+                // Modded ingredients follow the naming of the originals, anything else isn't one
+                None if !editor_id.starts_with("ingred_") => {}
+                _ => {
+                    if !ingredient_counts.unrecognised.contains(&item.id) {
+                        ingredient_counts.unrecognised.push(item.id.clone());
+                    }
+                }
+            }
+        }
+
+        ingredient_counts
+    }
+}
+
+/// The `Ingredient::id` of the Morrowind ingredient with this editor ID, or None if it isn't one,
+/// e.g. "ingred_guar_hide_marsus" is "marsus_guar_hide"
+pub fn ingredient_id_from_editor_id(editor_id: &str) -> Option<String> {
+    editor_ids()
+        .get(&editor_id.to_lowercase())
+        .map(|name| Ingredient::id_from_name(name))
+}
+
+/// The name of the ingredient for each lowercase editor ID of the bundled Morrowind ingredients
+fn editor_ids() -> BTreeMap<String, String> {
+    serde_yaml::from_str(include_str!("../res/Morrowind Editor IDs.yaml")).unwrap_or_default()
+}
+
+/// The best apparatus of each type among the items
+fn carried_apparatus(items: &[InventoryItem]) -> Apparatus {
+    let mut apparatus = Apparatus::default();
+    for item in items.iter() {
+        if let Some((apparatus_type, quality)) = ApparatusType::from_editor_id(&item.id) {
+            apparatus.add(apparatus_type, quality);
+        }
+    }
+
+    apparatus
+}
+
+/// Read the NPCO subrecords of a record, merging stacks of the same item
+fn read_items(subrecords: &[(&str, &[u8])]) -> Result<Vec<InventoryItem>, String> {
+    let mut items: Vec<InventoryItem> = Vec::new();
+    for (_, data) in subrecords.iter().filter(|(name, _)| *name == "NPCO") {
+        if data.len() != NPCO_LENGTH {
+            return Err("Invalid inventory entry in the save file".to_string());
+        }
+        // Negative counts mark items that restock, which doesn't matter here
+        let count = read_i32(&data[..4])?.unsigned_abs();
+        let id = read_string(&data[4..]);
+        items.push(InventoryItem { id, count });
+    }
+
+    Ok(merge_items(items))
+}
+
+/// Merge stacks of the same item, which may differ in case
+fn merge_items(items: Vec<InventoryItem>) -> Vec<InventoryItem> {
+    let mut merged: Vec<InventoryItem> = Vec::new();
+    for item in items {
+        match merged
+            .iter_mut()
+            .find(|merged_item| merged_item.id.eq_ignore_ascii_case(&item.id))
+        {
+            Some(merged_item) => merged_item.count += item.count,
+            None => merged.push(item),
+        }
+    }
+
+    merged
+}
+
+fn read_i32(data: &[u8]) -> Result<i32, String> {
+    let data: [u8; 4] = data
+        .try_into()
+        .map_err(|_| "Invalid number in the save file".to_string())?;
+    Ok(i32::from_le_bytes(data))
+}
+
+/// Read the base value of an OpenMW stat, which is a float
+/// Older saves store integers, which as floats would be tiny subnormal numbers
+fn read_stat(data: &[u8]) -> Result<u16, String> {
+    let bits = read_i32(data)?;
+    let value = if (0..0x0080_0000).contains(&bits) {
+        bits as f32
+    } else {
+        f32::from_bits(bits as u32)
+    };
+
+    Ok(value.round().clamp(0.0, u16::MAX.into()) as u16)
+}
@@ -0,0 +1,130 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use itertools::Itertools;
+
+use super::{merchants_selling, Barter, Character, Merchant, Potion};
+
+/// One ingredient on a shopping list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShoppingItem {
+    pub name: String,
+    /// How many the recipes use altogether
+    pub needed: u32,
+    /// How many of those are already owned
+    pub owned: u32,
+    /// `needed` less `owned`
+    pub to_buy: u32,
+    /// What buying `to_buy` of them costs
+    pub cost: u32,
+    /// Weight of `to_buy` of them
+    pub weight: f32,
+    /// The merchants who restock the ingredient
+    pub merchants: Vec<String>,
+    /// Where the ingredient can be harvested or looted, from its description
+    pub found: String,
+    /// As given in the ingredient list, e.g. "60" or "70x2", or empty if unknown
+    pub harvest_chance: String,
+}
+
+/// Everything to buy to brew a number of potions of each chosen recipe
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShoppingList {
+    /// Sorted by name, including ingredients that are already owned in full
+    pub items: Vec<ShoppingItem>,
+    pub total_cost: u32,
+    pub total_weight: f32,
+}
+
+impl ShoppingList {
+    /// The ingredients needed to brew `count` of each recipe, less the `inventory` of each
+    /// ingredient by ID, priced by `barter` with the character's stats
+    pub fn new(
+        recipes: &[(Potion, u32)],
+        inventory: &BTreeMap<String, u32>,
+        barter: &Barter,
+        character: &Character,
+        merchants: &[Merchant],
+    ) -> ShoppingList {
+        let mut needed = BTreeMap::new();
+        for (recipe, count) in recipes {
+            for ingredient in recipe.ingredients.iter().flatten() {
+                let Ok(borrowed_ingredient) = ingredient.try_borrow() else {
+                    continue; // Unable to borrow ingredient so continuing is better than crashing
+                };
+                needed
+                    .entry(borrowed_ingredient.name.clone())
+                    .or_insert((ingredient, 0))
+                    .1 += count;
+            }
+        }
+
+        let items: Vec<ShoppingItem> = needed
+            .into_values()
+            .filter_map(|(ingredient, needed)| {
+                let ingredient = ingredient.try_borrow().ok()?;
+                let owned = inventory
+                    .get(&ingredient.id())
+                    .copied()
+                    .unwrap_or(0)
+                    .min(needed);
+                let to_buy = needed - owned;
+                Some(ShoppingItem {
+                    name: ingredient.name.clone(),
+                    needed,
+                    owned,
+                    to_buy,
+                    cost: barter.buy_price(character, u32::from(ingredient.value)) * to_buy,
+                    weight: ingredient.weight * to_buy as f32,
+                    merchants: merchants_selling(merchants, &ingredient)
+                        .iter()
+                        .map(|merchant| merchant.to_string())
+                        .collect(),
+                    found: ingredient.description.clone(),
+                    harvest_chance: ingredient.harvest_chance.clone(),
+                })
+            })
+            .collect();
+
+        ShoppingList {
+            total_cost: items.iter().map(|item| item.cost).sum(),
+            total_weight: items.iter().map(|item| item.weight).sum(),
+            items,
+        }
+    }
+}
+
+/// Plain text, for reading off a phone or a second monitor
+impl Display for ShoppingList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Shopping List")?;
+        for item in self.items.iter() {
+            if item.to_buy == 0 {
+                writeln!(f, "[x] {} (have all {})", item.name, item.needed)?;
+                continue;
+            }
+            writeln!(
+                f,
+                "[ ] {} × {} (have {} of {}): {} gold, {:.1} weight",
+                item.to_buy, item.name, item.owned, item.needed, item.cost, item.weight
+            )?;
+            if !item.merchants.is_empty() {
+                writeln!(f, "    Buy from: {}", item.merchants.iter().join(", "))?;
+            }
+            if !item.found.is_empty() {
+                match item.harvest_chance.parse::<u32>() {
+                    Ok(harvest_chance) => writeln!(
+                        f,
+                        "    Found: {} ({}% harvest chance)",
+                        item.found, harvest_chance
+                    )?,
+                    Err(_) => writeln!(f, "    Found: {}", item.found)?,
+                }
+            }
+        }
+        write!(
+            f,
+            "Total: {} gold, {:.1} weight",
+            self.total_cost, self.total_weight
+        )
+    }
+}
@@ -0,0 +1,112 @@
+use std::{cell::RefCell, cmp::Reverse, rc::Rc};
+
+use itertools::Itertools;
+
+use super::{Effect, Ingredient, Potion};
+
+/// A recipe with one ingredient swapped for another, and how that changes the potion
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    pub substitute: Rc<RefCell<Ingredient>>,
+    pub potion: Potion,
+    /// Effects of the original potion that the new one still has
+    pub kept: Vec<Effect>,
+    /// Effects of the original potion that the new one doesn't have
+    pub lost: Vec<Effect>,
+    /// Effects only the new potion has
+    pub new: Vec<Effect>,
+}
+
+impl Substitution {
+    /// Swap `missing` in `recipe` for `substitute`
+    pub fn new(
+        recipe: &Potion,
+        missing: &Rc<RefCell<Ingredient>>,
+        substitute: &Rc<RefCell<Ingredient>>,
+    ) -> Substitution {
+        let ingredients: Vec<&Rc<RefCell<Ingredient>>> = recipe
+            .ingredients
+            .iter()
+            .flatten()
+            .map(|ingredient| {
+                if Rc::ptr_eq(ingredient, missing) {
+                    substitute
+                } else {
+                    ingredient
+                }
+            })
+            .collect();
+        let potion = Potion::new_potion_from_ingredients(&ingredients);
+        let (kept, lost) = recipe
+            .effects
+            .iter()
+            .partition(|effect| potion.effects.contains(effect));
+        let new = potion
+            .effects
+            .iter()
+            .filter(|effect| !recipe.effects.contains(effect))
+            .copied()
+            .collect();
+
+        Substitution {
+            substitute: substitute.clone(),
+            potion,
+            kept,
+            lost,
+            new,
+        }
+    }
+}
+
+/// Ingredients to use instead of `missing` in `recipe`, each sharing at least one of the effects
+/// `missing` adds to the potion
+/// Best first: those that keep the most effects, then add the fewest harmful and the fewest
+/// other new effects
+pub fn suggest_substitutes(
+    recipe: &Potion,
+    missing: &Rc<RefCell<Ingredient>>,
+    ingredients: &[Rc<RefCell<Ingredient>>],
+) -> Vec<Substitution> {
+    let Ok(borrowed_missing) = missing.try_borrow() else {
+        return Vec::new();
+    };
+    let contributing_effects: Vec<Effect> = borrowed_missing
+        .effects
+        .iter()
+        .flatten()
+        .filter(|effect| recipe.effects.contains(effect))
+        .copied()
+        .collect();
+
+    ingredients
+        .iter()
+        .filter(|ingredient| {
+            !recipe
+                .ingredients
+                .iter()
+                .flatten()
+                .any(|recipe_ingredient| Rc::ptr_eq(recipe_ingredient, ingredient))
+        })
+        .filter(|ingredient| match ingredient.try_borrow() {
+            Ok(ingredient) => ingredient
+                .effects
+                .iter()
+                .flatten()
+                .any(|effect| contributing_effects.contains(effect)),
+            Err(_) => false,
+        })
+        .map(|substitute| Substitution::new(recipe, missing, substitute))
+        .sorted_by_cached_key(|substitution| {
+            (
+                Reverse(substitution.kept.len()),
+                substitution
+                    .new
+                    .iter()
+                    .filter(|effect| effect.is_harmful())
+                    .count(),
+                substitution.new.len(),
+                substitution.potion.recipe_key(),
+            )
+        })
+        .collect()
+}
@@ -1,5 +1,74 @@
 use super::*;
 
+/// A test ingredient with `effects` in its first slots, which each module sets just the fields of
+/// that its tests need, e.g. `ingredient("Pearl", &[Effect::RestoreHealth]).value(100).shared()`
+struct TestIngredient(Ingredient);
+
+fn ingredient(name: &str, effects: &[Effect]) -> TestIngredient {
+    let mut ingredient_effects = [None; 4];
+    for (slot, effect) in ingredient_effects.iter_mut().zip(effects) {
+        *slot = Some(*effect);
+    }
+    TestIngredient(Ingredient {
+        name: name.to_string(),
+        ..Ingredient::new_default_ingredient_with_effects(ingredient_effects)
+    })
+}
+
+impl TestIngredient {
+    fn value(mut self, value: u16) -> Self {
+        self.0.value = value;
+        self
+    }
+
+    fn weight(mut self, weight: f32) -> Self {
+        self.0.weight = weight;
+        self
+    }
+
+    /// Where the ingredient is found and the chance of harvesting it there
+    fn found(mut self, description: &str, harvest_chance: &str) -> Self {
+        self.0.description = description.to_string();
+        self.0.harvest_chance = harvest_chance.to_string();
+        self
+    }
+
+    fn build(self) -> Ingredient {
+        self.0
+    }
+
+    fn shared(self) -> Rc<RefCell<Ingredient>> {
+        Rc::new(RefCell::new(self.0))
+    }
+}
+
+/// Share test ingredients the way loaded ingredient lists are
+fn shared<const N: usize>(ingredients: [TestIngredient; N]) -> Vec<Rc<RefCell<Ingredient>>> {
+    ingredients
+        .into_iter()
+        .map(TestIngredient::shared)
+        .collect()
+}
+
+/// The bundled Morrowind ingredients with these names, in this order, for tests real data suits
+fn bundled(names: &[&str]) -> Vec<Rc<RefCell<Ingredient>>> {
+    let ingredients = bundled_ingredients();
+    names
+        .iter()
+        .map(|name| {
+            ingredients
+                .iter()
+                .find(|ingredient| ingredient.borrow().name == *name)
+                .unwrap_or_else(|| panic!("{} isn't a bundled ingredient", name))
+                .clone()
+        })
+        .collect()
+}
+
+fn potion_from(ingredients: &[Rc<RefCell<Ingredient>>]) -> Potion {
+    Potion::new_potion_from_ingredients(&ingredients.iter().collect::<Vec<_>>())
+}
+
 // use lazy_static::lazy_static;
 // lazy_static! {
 //     static ref INGREDIENTS: Vec<Ingredient> = {
@@ -878,3 +947,3357 @@ mod four_ingredient_tests {
         assert!(actual_effects.is_empty());
     }
 }
+
+mod canonical_form_tests {
+    use super::*;
+
+    fn ash_yam() -> Ingredient {
+        ingredient(
+            "Ash Yam",
+            &[
+                Effect::FortifyIntelligence,
+                Effect::FortifyStrength,
+                Effect::ResistCommonDisease,
+                Effect::DetectKey,
+            ],
+        )
+        .build()
+    }
+
+    fn bungler_s_bane() -> Ingredient {
+        ingredient(
+            "Bungler's Bane",
+            &[
+                Effect::DrainSpeed,
+                Effect::DrainEndurance,
+                Effect::FortifyStrength,
+                Effect::DetectKey,
+            ],
+        )
+        .build()
+    }
+
+    fn corkbulb_root() -> Ingredient {
+        ingredient(
+            "Corkbulb Root",
+            &[
+                Effect::CureParalyzation,
+                Effect::RestoreHealth,
+                Effect::DetectKey,
+                Effect::FortifyLuck,
+            ],
+        )
+        .build()
+    }
+
+    #[test]
+    fn test_ingredient_id_is_derived_from_name() {
+        assert_eq!(ash_yam().id(), "ash_yam");
+        assert_eq!(bungler_s_bane().id(), "bunglers_bane");
+        assert_eq!(
+            ingredient("Marsus' Guar Hide", &[]).build().id(),
+            "marsus_guar_hide"
+        );
+    }
+
+    #[test]
+    fn test_potion_effects_are_in_canonical_order() {
+        let potion = Potion::new_potion_from_optional_ingredients(&[
+            Some(bungler_s_bane()),
+            Some(ash_yam()),
+            None,
+            None,
+        ]);
+
+        assert_eq!(potion.effects, [Effect::DetectKey, Effect::FortifyStrength]);
+    }
+
+    #[test]
+    fn test_potion_ingredients_are_sorted_by_id() {
+        let potion = Potion::new_potion_from_optional_ingredients(&[
+            Some(corkbulb_root()),
+            None,
+            Some(bungler_s_bane()),
+            Some(ash_yam()),
+        ]);
+
+        assert_eq!(
+            potion.ingredient_ids(),
+            ["ash_yam", "bunglers_bane", "corkbulb_root"]
+        );
+        assert!(potion.ingredients[3].is_none());
+    }
+
+    #[test]
+    fn test_recipe_identity_ignores_ingredient_order() {
+        let potion_a = Potion::new_potion_from_optional_ingredients(&[
+            Some(ash_yam()),
+            Some(corkbulb_root()),
+            None,
+            None,
+        ]);
+        let potion_b = Potion::new_potion_from_optional_ingredients(&[
+            Some(corkbulb_root()),
+            Some(ash_yam()),
+            None,
+            None,
+        ]);
+
+        assert_eq!(potion_a.recipe_key(), "ash_yam+corkbulb_root");
+        assert_eq!(potion_a.recipe_key(), potion_b.recipe_key());
+        assert_eq!(potion_a, potion_b);
+    }
+}
+
+mod recipe_code_tests {
+    use super::*;
+
+    #[test]
+    fn test_recipe_code_round_trip_without_query() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let potion = potion_from(&ingredients[1..]);
+
+        let code = RecipeCode::from_potion(&potion, None).encode();
+        let decoded = RecipeCode::decode(&code, &ingredients).unwrap();
+
+        assert_eq!(decoded.ingredient_ids, ["corkbulb_root", "saltrice"]);
+        assert_eq!(decoded.query, None);
+        assert_eq!(potion_from(&decoded.ingredients(&ingredients)), potion);
+    }
+
+    #[test]
+    fn test_recipe_code_round_trip_with_query() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let potion = potion_from(&ingredients);
+        let query = RecipeQuery {
+            desired_effects: [
+                Some(Effect::RestoreHealth),
+                None,
+                Some(Effect::DetectKey),
+                None,
+            ],
+            allow_extra_effects: true,
+        };
+
+        let code = RecipeCode::from_potion(&potion, Some(query)).encode();
+        let decoded = RecipeCode::decode(&code, &ingredients).unwrap();
+
+        assert_eq!(
+            decoded.ingredient_ids,
+            ["ash_yam", "corkbulb_root", "saltrice"]
+        );
+        assert_eq!(
+            decoded.query,
+            Some(RecipeQuery {
+                desired_effects: [
+                    Some(Effect::RestoreHealth),
+                    Some(Effect::DetectKey),
+                    None,
+                    None
+                ],
+                allow_extra_effects: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_recipe_code_is_short_and_url_safe() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let code = RecipeCode::from_potion(&potion_from(&ingredients[..2]), None).encode();
+
+        assert!(code.len() <= 16);
+        assert!(code
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric()
+                || character == '-'
+                || character == '_'));
+    }
+
+    #[test]
+    fn test_recipe_code_survives_new_ingredients() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let code = RecipeCode::from_potion(&potion_from(&ingredients[..2]), None).encode();
+
+        let mut more_ingredients = vec![ingredient("Alit Hide", &[]).shared()];
+        more_ingredients.extend(ingredients);
+
+        let decoded = RecipeCode::decode(&code, &more_ingredients).unwrap();
+        assert_eq!(decoded.ingredient_ids, ["ash_yam", "corkbulb_root"]);
+    }
+
+    #[test]
+    fn test_recipe_code_rejects_unknown_ingredient() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let code = RecipeCode::from_potion(&potion_from(&ingredients[..2]), None).encode();
+
+        assert_eq!(
+            RecipeCode::decode(&code, &ingredients[1..]),
+            Err(RecipeCodeError::UnknownIngredient)
+        );
+    }
+
+    #[test]
+    fn test_recipe_code_rejects_duplicate_ingredient() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let code = RecipeCode {
+            ingredient_ids: vec!["ash_yam".to_string(), "ash_yam".to_string()],
+            query: None,
+        }
+        .encode();
+
+        assert_eq!(
+            RecipeCode::decode(&code, &ingredients),
+            Err(RecipeCodeError::DuplicateIngredient)
+        );
+    }
+
+    #[test]
+    fn test_recipe_code_rejects_invalid_codes() {
+        let ingredients = bundled(&["Ash Yam", "Corkbulb Root", "Saltrice"]);
+        let code = RecipeCode::from_potion(&potion_from(&ingredients[..2]), None).encode();
+
+        assert_eq!(
+            RecipeCode::decode("", &ingredients),
+            Err(RecipeCodeError::Empty)
+        );
+        assert_eq!(
+            RecipeCode::decode("not a code!", &ingredients),
+            Err(RecipeCodeError::InvalidEncoding)
+        );
+        // A truncated code must never decode to a different recipe
+        assert!(RecipeCode::decode(&code[..code.len() - 2], &ingredients).is_err());
+
+        let mut damaged = code.into_bytes();
+        damaged[4] = if damaged[4] == b'A' { b'B' } else { b'A' };
+        assert_eq!(
+            RecipeCode::decode(&String::from_utf8(damaged).unwrap(), &ingredients),
+            Err(RecipeCodeError::ChecksumMismatch)
+        );
+    }
+}
+
+mod deep_link_tests {
+    use super::*;
+
+    #[test]
+    fn test_effect_from_display_or_serialized_name() {
+        assert_eq!("Restore Health".parse(), Ok(Effect::RestoreHealth));
+        assert_eq!("RestoreHealth".parse(), Ok(Effect::RestoreHealth));
+        assert_eq!("weakness to fire".parse(), Ok(Effect::WeaknessToFire));
+        assert!("Restore Everything".parse::<Effect>().is_err());
+    }
+
+    #[test]
+    fn test_deep_link_round_trip() {
+        let deep_link = DeepLink {
+            ruleset: Ruleset::Morrowind,
+            desired_effects: [
+                Some(Effect::RestoreHealth),
+                Some(Effect::RestoreFatigue),
+                None,
+                None,
+            ],
+            excluded_effects: [Some(Effect::Poison), None, None, None],
+            allow_extra_effects: true,
+            selected_ingredient_ids: vec!["ash_yam".to_string(), "saltrice".to_string()],
+        };
+
+        let fragment = deep_link.to_fragment();
+        assert_eq!(
+            fragment,
+            "effects=RestoreHealth,RestoreFatigue&exclude=Poison&extra=1&ingredients=ash_yam,saltrice"
+        );
+        assert_eq!(DeepLink::from_fragment(&fragment), deep_link);
+    }
+
+    #[test]
+    fn test_deep_link_from_hand_written_fragment() {
+        let deep_link =
+            DeepLink::from_fragment("#effects=RestoreHealth,Restore%20Nothing&unknown=1");
+
+        assert_eq!(
+            deep_link.desired_effects,
+            [Some(Effect::RestoreHealth), None, None, None]
+        );
+        assert!(!deep_link.allow_extra_effects);
+        assert!(deep_link.selected_ingredient_ids.is_empty());
+    }
+
+    #[test]
+    fn test_blank_deep_link_has_empty_fragment() {
+        assert_eq!(DeepLink::default().to_fragment(), "");
+        assert_eq!(DeepLink::from_fragment(""), DeepLink::default());
+    }
+}
+
+#[cfg(feature = "gui")]
+mod persistence_tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStorage {
+        values: HashMap<String, String>,
+    }
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.values.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.values.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn persisted_state() -> PersistedState {
+        PersistedState::from_deep_link(
+            DeepLink {
+                ruleset: Ruleset::Morrowind,
+                desired_effects: [Some(Effect::RestoreHealth), None, None, None],
+                excluded_effects: [Some(Effect::Poison), None, None, None],
+                allow_extra_effects: true,
+                selected_ingredient_ids: vec!["saltrice".to_string()],
+            },
+            false,
+        )
+    }
+
+    #[test]
+    fn test_persisted_state_round_trip() {
+        let mut storage = MemoryStorage::default();
+        persisted_state().save(&mut storage);
+
+        assert_eq!(PersistedState::load(&storage), Some(persisted_state()));
+    }
+
+    #[test]
+    fn test_persisted_state_keeps_query() {
+        let persisted_state = persisted_state();
+
+        assert_eq!(
+            persisted_state.deep_link().to_fragment(),
+            "effects=RestoreHealth&exclude=Poison&extra=1&ingredients=saltrice"
+        );
+        assert!(!persisted_state.dark_mode);
+    }
+
+    #[test]
+    fn test_missing_persisted_state() {
+        assert_eq!(PersistedState::load(&MemoryStorage::default()), None);
+    }
+
+    #[test]
+    fn test_corrupt_persisted_state_is_discarded() {
+        let mut storage = MemoryStorage::default();
+        eframe::Storage::set_string(
+            &mut storage,
+            eframe::APP_KEY,
+            "(version: 1, desired".to_string(),
+        );
+
+        assert_eq!(PersistedState::load(&storage), None);
+    }
+
+    #[test]
+    fn test_outdated_persisted_state_is_discarded() {
+        let mut storage = MemoryStorage::default();
+        PersistedState {
+            version: PERSISTED_STATE_VERSION + 1,
+            ..persisted_state()
+        }
+        .save(&mut storage);
+
+        assert_eq!(PersistedState::load(&storage), None);
+    }
+
+    #[test]
+    fn test_unversioned_persisted_state_is_discarded() {
+        // The whole app, as stored before the state was versioned
+        let mut storage = MemoryStorage::default();
+        eframe::Storage::set_string(
+            &mut storage,
+            eframe::APP_KEY,
+            "(ingredients: [], desired_effects: (Some(RestoreHealth), None, None, None), \
+             previous_effects: (None, None, None, None), potential_ingredients: [], \
+             filtered_ingredients: [], potential_potions: [], allow_extra_effects: true, \
+             visuals: ())"
+                .to_string(),
+        );
+
+        assert_eq!(PersistedState::load(&storage), None);
+        assert_eq!(
+            PersistedState::load(&storage).unwrap_or_default(),
+            PersistedState::default()
+        );
+    }
+
+    #[test]
+    fn test_recipe_book_outlives_outdated_persisted_state() {
+        let mut storage = MemoryStorage::default();
+        let recipe_book = RecipeBook {
+            favourites: vec![Favourite {
+                ruleset: Ruleset::Skyrim,
+                ingredient_ids: vec!["blue_mountain_flower".to_string(), "wheat".to_string()],
+                note: "sell to Arcadia".to_string(),
+            }],
+        };
+        recipe_book.save(&mut storage);
+        PersistedState {
+            version: PERSISTED_STATE_VERSION + 1,
+            ..persisted_state()
+        }
+        .save(&mut storage);
+
+        assert_eq!(PersistedState::load(&storage), None);
+        assert_eq!(RecipeBook::load(&storage), recipe_book);
+    }
+
+    #[test]
+    fn test_recipe_book_moves_out_of_persisted_state() {
+        // The recipe book as stored inside the state by older versions of the tool
+        let mut storage = MemoryStorage::default();
+        eframe::Storage::set_string(
+            &mut storage,
+            eframe::APP_KEY,
+            "(version: 1, recipe_book: (favourites: [(ingredient_ids: [\"ash_yam\", \"saltrice\"], \
+             note: \"for the Telvanni quest\")]))"
+                .to_string(),
+        );
+
+        let recipe_book = RecipeBook::load(&storage);
+
+        assert_eq!(recipe_book.favourites.len(), 1);
+        assert_eq!(recipe_book.favourites[0].ruleset, Ruleset::Morrowind);
+        assert_eq!(recipe_book.favourites[0].recipe_key(), "ash_yam+saltrice");
+        assert_eq!(
+            RecipeBook::load(&MemoryStorage::default()),
+            RecipeBook::default()
+        );
+    }
+}
+
+mod recipe_book_tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_favourite() {
+        let potion = potion_from(&bundled(&["Corkbulb Root", "Saltrice"]));
+        let mut recipe_book = RecipeBook::default();
+
+        recipe_book.toggle(Ruleset::Morrowind, &potion);
+        assert!(recipe_book.is_favourite(Ruleset::Morrowind, &potion));
+        assert!(!recipe_book.is_favourite(Ruleset::Oblivion, &potion));
+        assert_eq!(recipe_book.favourites[0].recipe_key(), potion.recipe_key());
+
+        // The same ingredients are a separate favourite in another game
+        recipe_book.toggle(Ruleset::Oblivion, &potion);
+        assert_eq!(recipe_book.favourites.len(), 2);
+        assert_eq!(recipe_book.favourites(Ruleset::Oblivion).count(), 1);
+
+        recipe_book.toggle(Ruleset::Morrowind, &potion);
+        assert!(!recipe_book.is_favourite(Ruleset::Morrowind, &potion));
+        assert!(recipe_book.is_favourite(Ruleset::Oblivion, &potion));
+    }
+
+    #[test]
+    fn test_favourite_recreates_potion() {
+        let ingredients = bundled(&["Corkbulb Root", "Saltrice"]);
+        let potion = potion_from(&ingredients);
+        let favourite = Favourite::from_potion(Ruleset::Morrowind, &potion);
+
+        assert_eq!(favourite.potion(&ingredients), Some(potion));
+        assert_eq!(favourite.potion(&ingredients[..1]), None);
+    }
+
+    #[test]
+    fn test_recipe_book_export_import() {
+        let potion = potion_from(&bundled(&["Corkbulb Root", "Saltrice"]));
+        let mut recipe_book = RecipeBook::default();
+        recipe_book.toggle(Ruleset::Skyrim, &potion);
+        recipe_book.favourites[0].note = "sell to Ajira".to_string();
+
+        let mut imported = RecipeBook::default();
+        assert_eq!(imported.import(&recipe_book.export().unwrap()), Ok(1));
+        assert_eq!(imported, recipe_book);
+    }
+
+    #[test]
+    fn test_recipe_book_import_merges() {
+        let mut recipe_book = RecipeBook {
+            favourites: vec![Favourite {
+                ruleset: Ruleset::Morrowind,
+                ingredient_ids: vec!["ash_yam".to_string(), "saltrice".to_string()],
+                note: "for the Telvanni quest".to_string(),
+            }],
+        };
+
+        let new_favourites = recipe_book.import(
+            r#"favourites:
+- ingredient_ids: [saltrice, ash_yam]
+  note: sell to Ajira
+- ingredient_ids: [bread, saltrice]
+"#,
+        );
+
+        assert_eq!(new_favourites, Ok(1));
+        assert_eq!(recipe_book.favourites.len(), 2);
+        assert_eq!(recipe_book.favourites[0].note, "for the Telvanni quest");
+        assert_eq!(recipe_book.favourites[1].recipe_key(), "bread+saltrice");
+    }
+
+    #[test]
+    fn test_recipe_book_import_rejects_invalid_data() {
+        let mut recipe_book = RecipeBook::default();
+
+        assert!(recipe_book.import("not a recipe book").is_err());
+        assert!(recipe_book.favourites.is_empty());
+    }
+
+    #[test]
+    fn test_stored_recipe_book_keeps_readable_favourites() {
+        let recipe_book = RecipeBook::read_lenient(
+            r#"favourites:
+- ingredient_ids: [ash_yam, saltrice]
+- ingredient_ids: 12
+- ruleset: Oblivion
+  ingredient_ids: [garlic, wheat_grain]
+  note: for sale
+"#,
+        );
+
+        assert_eq!(
+            recipe_book.favourites,
+            vec![
+                Favourite {
+                    ruleset: Ruleset::Morrowind,
+                    ingredient_ids: vec!["ash_yam".to_string(), "saltrice".to_string()],
+                    note: String::new(),
+                },
+                Favourite {
+                    ruleset: Ruleset::Oblivion,
+                    ingredient_ids: vec!["garlic".to_string(), "wheat_grain".to_string()],
+                    note: "for sale".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            RecipeBook::read_lenient("not a recipe book"),
+            RecipeBook::default()
+        );
+    }
+}
+
+mod export_tests {
+    use super::*;
+
+    fn potions() -> Vec<Potion> {
+        vec![potion_from(&bundled(&["Bungler's Bane", "Saltrice"]))]
+    }
+
+    #[test]
+    fn test_export_potions_as_csv() {
+        let csv = export_potions(&potions(), ExportFormat::Csv).unwrap();
+        let code = RecipeCode::from_potion(&potions()[0], None).encode();
+
+        assert_eq!(
+            csv,
+            format!(
+                "Ingredients,Effects,Total Value,Total Weight,Recipe Code\nBungler's Bane; Saltrice,Drain Strength,2,0.6,{}\n",
+                code
+            )
+        );
+    }
+
+    #[test]
+    fn test_export_potions_as_json() {
+        let json = export_potions(&potions(), ExportFormat::Json).unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            records[0]["ingredients"],
+            serde_json::json!(["Bungler's Bane", "Saltrice"])
+        );
+        assert_eq!(records[0]["effects"], serde_json::json!(["Drain Strength"]));
+        assert_eq!(records[0]["total_value"], 2);
+    }
+
+    #[test]
+    fn test_export_potions_as_markdown() {
+        let markdown = export_potions(&potions(), ExportFormat::Markdown).unwrap();
+        let lines: Vec<&str> = markdown.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "| Ingredients | Effects | Total Value | Total Weight | Recipe Code |"
+        );
+        assert_eq!(lines[1], "| --- | --- | --- | --- | --- |");
+        assert!(lines[2].starts_with("| Bungler's Bane; Saltrice | Drain Strength | 2 | 0.6 |"));
+    }
+
+    #[test]
+    fn test_export_brewed_potions() {
+        let brewing = BrewingDetails {
+            engine: Engine::OpenMw,
+            character: Character {
+                alchemy: 59,
+                intelligence: 60,
+                luck: 40,
+                ..Default::default()
+            },
+            barter: Barter::default(),
+        };
+
+        let csv = export_brewed_potions(&potions(), &brewing, ExportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Ingredients,Effects,Total Value,Total Weight,Recipe Code,Brewed Effects,Brewed Value,Ingredient Cost"
+        );
+        let brewed_potion = brewing.engine.brew(&potions()[0], &brewing.character);
+        assert!(lines[1].ends_with(&format!(
+            ",{},{},{}",
+            brewed_potion.effects[0],
+            brewed_potion.value,
+            brewing
+                .barter
+                .recipe_cost(&brewing.character, &potions()[0])
+        )));
+
+        let json = export_brewed_potions(&potions(), &brewing, ExportFormat::Json).unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            records[0]["brewed_value"],
+            serde_json::json!(brewed_potion.value)
+        );
+        // Without brewing details the columns are left out altogether
+        let json = export_potions(&potions(), ExportFormat::Json).unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(records[0].get("brewed_value").is_none());
+    }
+
+    #[test]
+    fn test_export_ingredients_escapes_csv() {
+        let ingredients = bundled(&["Bungler's Bane"]);
+        ingredients[0].borrow_mut().name = "Bane, \"Bungler's\"".to_string();
+
+        let csv = export_ingredients(&ingredients[..1], ExportFormat::Csv).unwrap();
+
+        assert_eq!(
+            csv,
+            "ID,Name,Effects,Value,Weight,Harvest Chance,Selected\nbane_bunglers,\"Bane, \"\"Bungler's\"\"\",Drain Speed; Drain Endurance; Dispel; Drain Strength,1,0.5,90,false\n"
+        );
+    }
+}
+
+mod cli_tests {
+    use super::*;
+
+    pub(super) fn run_cli(args: &[&str]) -> Result<String, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cli::run(&args).map(|output| match output {
+            cli::Output::Text(text) => text,
+            #[cfg(feature = "server")]
+            cli::Output::Serve(_) => panic!("Expected text, not a server"),
+        })
+    }
+
+    #[test]
+    fn test_find_as_json() {
+        let json = run_cli(&[
+            "find",
+            "--effect",
+            "Fortify Strength",
+            "--max-ingredients",
+            "2",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let records = records.as_array().unwrap();
+
+        assert!(!records.is_empty());
+        for record in records {
+            assert_eq!(record["effects"], serde_json::json!(["Fortify Strength"]));
+            assert_eq!(record["ingredients"].as_array().unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_find_matches_the_gui_query() {
+        let ingredients = load_ingredients(Path::new("config.yaml")).unwrap();
+        let query = PotionQuery {
+            desired_effects: [
+                Some(Effect::RestoreHealth),
+                Some(Effect::RestoreFatigue),
+                None,
+                None,
+            ],
+            allow_extra_effects: true,
+            max_ingredients: 3,
+            ..Default::default()
+        };
+        let expected = query.find_potions(&get_potential_ingredients(
+            &query.desired_effects,
+            &ingredients,
+        ));
+
+        let csv = run_cli(&[
+            "find",
+            "--effect",
+            "restore health",
+            "--effect",
+            "RestoreFatigue",
+            "--allow-extra",
+            "--max-ingredients",
+            "3",
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+
+        assert_eq!(csv, export_potions(&expected, ExportFormat::Csv).unwrap());
+    }
+
+    #[test]
+    fn test_find_only_uses_given_ingredients() {
+        let table = run_cli(&[
+            "find",
+            "--effect",
+            "Fortify Strength",
+            "--ingredient",
+            "ash_yam",
+            "--ingredient",
+            "Daedra Skin",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            table,
+            "Ingredients           Effects           Value\n\
+             --------------------  ----------------  -----\n\
+             Ash Yam, Daedra Skin  Fortify Strength  201\n\
+             1 potions found\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert_eq!(
+            run_cli(&["find", "--effect", "Bogus"]),
+            Err("Unknown effect: Bogus".to_string())
+        );
+        assert!(run_cli(&["find"]).is_err());
+        assert!(run_cli(&["find", "--effect"]).is_err());
+        assert!(run_cli(&["find", "--effect", "Burden", "--max-ingredients", "5"]).is_err());
+        assert!(run_cli(&["find", "--effect", "Burden", "--ingredient", "Bogus"]).is_err());
+        assert!(run_cli(&["find", "--effect", "Burden", "--format", "xml"]).is_err());
+        assert!(run_cli(&["brew"]).is_err());
+    }
+}
+
+mod library_tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_ingredients_match_config() {
+        // A game install on this machine is only used when asked for, so the lists are loaded
+        let bundled_ingredients: Vec<String> = bundled_ingredients()
+            .iter()
+            .map(|ingredient| ingredient.borrow().id())
+            .collect();
+        let loaded_ingredients: Vec<String> = load_ingredients(Path::new("config.yaml"))
+            .unwrap()
+            .iter()
+            .map(|ingredient| ingredient.borrow().id())
+            .collect();
+
+        assert!(!bundled_ingredients.is_empty());
+        assert_eq!(bundled_ingredients, loaded_ingredients);
+    }
+
+    #[test]
+    fn test_search_ignores_ingredients_without_desired_effects() {
+        let ingredients = bundled_ingredients();
+        let query = PotionQuery {
+            desired_effects: [Some(Effect::FortifyStrength), None, None, None],
+            max_ingredients: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            query.search(&ingredients),
+            query.find_potions(&get_potential_ingredients(
+                &query.desired_effects,
+                &ingredients
+            ))
+        );
+    }
+}
+
+mod api_tests {
+    #[cfg(feature = "server")]
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        sync::mpsc,
+        thread,
+    };
+
+    use super::*;
+    #[cfg(feature = "server")]
+    use crate::api::ApiServer;
+    use crate::api::{Api, ApiResponse};
+
+    fn json(response: &ApiResponse) -> serde_json::Value {
+        serde_json::from_str(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_list_effects_and_ingredients() {
+        let api = Api::new(bundled_ingredients());
+
+        let effects = api.handle("GET", "/effects", "");
+        assert_eq!(effects.status, 200);
+        assert!(json(&effects)
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("Restore Health")));
+
+        let ingredients = api.handle("GET", "/ingredients/", "");
+        assert_eq!(ingredients.status, 200);
+        assert_eq!(
+            json(&ingredients).as_array().unwrap().len(),
+            bundled_ingredients().len()
+        );
+    }
+
+    #[test]
+    fn test_potions_query() {
+        let ingredients = bundled_ingredients();
+        let api = Api::new(ingredients.clone());
+
+        let response = api.handle(
+            "POST",
+            "/potions",
+            r#"{"effects": ["Restore Health", "RestoreFatigue"], "allow_extra_effects": true, "max_ingredients": 3}"#,
+        );
+
+        let expected = PotionQuery {
+            desired_effects: [
+                Some(Effect::RestoreHealth),
+                Some(Effect::RestoreFatigue),
+                None,
+                None,
+            ],
+            allow_extra_effects: true,
+            max_ingredients: 3,
+            ..Default::default()
+        }
+        .search(&ingredients);
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            json(&response),
+            serde_json::from_str::<serde_json::Value>(
+                &export_potions(&expected, ExportFormat::Json).unwrap()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mix() {
+        let api = Api::new(bundled_ingredients());
+
+        let response = api.handle(
+            "POST",
+            "/mix",
+            r#"{"ingredients": ["daedra_skin", "Ash Yam"]}"#,
+        );
+
+        assert_eq!(response.status, 200);
+        let potion = json(&response);
+        assert_eq!(
+            potion["ingredients"],
+            serde_json::json!(["Ash Yam", "Daedra Skin"])
+        );
+        assert_eq!(potion["effects"], serde_json::json!(["Fortify Strength"]));
+        assert_eq!(potion["total_value"], 201);
+    }
+
+    #[test]
+    fn test_errors() {
+        let api = Api::new(bundled_ingredients());
+
+        assert_eq!(api.handle("GET", "/brew", "").status, 404);
+        assert_eq!(api.handle("DELETE", "/effects", "").status, 405);
+        let unknown_effect = api.handle("POST", "/potions", r#"{"effects": ["Bogus"]}"#);
+        assert_eq!(unknown_effect.status, 400);
+        assert_eq!(
+            json(&unknown_effect),
+            serde_json::json!({ "error": "Unknown effect: Bogus" })
+        );
+        assert_eq!(api.handle("POST", "/potions", "not json").status, 400);
+        assert_eq!(
+            api.handle("POST", "/potions", r#"{"effects": []}"#).status,
+            400
+        );
+        assert_eq!(
+            api.handle("POST", "/mix", r#"{"ingredients": ["ash_yam"]}"#)
+                .status,
+            400
+        );
+        assert_eq!(
+            api.handle("POST", "/mix", r#"{"ingredients": ["ash_yam", "Ash Yam"]}"#)
+                .status,
+            400
+        );
+    }
+
+    /// Send one mix request to a server allowing `allowed_origin`, returning the raw response
+    #[cfg(feature = "server")]
+    fn mix_over_http(allowed_origin: Option<String>) -> String {
+        let (port_sender, port_receiver) = mpsc::channel();
+        let server_thread = thread::spawn(move || {
+            let server =
+                ApiServer::bind(Api::new(bundled_ingredients()), 0, allowed_origin).unwrap();
+            port_sender.send(server.port()).unwrap();
+            server.handle_next().unwrap();
+        });
+        let port = port_receiver.recv().unwrap();
+
+        let body = r#"{"ingredients": ["ash_yam", "daedra_skin"]}"#;
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            stream,
+            "POST /mix HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        server_thread.join().unwrap();
+        response
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_server_answers_over_http() {
+        let response = mix_over_http(None);
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("Content-Type: application/json"));
+        // Web pages may only call the API once an origin is allowed
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let potion: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(potion["effects"], serde_json::json!(["Fortify Strength"]));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_server_allows_only_the_given_origin() {
+        let response = mix_over_http(Some("http://localhost:3000".to_string()));
+
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:3000"));
+        assert!(ApiServer::bind(
+            Api::new(Vec::new()),
+            0,
+            Some("http://localhost:3000\r\nX-Injected: 1".to_string())
+        )
+        .is_err());
+    }
+}
+
+mod save_file_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    pub(super) fn subrecord(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut subrecord = name.as_bytes().to_vec();
+        subrecord.extend((data.len() as u32).to_le_bytes());
+        subrecord.extend(data);
+        subrecord
+    }
+
+    pub(super) fn record(name: &str, subrecords: &[Vec<u8>]) -> Vec<u8> {
+        let data = subrecords.concat();
+        let mut record = name.as_bytes().to_vec();
+        record.extend((data.len() as u32).to_le_bytes());
+        record.extend([0; 8]);
+        record.extend(data);
+        record
+    }
+
+    pub(super) fn string(value: &str) -> Vec<u8> {
+        let mut string = value.as_bytes().to_vec();
+        string.push(0);
+        string
+    }
+
+    fn npdt(
+        level: u16,
+        intelligence: u8,
+        personality: u8,
+        luck: u8,
+        alchemy: u8,
+        mercantile: u8,
+    ) -> Vec<u8> {
+        let mut npdt = vec![0; 52];
+        npdt[0..2].copy_from_slice(&level.to_le_bytes());
+        npdt[2 + 1] = intelligence;
+        npdt[2 + 6] = personality;
+        npdt[2 + 7] = luck;
+        npdt[10 + 16] = alchemy;
+        npdt[10 + 24] = mercantile;
+        subrecord("NPDT", &npdt)
+    }
+
+    fn npco(count: i32, id: &str) -> Vec<u8> {
+        let mut npco = count.to_le_bytes().to_vec();
+        let mut name = [0; 32];
+        name[..id.len()].copy_from_slice(id.as_bytes());
+        npco.extend(name);
+        subrecord("NPCO", &npco)
+    }
+
+    /// A minimal save: a header, another NPC, the player and the player's changed inventory
+    fn save_file() -> Vec<u8> {
+        [
+            record("TES3", &[subrecord("HEDR", &[0; 300])]),
+            record(
+                "NPC_",
+                &[
+                    subrecord("NAME", &string("fargoth")),
+                    npdt(2, 30, 30, 30, 1, 1),
+                    npco(5, "ingred_bread_01"),
+                ],
+            ),
+            record(
+                "NPC_",
+                &[
+                    subrecord("NAME", &string("player")),
+                    subrecord("FNAM", &string("Nerevar")),
+                    npdt(12, 65, 52, 48, 47, 21),
+                    npco(1, "ingred_ash_yam_01"),
+                ],
+            ),
+            record(
+                "NPCC",
+                &[
+                    subrecord("NAME", &string("PlayeR")),
+                    subrecord("NPDT", &[0; 8]),
+                    npco(3, "ingred_ash_yam_01"),
+                    npco(2, "Ingred_Ash_Yam_01"),
+                    npco(-1, "ingred_bc_bungler's_bane"),
+                    npco(1, "ingred_moddedroot_01"),
+                    npco(1, "apparatus_a_mortar_01"),
+                    npco(1, "apparatus_m_mortar_01"),
+                    npco(1, "apparatus_j_alembic_01"),
+                    npco(100, "gold_001"),
+                ],
+            ),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_read_player_from_save() {
+        let save_game = SaveGame::parse(&save_file()).unwrap();
+
+        assert_eq!(save_game.player_name, "Nerevar");
+        assert_eq!(
+            save_game.character,
+            Character {
+                level: 12,
+                alchemy: 47,
+                intelligence: 65,
+                luck: 48,
+                personality: 52,
+                mercantile: 21,
+                apparatus: Apparatus {
+                    mortar_and_pestle: Some(ApparatusQuality::Master),
+                    alembic: Some(ApparatusQuality::Journeyman),
+                    calcinator: None,
+                    retort: None,
+                },
+            }
+        );
+        // The changed inventory replaces the player's starting inventory
+        assert_eq!(
+            save_game.inventory[0],
+            InventoryItem {
+                id: "ingred_ash_yam_01".to_string(),
+                count: 5
+            }
+        );
+        assert!(!save_game
+            .inventory
+            .iter()
+            .any(|item| item.id == "ingred_bread_01"));
+    }
+
+    #[test]
+    fn test_count_ingredients_from_save() {
+        let save_game = SaveGame::parse(&save_file()).unwrap();
+
+        let ingredient_counts = save_game.ingredient_counts(&bundled_ingredients());
+
+        assert_eq!(
+            ingredient_counts.counts,
+            BTreeMap::from([("ash_yam".to_string(), 5), ("bunglers_bane".to_string(), 1)])
+        );
+        assert_eq!(
+            ingredient_counts.unrecognised,
+            vec!["ingred_moddedroot_01".to_string()]
+        );
+    }
+
+    /// A minimal OpenMW save: a header, the save menu summary and the player
+    fn openmw_save_file() -> Vec<u8> {
+        let stat = |value: f32| subrecord("STBA", &value.to_le_bytes());
+        let mut skills = vec![stat(5.0); 27];
+        skills[16] = stat(47.0);
+        skills[24] = stat(21.0);
+        let mut attributes = vec![stat(40.0); 8];
+        attributes[1] = stat(65.0);
+        attributes[6] = stat(52.0);
+        attributes[7] = stat(48.0);
+
+        let player = [
+            vec![
+                subrecord("NAME", &string("player")),
+                subrecord("DATA", &[0; 24]),
+                subrecord("ICNT", &5i32.to_le_bytes()),
+                subrecord("NAME", &string("ingred_ash_yam_01")),
+                subrecord("NAM9", &3i32.to_le_bytes()),
+                subrecord("NAME", &string("Ingred_Ash_Yam_01")),
+                subrecord("NAM9", &2i32.to_le_bytes()),
+                subrecord("NAME", &string("ingred_guar_hide_marsus")),
+                subrecord("NAME", &string("apparatus_m_mortar_01")),
+                subrecord("NAME", &string("gold_001")),
+                subrecord("COUN", &100i32.to_le_bytes()),
+                subrecord("EQUI", &[0; 8]),
+                subrecord("FACT", &string("mages guild")),
+            ],
+            skills,
+            attributes,
+            vec![stat(80.0); 3],
+        ]
+        .concat();
+
+        [
+            record("TES3", &[subrecord("FORM", &21i32.to_le_bytes())]),
+            record(
+                "SAVE",
+                &[
+                    subrecord("PLNM", &string("Nerevar")),
+                    subrecord("PLLE", &12i32.to_le_bytes()),
+                ],
+            ),
+            record("PLAY", &player),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_read_player_from_openmw_save() {
+        let save_game = SaveGame::parse(&openmw_save_file()).unwrap();
+
+        assert_eq!(save_game.player_name, "Nerevar");
+        assert_eq!(
+            save_game.character,
+            Character {
+                level: 12,
+                alchemy: 47,
+                intelligence: 65,
+                luck: 48,
+                personality: 52,
+                mercantile: 21,
+                apparatus: Apparatus {
+                    mortar_and_pestle: Some(ApparatusQuality::Master),
+                    ..Default::default()
+                },
+            }
+        );
+        assert_eq!(
+            save_game.inventory,
+            vec![
+                InventoryItem {
+                    id: "ingred_ash_yam_01".to_string(),
+                    count: 5
+                },
+                InventoryItem {
+                    id: "ingred_guar_hide_marsus".to_string(),
+                    count: 1
+                },
+                InventoryItem {
+                    id: "apparatus_m_mortar_01".to_string(),
+                    count: 1
+                },
+                InventoryItem {
+                    id: "gold_001".to_string(),
+                    count: 100
+                },
+            ]
+        );
+        assert_eq!(
+            save_game.ingredient_counts(&bundled_ingredients()).counts,
+            BTreeMap::from([
+                ("ash_yam".to_string(), 5),
+                ("marsus_guar_hide".to_string(), 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ingredient_ids_from_editor_ids() {
+        assert_eq!(
+            ingredient_id_from_editor_id("ingred_hackle-lo_leaf_01"),
+            Some("hackle_lo_leaf".to_string())
+        );
+        assert_eq!(
+            ingredient_id_from_editor_id("ingred_Daedras_Heart_01"),
+            Some("daedras_heart".to_string())
+        );
+        assert_eq!(
+            ingredient_id_from_editor_id("ingred_guar_hide_marsus"),
+            Some("marsus_guar_hide".to_string())
+        );
+        assert_eq!(
+            ingredient_id_from_editor_id("food_kwama_egg_02"),
+            Some("large_kwama_egg".to_string())
+        );
+        assert_eq!(ingredient_id_from_editor_id("apparatus_a_mortar_01"), None);
+
+        // Every editor ID names a bundled ingredient
+        let ingredient_ids: Vec<String> = bundled_ingredients()
+            .iter()
+            .map(|ingredient| ingredient.borrow().id())
+            .collect();
+        let editor_ids: BTreeMap<String, String> =
+            serde_yaml::from_str(include_str!("../res/Morrowind Editor IDs.yaml")).unwrap();
+        for (editor_id, name) in editor_ids {
+            assert!(
+                ingredient_ids.contains(&Ingredient::id_from_name(&name)),
+                "{} is {}, which isn't bundled",
+                editor_id,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_saves() {
+        assert!(SaveGame::parse(b"not a save").is_err());
+        assert!(SaveGame::parse(&save_file()[..100]).is_err());
+        assert_eq!(
+            SaveGame::parse(&record("TES3", &[])),
+            Err("No player found in the save file".to_string())
+        );
+
+        // A record claiming to be as long as a length can be
+        let mut huge_record = record("TES3", &[]);
+        huge_record[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SaveGame::parse(&huge_record).is_err());
+
+        let openmw_save = [record("TES3", &[]), record("SAVE", &[])].concat();
+        assert_eq!(
+            SaveGame::parse(&openmw_save),
+            Err("No player found in the save file".to_string())
+        );
+        let openmw_save = [
+            record("TES3", &[]),
+            record("SAVE", &[]),
+            record("PLAY", &[subrecord("NAME", &string("player"))]),
+        ]
+        .concat();
+        assert_eq!(
+            SaveGame::parse(&openmw_save),
+            Err("No player stats found in the save file".to_string())
+        );
+    }
+}
+
+mod game_data_tests {
+    use std::path::PathBuf;
+
+    use super::save_file_tests::{record, string, subrecord};
+    use super::*;
+
+    /// IRDT for an ingredient with these magic effect, skill and attribute indexes
+    fn irdt(weight: f32, value: i32, effects: [(i32, i32, i32); 4]) -> Vec<u8> {
+        let mut irdt = weight.to_le_bytes().to_vec();
+        irdt.extend(value.to_le_bytes());
+        for index in 0..3 {
+            for effect in effects.iter() {
+                let value = [effect.0, effect.1, effect.2][index];
+                irdt.extend(value.to_le_bytes());
+            }
+        }
+        subrecord("IRDT", &irdt)
+    }
+
+    fn ingredient(id: &str, name: &str, effects: [(i32, i32, i32); 4]) -> Vec<u8> {
+        record(
+            "INGR",
+            &[
+                subrecord("NAME", &string(id)),
+                subrecord("MODL", &string("n\\ingred.nif")),
+                subrecord("FNAM", &string(name)),
+                irdt(0.5, 10, effects),
+            ],
+        )
+    }
+
+    fn plugin(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut plugin = record("TES3", &[subrecord("HEDR", &[0; 300])]);
+        plugin.extend(records.concat());
+        plugin
+    }
+
+    /// An empty directory for this test's files
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("morrowind_alchemy_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    const NONE: (i32, i32, i32) = (-1, -1, -1);
+
+    #[test]
+    fn test_parse_openmw_cfg() {
+        let cfg = r#"# Comment
+data="/games/Morrowind/Data Files"
+data=?userdata?data
+data="mods/&"Quoted&" Mod"
+content=Morrowind.esm
+content=Tribunal.esm
+replace=content
+content=Morrowind.esm
+content=Better Ingredients.esp
+content=builtin.omwscripts
+data-local=local
+"#;
+
+        let game_files =
+            GameFiles::parse_openmw_cfg(cfg, Path::new("/home/nerevar/.config/openmw"));
+
+        assert_eq!(
+            game_files.data_directories,
+            vec![
+                PathBuf::from("/games/Morrowind/Data Files"),
+                PathBuf::from("/home/nerevar/.config/openmw/mods/\"Quoted\" Mod"),
+                PathBuf::from("/home/nerevar/.config/openmw/local"),
+            ]
+        );
+        assert_eq!(
+            game_files.content_files,
+            vec![
+                "Morrowind.esm",
+                "Better Ingredients.esp",
+                "builtin.omwscripts"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_morrowind_ini_puts_masters_first() {
+        let ini = "[General]\r\nGameFile0=Ignored.esm\r\n[Game Files]\r\nGameFile0=Morrowind.esm\r\nGameFile1=Better Ingredients.esp\r\nGameFile2=Tribunal.esm\r\n\r\n[Archives]\r\nArchive 0=Tribunal.bsa\r\n";
+
+        let game_files = GameFiles::parse_morrowind_ini(ini, Path::new("/games/Morrowind"));
+
+        assert_eq!(
+            game_files.data_directories,
+            vec![PathBuf::from("/games/Morrowind/Data Files")]
+        );
+        assert_eq!(
+            game_files.content_files,
+            vec!["Morrowind.esm", "Tribunal.esm", "Better Ingredients.esp"]
+        );
+    }
+
+    #[test]
+    fn test_read_plugin_ingredients() {
+        let data = plugin(&[
+            record("GMST", &[subrecord("NAME", &string("fPotionStrengthMult"))]),
+            ingredient(
+                "ingred_test_01",
+                "Test Root",
+                [
+                    (79, -1, 1),  // Fortify Intelligence
+                    (21, 11, -1), // Drain Alteration
+                    (44, -1, -1), // Charm, which the tool doesn't know
+                    (75, -1, -1), // Restore Health
+                ],
+            ),
+        ]);
+
+        let ingredients = read_plugin_ingredients(&data, "Test.esp").unwrap();
+
+        assert_eq!(ingredients.len(), 1);
+        assert_eq!(ingredients[0].id, "ingred_test_01");
+        let ingredient = ingredients[0].ingredient.as_ref().unwrap();
+        assert_eq!(ingredient.name, "Test Root");
+        assert_eq!(ingredient.value, 10);
+        assert_eq!(ingredient.weight, 0.5);
+        assert_eq!(
+            ingredient.effects,
+            [
+                Some(Effect::FortifyIntelligence),
+                Some(Effect::DrainAlteration),
+                None,
+                Some(Effect::RestoreHealth),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_later_plugins_replace_and_delete_ingredients() {
+        let directory = test_directory("load_order");
+        std::fs::write(
+            directory.join("Morrowind.esm"),
+            plugin(&[
+                ingredient(
+                    "ingred_ash_yam_01",
+                    "Ash Yam",
+                    [(81, -1, -1), NONE, NONE, NONE],
+                ),
+                ingredient("ingred_bread_01", "Bread", [(77, -1, -1), NONE, NONE, NONE]),
+            ]),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("better ingredients.esp"),
+            plugin(&[
+                ingredient(
+                    "Ingred_Ash_Yam_01",
+                    "Ash Yam",
+                    [(75, -1, -1), NONE, NONE, NONE],
+                ),
+                record(
+                    "INGR",
+                    &[
+                        subrecord("NAME", &string("ingred_bread_01")),
+                        subrecord("DELE", &[0; 4]),
+                    ],
+                ),
+            ]),
+        )
+        .unwrap();
+        let game_files = GameFiles {
+            data_directories: vec![directory.clone()],
+            content_files: vec![
+                "Morrowind.esm".to_string(),
+                // Found regardless of case
+                "Better Ingredients.esp".to_string(),
+                "builtin.omwscripts".to_string(),
+            ],
+        };
+
+        let ingredients = game_files.load_ingredients();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let ingredients = ingredients.unwrap();
+
+        assert_eq!(ingredients.len(), 1);
+        assert_eq!(ingredients[0].name, "Ash Yam");
+        assert_eq!(ingredients[0].effects[0], Some(Effect::RestoreHealth));
+    }
+
+    #[test]
+    fn test_missing_plugin_is_an_error() {
+        let game_files = GameFiles {
+            data_directories: vec![PathBuf::from("/nonexistent")],
+            content_files: vec!["Morrowind.esm".to_string()],
+        };
+
+        assert!(game_files
+            .load_ingredients()
+            .unwrap_err()
+            .contains("Morrowind.esm"));
+    }
+
+    #[test]
+    fn test_config_can_name_an_openmw_cfg() {
+        let directory = test_directory("openmw_cfg");
+        std::fs::create_dir(directory.join("data")).unwrap();
+        std::fs::write(
+            directory.join("data/Morrowind.esm"),
+            plugin(&[
+                ingredient("ingred_bread_01", "Bread", [(77, -1, -1), NONE, NONE, NONE]),
+                ingredient(
+                    "ingred_ash_yam_01",
+                    "Ash Yam",
+                    [(81, -1, -1), NONE, NONE, NONE],
+                ),
+            ]),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("openmw.cfg"),
+            "data=data\ncontent=Morrowind.esm\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("config.yaml"),
+            format!("openmw_cfg: {}\n", directory.join("openmw.cfg").display()),
+        )
+        .unwrap();
+
+        let ingredients = load_ingredients(&directory.join("config.yaml"));
+        std::fs::remove_dir_all(&directory).unwrap();
+        let names: Vec<String> = ingredients
+            .unwrap()
+            .iter()
+            .map(|ingredient| ingredient.borrow().name.clone())
+            .collect();
+
+        assert_eq!(names, vec!["Ash Yam", "Bread"]);
+    }
+
+    #[test]
+    fn test_game_ingredients_keep_listed_details() {
+        let directory = test_directory("listed_details");
+        std::fs::create_dir(directory.join("data")).unwrap();
+        std::fs::write(
+            directory.join("data/Morrowind.esm"),
+            plugin(&[
+                ingredient(
+                    "ingred_ash_yam_01",
+                    "Ash Yam",
+                    [(81, -1, -1), NONE, NONE, NONE],
+                ),
+                ingredient(
+                    "ingred_mod_root_01",
+                    "Mod Root",
+                    [(77, -1, -1), NONE, NONE, NONE],
+                ),
+            ]),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("openmw.cfg"),
+            "data=data\ncontent=Morrowind.esm\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("config.yaml"),
+            format!(
+                "openmw_cfg: {}\ningredient_lists:\n  - res/Morrowind Base Game Ingredients.yaml\n",
+                directory.join("openmw.cfg").display()
+            ),
+        )
+        .unwrap();
+
+        let ingredients = load_ingredients(&directory.join("config.yaml"));
+        std::fs::remove_dir_all(&directory).unwrap();
+        let ingredients = ingredients.unwrap();
+        let listed_ash_yam = bundled(&["Ash Yam"]);
+        let listed_ash_yam = listed_ash_yam[0].borrow();
+
+        let ash_yam = ingredients[0].borrow();
+        assert_eq!(ash_yam.name, "Ash Yam");
+        assert_eq!(ash_yam.description, listed_ash_yam.description);
+        assert_eq!(ash_yam.harvest_chance, listed_ash_yam.harvest_chance);
+        assert!(!ash_yam.harvest_chance.is_empty());
+        // An ingredient only the plugin has keeps what the plugin says
+        let mod_root = ingredients[1].borrow();
+        assert_eq!(mod_root.description, "From Morrowind.esm");
+        assert!(mod_root.harvest_chance.is_empty());
+    }
+}
+
+mod ruleset_tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn character(alchemy: u16, intelligence: u16, luck: u16) -> Character {
+        Character {
+            alchemy,
+            intelligence,
+            luck,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::Journeyman),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bundled_ingredients_only_use_the_games_effects() {
+        for ruleset in Ruleset::iter() {
+            let effects = ruleset.effects();
+            let ingredients = ruleset.bundled_ingredients();
+
+            assert!(!ingredients.is_empty(), "{} has no ingredients", ruleset);
+            for ingredient in ingredients.iter() {
+                let ingredient = ingredient.borrow();
+                for effect in ingredient.effects.iter().flatten() {
+                    assert!(
+                        effects.contains(effect),
+                        "{} has {} which isn't a {} effect",
+                        ingredient.name,
+                        effect,
+                        ruleset
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_skyrim_potions_have_at_most_three_ingredients() {
+        let ingredients = Ruleset::Skyrim.bundled_ingredients();
+        let query = PotionQuery {
+            desired_effects: [Some(Effect::RestoreHealth), None, None, None],
+            allow_extra_effects: true,
+            max_ingredients: Ruleset::Skyrim.max_ingredients(),
+            ..Default::default()
+        };
+
+        let potions = query.find_potions(&get_potential_ingredients(
+            &query.desired_effects,
+            &ingredients,
+        ));
+
+        assert!(!potions.is_empty());
+        assert!(potions
+            .iter()
+            .all(|potion| potion.ingredients.iter().flatten().count() <= 3));
+    }
+
+    #[test]
+    fn test_visible_effects() {
+        assert_eq!(Ruleset::Morrowind.visible_effects(14), 0);
+        assert_eq!(Ruleset::Morrowind.visible_effects(30), 2);
+        assert_eq!(Ruleset::Morrowind.visible_effects(100), 4);
+        assert_eq!(Ruleset::Oblivion.visible_effects(0), 1);
+        assert_eq!(Ruleset::Oblivion.visible_effects(50), 3);
+        assert_eq!(Ruleset::Oblivion.visible_effects(100), 4);
+        assert_eq!(Ruleset::Skyrim.visible_effects(15), 4);
+    }
+
+    #[test]
+    fn test_known_effects() {
+        let ingredients = vec![
+            ingredient(
+                "Bread",
+                &[
+                    Effect::RestoreFatigue,
+                    Effect::RestoreHealth,
+                    Effect::Poison,
+                ],
+            )
+            .shared(),
+            ingredient(
+                "Pearl",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::Poison,
+                    Effect::RestoreFatigue,
+                ],
+            )
+            .shared(),
+        ];
+        let potion = potion_from(&ingredients);
+
+        // Alchemy 15 shows only the first effect of each ingredient
+        assert_eq!(
+            Ruleset::Morrowind.known_effects(15, &ingredients),
+            [Effect::RestoreFatigue, Effect::RestoreHealth]
+        );
+        assert_eq!(
+            Ruleset::Morrowind.known_potion_effects(15, &potion),
+            [Effect::RestoreHealth, Effect::RestoreFatigue]
+        );
+        assert!(Ruleset::Morrowind
+            .known_effects(14, &ingredients)
+            .is_empty());
+        assert_eq!(
+            Ruleset::Morrowind.known_potion_effects(30, &potion),
+            potion.effects
+        );
+    }
+
+    #[test]
+    fn test_harmful_effects_of_every_game() {
+        assert!(Effect::Frenzy.is_harmful());
+        assert!(Effect::Slow.is_harmful());
+        assert!(Effect::RavageHealth.is_harmful());
+        assert!(Effect::Burden.is_harmful());
+        assert!(!Effect::RegenerateHealth.is_harmful());
+        assert!(!Effect::FortifyBarter.is_harmful());
+        assert!(!Effect::ReflectSpell.is_harmful());
+        assert!(!Effect::ShockShield.is_harmful());
+    }
+
+    #[test]
+    fn test_potion_strength() {
+        assert_eq!(
+            Ruleset::Morrowind.potion_strength(&character(50, 60, 40)),
+            30.0
+        );
+        // 50 + 0.4 × (60 - 50), plus 25 × 0.5 for a Journeyman mortar and pestle
+        assert_eq!(
+            Ruleset::Oblivion.potion_strength(&character(50, 60, 60)),
+            66.5
+        );
+        // Oblivion has nothing above Master, which has a quality of 1
+        let secret_master_mortar_and_pestle = Character {
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::SecretMaster),
+                ..Default::default()
+            },
+            ..character(50, 60, 60)
+        };
+        assert_eq!(
+            Ruleset::Oblivion.potion_strength(&secret_master_mortar_and_pestle),
+            79.0
+        );
+        // 4 × (1 + 0.5 × 100 / 100)
+        assert_eq!(
+            Ruleset::Skyrim.potion_strength(&character(100, 60, 40)),
+            6.0
+        );
+        assert_eq!(Ruleset::Skyrim.potion_strength(&character(15, 60, 40)), 4.3);
+
+        let no_mortar_and_pestle = Character {
+            apparatus: Apparatus::default(),
+            ..character(50, 60, 60)
+        };
+        assert_eq!(
+            Ruleset::Oblivion.potion_strength(&no_mortar_and_pestle),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_ruleset_in_deep_link() {
+        let deep_link = DeepLink {
+            ruleset: Ruleset::Skyrim,
+            desired_effects: [Some(Effect::FortifySmithing), None, None, None],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            deep_link.to_fragment(),
+            "game=skyrim&effects=FortifySmithing"
+        );
+        assert_eq!(
+            DeepLink::from_fragment("game=Skyrim&effects=FortifySmithing"),
+            deep_link
+        );
+        assert_eq!(
+            DeepLink::from_fragment("game=daggerfall").ruleset,
+            Ruleset::Morrowind
+        );
+    }
+
+    #[test]
+    fn test_cli_game_option() {
+        let run_cli = super::cli_tests::run_cli;
+
+        let effects = run_cli(&["effects", "--game", "skyrim"]).unwrap();
+        assert!(effects.lines().any(|line| line == "Fortify Smithing"));
+        assert!(!effects.lines().any(|line| line == "Levitate"));
+
+        let json = run_cli(&[
+            "find",
+            "--game",
+            "oblivion",
+            "--effect",
+            "Restore Fatigue",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(!records.as_array().unwrap().is_empty());
+
+        assert_eq!(
+            run_cli(&[
+                "find",
+                "--game",
+                "skyrim",
+                "--effect",
+                "Restore Health",
+                "--max-ingredients",
+                "4",
+            ]),
+            Err("Skyrim potions have at most 3 ingredients".to_string())
+        );
+        assert_eq!(
+            run_cli(&["effects", "--game", "daggerfall"]),
+            Err("Unknown game: daggerfall".to_string())
+        );
+    }
+
+    #[test]
+    fn test_api_uses_the_rulesets_ingredient_limit() {
+        let api =
+            crate::api::Api::with_ruleset(Ruleset::Skyrim, Ruleset::Skyrim.bundled_ingredients());
+
+        let response = api.handle(
+            "POST",
+            "/potions",
+            r#"{"effects": ["Restore Health"], "max_ingredients": 4}"#,
+        );
+
+        assert_eq!(response.status, 400);
+        assert!(response
+            .body
+            .contains("max_ingredients must be from 2 to 3, not 4"));
+    }
+}
+
+mod engine_tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    /// Alchemy factor 69, so potion strength 34.5 with a Journeyman's mortar and pestle
+    fn character() -> Character {
+        Character {
+            alchemy: 59,
+            intelligence: 60,
+            luck: 40,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::Journeyman),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn potion(effects: [Option<Effect>; 4]) -> Potion {
+        Potion::new_potion_from_optional_ingredients(&[
+            Some(Ingredient::new_default_ingredient_with_effects(effects)),
+            Some(Ingredient::new_default_ingredient_with_effects(effects)),
+            None,
+            None,
+        ])
+    }
+
+    /// A character with these stats and apparatus of one quality, the mortar and pestle included
+    fn brewer(
+        alchemy: u16,
+        intelligence: u16,
+        luck: u16,
+        quality: ApparatusQuality,
+        alembic: bool,
+        retort: bool,
+    ) -> Character {
+        Character {
+            alchemy,
+            intelligence,
+            luck,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(quality),
+                alembic: alembic.then_some(quality),
+                calcinator: None,
+                retort: retort.then_some(quality),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Magnitude, duration and value of a potion of one effect
+    fn brew(engine: Engine, character: &Character, effect: Effect) -> (u32, u32, u32) {
+        let brewed_potion = engine.brew(&potion([Some(effect), None, None, None]), character);
+        let effect_strength = brewed_potion.effects[0];
+        (
+            effect_strength.magnitude,
+            effect_strength.duration,
+            brewed_potion.value,
+        )
+    }
+
+    // The expected numbers below are pinned, worked out by hand from the game settings
+    // (fPotionStrengthMult 0.5, fPotionT1MagMult 1.5, fPotionT1DurMult 0.5, iAlchemyMod 2) and
+    // the sources in Engine's documentation, rather than by the code under test
+
+    #[test]
+    fn test_openmw_pinned_values() {
+        let journeyman = brewer(47, 65, 48, ApparatusQuality::Journeyman, false, false);
+        let master = brewer(75, 80, 55, ApparatusQuality::Master, false, true);
+        let secret_master = brewer(100, 100, 100, ApparatusQuality::SecretMaster, true, false);
+
+        assert_eq!(
+            brew(Engine::OpenMw, &journeyman, Effect::RestoreHealth),
+            (4, 12, 58)
+        );
+        assert_eq!(
+            brew(Engine::OpenMw, &master, Effect::RestoreFatigue),
+            (37, 107, 106)
+        );
+        assert_eq!(
+            brew(Engine::OpenMw, &secret_master, Effect::Poison),
+            (3, 9, 240)
+        );
+    }
+
+    #[test]
+    fn test_original_pinned_values() {
+        let journeyman = brewer(47, 65, 48, ApparatusQuality::Journeyman, false, false);
+        let master = brewer(75, 80, 55, ApparatusQuality::Master, false, true);
+        let secret_master = brewer(100, 100, 100, ApparatusQuality::SecretMaster, true, false);
+
+        assert_eq!(
+            brew(Engine::Original, &journeyman, Effect::RestoreHealth),
+            (3, 11, 58)
+        );
+        assert_eq!(
+            brew(Engine::Original, &master, Effect::RestoreFatigue),
+            (36, 107, 106)
+        );
+        assert_eq!(
+            brew(Engine::Original, &secret_master, Effect::Poison),
+            (2, 8, 240)
+        );
+        assert_eq!(
+            Engine::Original
+                .brew(
+                    &potion([Some(Effect::RestoreHealth), None, None, None]),
+                    &journeyman
+                )
+                .effects[0]
+                .to_string(),
+            "Restore Health 3 pts for 11 secs"
+        );
+    }
+
+    #[test]
+    fn test_code_patch_pinned_values() {
+        let journeyman = brewer(47, 65, 48, ApparatusQuality::Journeyman, false, false);
+        let master = brewer(75, 80, 55, ApparatusQuality::Master, false, false);
+        let secret_master = brewer(100, 100, 100, ApparatusQuality::SecretMaster, false, false);
+
+        // Effects as in Morrowind.exe, values from their spell cost
+        assert_eq!(
+            brew(Engine::CodePatch, &journeyman, Effect::RestoreHealth),
+            (3, 11, 9)
+        );
+        assert_eq!(
+            brew(Engine::CodePatch, &master, Effect::RestoreFatigue),
+            (35, 106, 187)
+        );
+        assert_eq!(
+            brew(Engine::CodePatch, &secret_master, Effect::Poison),
+            (8, 26, 97)
+        );
+    }
+
+    #[test]
+    fn test_effects_without_magnitude_or_duration() {
+        let potion = potion([
+            Some(Effect::CurePoison),
+            Some(Effect::Invisibility),
+            Some(Effect::Dispel),
+            None,
+        ]);
+
+        let brewed_potion = Engine::OpenMw.brew(&potion, &character());
+
+        assert_eq!(
+            brewed_potion
+                .effects
+                .iter()
+                .map(|effect| effect.to_string())
+                .sorted()
+                .collect::<Vec<_>>(),
+            vec!["Cure Poison", "Dispel 5 pts", "Invisibility for 2 secs"]
+        );
+    }
+
+    #[test]
+    fn test_weak_effects_are_left_out() {
+        let novice = Character {
+            alchemy: 5,
+            intelligence: 30,
+            luck: 30,
+            ..character()
+        };
+        let potion = potion([Some(Effect::Levitate), Some(Effect::Feather), None, None]);
+
+        // Strength 5.5 gives Levitate 0.37 points, which isn't enough to float
+        let brewed_potion = Engine::OpenMw.brew(&potion, &novice);
+
+        assert_eq!(
+            brewed_potion
+                .effects
+                .iter()
+                .map(|effect| effect.effect)
+                .collect::<Vec<_>>(),
+            vec![Effect::Feather]
+        );
+    }
+
+    #[test]
+    fn test_success_chance() {
+        // The same for every engine
+        for engine in Engine::iter() {
+            assert_eq!(engine.success_chance(&character()), 0.7);
+            assert_eq!(
+                engine.success_chance(&brewer(
+                    47,
+                    65,
+                    48,
+                    ApparatusQuality::Journeyman,
+                    false,
+                    false
+                )),
+                0.59
+            );
+            assert_eq!(
+                engine.success_chance(&brewer(75, 80, 55, ApparatusQuality::Master, false, false)),
+                0.89
+            );
+        }
+        let master = Character {
+            alchemy: 100,
+            ..character()
+        };
+        assert_eq!(Engine::OpenMw.success_chance(&master), 1.0);
+
+        let no_mortar_and_pestle = Character {
+            apparatus: Apparatus::default(),
+            ..character()
+        };
+        let brewed_potion = Engine::OpenMw.brew(
+            &potion([Some(Effect::RestoreHealth), None, None, None]),
+            &no_mortar_and_pestle,
+        );
+        assert!(brewed_potion.effects.is_empty());
+        assert_eq!(brewed_potion.success_chance, 0.0);
+    }
+
+    #[test]
+    fn test_engine_names() {
+        assert_eq!("OpenMW".parse(), Ok(Engine::OpenMw));
+        assert_eq!("mcp".parse(), Ok(Engine::CodePatch));
+        assert_eq!("Morrowind.exe".parse(), Ok(Engine::Original));
+        assert!("daggerfall".parse::<Engine>().is_err());
+    }
+}
+
+mod apparatus_tests {
+    use super::*;
+
+    /// Potion strength 34.5, with only a Journeyman's mortar and pestle
+    fn character(alembic: bool, calcinator: bool, retort: bool) -> Character {
+        let quality = |owned: bool| owned.then_some(ApparatusQuality::Journeyman);
+        Character {
+            alchemy: 59,
+            intelligence: 60,
+            luck: 40,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::Journeyman),
+                alembic: quality(alembic),
+                calcinator: quality(calcinator),
+                retort: quality(retort),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Restore Health with Poison
+    fn strength(character: &Character, effect: Effect) -> Option<(u32, u32)> {
+        let potion = potion_from(&shared([
+            ingredient("First", &[Effect::RestoreHealth, Effect::Poison]),
+            ingredient("Second", &[Effect::RestoreHealth, Effect::Poison]),
+        ]));
+        Engine::OpenMw
+            .brew(&potion, character)
+            .effects
+            .iter()
+            .find(|effect_strength| effect_strength.effect == effect)
+            .map(|effect_strength| (effect_strength.magnitude, effect_strength.duration))
+    }
+
+    #[test]
+    fn test_harmful_effects() {
+        assert!(Effect::Poison.is_harmful());
+        assert!(Effect::Blind.is_harmful());
+        assert!(Effect::WeaknessToShock.is_harmful());
+        assert!(!Effect::RestoreHealth.is_harmful());
+        assert!(!Effect::WaterWalking.is_harmful());
+    }
+
+    #[test]
+    fn test_alembic_weakens_harmful_effects() {
+        // 34.5 / 1.5 / 9 = 2.56 points for 34.5 / 0.5 / 9 = 7.67 seconds
+        assert_eq!(
+            strength(&character(false, false, false), Effect::Poison),
+            Some((3, 8))
+        );
+        assert_eq!(
+            strength(&character(true, false, false), Effect::Poison),
+            Some((1, 4))
+        );
+        assert_eq!(
+            strength(&character(true, true, false), Effect::Poison),
+            Some((1, 2))
+        );
+        // Without an alembic the calcinator strengthens harmful effects too
+        assert_eq!(
+            strength(&character(false, true, false), Effect::Poison),
+            Some((4, 9))
+        );
+        // The retort only helps beneficial effects
+        assert_eq!(
+            strength(&character(false, false, true), Effect::Poison),
+            Some((3, 8))
+        );
+    }
+
+    #[test]
+    fn test_retort_strengthens_beneficial_effects() {
+        assert_eq!(
+            strength(&character(false, false, true), Effect::RestoreHealth),
+            Some((6, 15))
+        );
+        assert_eq!(
+            strength(&character(false, true, true), Effect::RestoreHealth),
+            Some((8, 17))
+        );
+        // The alembic only helps against harmful effects
+        assert_eq!(
+            strength(&character(true, false, false), Effect::RestoreHealth),
+            Some((5, 14))
+        );
+    }
+
+    #[test]
+    fn test_weak_side_effects_can_be_allowed() {
+        let query = |side_effect_threshold| PotionQuery {
+            desired_effects: [Some(Effect::RestoreHealth), None, None, None],
+            side_effect_threshold,
+            ..Default::default()
+        };
+        let threshold = |character| {
+            Some(SideEffectThreshold {
+                engine: Engine::OpenMw,
+                character,
+                min_magnitude: 2,
+            })
+        };
+
+        let ingredients = shared([
+            ingredient("First", &[Effect::RestoreHealth, Effect::Poison]),
+            ingredient("Second", &[Effect::RestoreHealth, Effect::Poison]),
+        ]);
+
+        assert!(query(None).find_potions(&ingredients).is_empty());
+        // Poison weakened to 1 point is let through
+        assert_eq!(
+            query(threshold(character(true, true, false)))
+                .find_potions(&ingredients)
+                .len(),
+            1
+        );
+        // At 3 points it still matters
+        assert!(query(threshold(character(false, false, false)))
+            .find_potions(&ingredients)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_harmful_effects_without_magnitude_always_matter() {
+        let brewed_potion = BrewedPotion {
+            effects: vec![EffectStrength {
+                effect: Effect::Paralyze,
+                magnitude: 1,
+                duration: 2,
+            }],
+            value: 10,
+            success_chance: 1.0,
+        };
+
+        assert!(!brewed_potion.is_negligible(Effect::Paralyze, 5));
+        assert!(brewed_potion.is_negligible(Effect::Poison, 5));
+        assert!(!brewed_potion.is_negligible(Effect::RestoreHealth, 5));
+    }
+}
+
+mod leveling_tests {
+    use super::*;
+
+    /// A cheap pair sharing Restore Fatigue and a dear one sharing Drain Agility
+    const INGREDIENTS: [&str; 4] = ["Bread", "Saltrice", "Pearl", "Diamond"];
+
+    /// Alchemy factor 50, so half of all brews fail
+    fn query(gold: u32) -> LevelingQuery {
+        LevelingQuery {
+            engine: Engine::OpenMw,
+            character: Character {
+                alchemy: 40,
+                intelligence: 50,
+                luck: 50,
+                apparatus: Apparatus {
+                    mortar_and_pestle: Some(ApparatusQuality::Journeyman),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            target_alchemy: 42,
+            gold,
+            barter: Barter::default(),
+            skill_type: SkillType::Major,
+            specialized: false,
+        }
+    }
+
+    #[test]
+    fn test_successful_brews_for_level() {
+        let query = |skill_type, specialized| LevelingQuery {
+            skill_type,
+            specialized,
+            ..query(0)
+        };
+
+        // 6 × 1.25 / 2 = 3.75
+        assert_eq!(
+            query(SkillType::Miscellaneous, false).successful_brews_for_level(5),
+            4
+        );
+        // 10 × 0.75 × 0.8 / 2 = 3 exactly
+        assert_eq!(
+            query(SkillType::Major, true).successful_brews_for_level(9),
+            3
+        );
+        assert_eq!(
+            query(SkillType::Minor, false).successful_brews_for_level(99),
+            50
+        );
+    }
+
+    #[test]
+    fn test_plan_uses_the_cheapest_recipe() {
+        let plan = query(1000).plan(&bundled(&INGREDIENTS)).unwrap();
+
+        assert_eq!(
+            plan.recipes
+                .iter()
+                .map(|recipe| recipe.recipe_key())
+                .collect::<Vec<_>>(),
+            vec!["bread+saltrice", "diamond+pearl"]
+        );
+        assert_eq!(plan.cost_per_brew, 2);
+        // 16 successes at 51% then 16 at 52%
+        assert_eq!(plan.brews, 32 + 31);
+        assert_eq!(plan.failures, 16 + 15);
+        assert_eq!(plan.total_cost, 126);
+        assert_eq!(plan.reached_alchemy, 42);
+    }
+
+    #[test]
+    fn test_plan_stops_when_the_gold_runs_out() {
+        let plan = query(100).plan(&bundled(&INGREDIENTS)).unwrap();
+
+        assert_eq!(plan.brews, 32);
+        assert_eq!(plan.total_cost, 64);
+        assert_eq!(plan.reached_alchemy, 41);
+    }
+
+    #[test]
+    fn test_no_plan_without_a_mortar_and_pestle() {
+        let mut query = query(1000);
+        query.character.apparatus = Apparatus::default();
+
+        assert!(query.plan(&bundled(&INGREDIENTS)).is_none());
+    }
+}
+
+mod barter_tests {
+    use super::*;
+
+    fn character(mercantile: u16, personality: u16, luck: u16) -> Character {
+        Character {
+            mercantile,
+            personality,
+            luck,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prices_for_a_new_character() {
+        let barter = Barter::default();
+        let character = Character::default();
+
+        // The character's 17 points against the merchant's 42, both at full fatigue
+        // Buying: 100 - 0.5 × (21.25 - 52.5) = 115.625%
+        assert_eq!(barter.buy_price(&character, 100), 115);
+        // Selling: 50 - 0.5 × (52.5 - 21.25) = 34.375%
+        assert_eq!(barter.sell_price(&character, 100), 34);
+    }
+
+    #[test]
+    fn test_better_stats_and_disposition_improve_prices() {
+        let barter = Barter::default();
+        let novice = character(5, 40, 40);
+        let haggler = character(100, 100, 100);
+        let friend = Barter {
+            disposition: 100,
+            ..barter
+        };
+
+        assert!(barter.buy_price(&haggler, 100) < barter.buy_price(&novice, 100));
+        assert!(barter.sell_price(&haggler, 100) > barter.sell_price(&novice, 100));
+        assert!(friend.buy_price(&novice, 100) < barter.buy_price(&novice, 100));
+        assert!(friend.sell_price(&novice, 100) > barter.sell_price(&novice, 100));
+        // Stats beyond what the formula counts make no difference
+        assert_eq!(
+            barter.buy_price(&character(1000, 1000, 1000), 100),
+            barter.buy_price(&haggler, 100)
+        );
+    }
+
+    #[test]
+    fn test_prices_never_drop_below_one_gold() {
+        let barter = Barter {
+            disposition: 100,
+            merchant_mercantile: 0,
+            merchant_personality: 0,
+            merchant_luck: 0,
+        };
+        let haggler = character(100, 100, 100);
+
+        assert_eq!(barter.buy_price(&haggler, 1000), 1);
+        assert_eq!(barter.buy_price(&Character::default(), 0), 0);
+        assert_eq!(barter.sell_price(&Character::default(), 1), 1);
+    }
+
+    #[test]
+    fn test_recipe_cost_prices_each_ingredient() {
+        let potion = potion_from(&[
+            ingredient("Bread", &[]).value(1).shared(),
+            ingredient("Pearl", &[]).value(100).shared(),
+        ]);
+
+        assert_eq!(
+            Barter::default().recipe_cost(&Character::default(), &potion),
+            1 + 115
+        );
+    }
+}
+
+mod merchant_tests {
+    use super::*;
+
+    fn merchant(ingredients: &[&str], barter_gold: u32) -> Merchant {
+        Merchant {
+            name: "Ajira".to_string(),
+            location: "Balmora, Mages Guild".to_string(),
+            ingredients: ingredients.iter().map(|name| name.to_string()).collect(),
+            barter_gold,
+        }
+    }
+
+    #[test]
+    fn test_merchant_sells_ingredients_by_name_or_id() {
+        let ajira = merchant(&["ASH YAM", "bread"], 500);
+
+        assert!(ajira.sells(&ingredient("Ash Yam", &[]).build()));
+        assert!(ajira.sells(&ingredient("Bread", &[]).build()));
+        assert!(!ajira.sells(&ingredient("Bittergreen Petals", &[]).build()));
+        assert_eq!(ajira.to_string(), "Ajira (Balmora, Mages Guild)");
+    }
+
+    #[test]
+    fn test_merchants_selling() {
+        let merchants = vec![
+            merchant(&["ash_yam"], 500),
+            merchant(&[], 1000),
+            merchant(&["Ash Yam"], 100),
+        ];
+
+        let selling = merchants_selling(&merchants, &ingredient("Ash Yam", &[]).build());
+        assert_eq!(
+            selling
+                .iter()
+                .map(|merchant| merchant.barter_gold)
+                .collect::<Vec<_>>(),
+            vec![500, 100]
+        );
+    }
+
+    #[test]
+    fn test_shortfall_when_barter_gold_is_too_low() {
+        let ajira = merchant(&[], 500);
+
+        assert_eq!(ajira.shortfall(499), None);
+        assert_eq!(ajira.shortfall(500), None);
+        assert_eq!(ajira.shortfall(750), Some(250));
+    }
+
+    #[test]
+    fn test_load_merchants_from_config() {
+        let directory = std::env::temp_dir();
+        let merchant_list_path = directory.join(format!(
+            "morrowind_alchemy_merchants_{}.yaml",
+            std::process::id()
+        ));
+        let config_path = directory.join(format!(
+            "morrowind_alchemy_merchant_config_{}.yaml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &merchant_list_path,
+            "- name: Creeper\n  location: Caldera, Ghorak Manor\n  barter_gold: 5000\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &config_path,
+            format!("merchant_lists:\n  - {}\n", merchant_list_path.display()),
+        )
+        .unwrap();
+
+        let merchants = load_merchants(&config_path);
+        std::fs::remove_file(&merchant_list_path).unwrap();
+        std::fs::remove_file(&config_path).unwrap();
+
+        assert_eq!(
+            merchants.unwrap(),
+            vec![Merchant {
+                name: "Creeper".to_string(),
+                location: "Caldera, Ghorak Manor".to_string(),
+                ingredients: Vec::new(),
+                barter_gold: 5000,
+            }]
+        );
+        // The merchant lists are optional
+        assert_eq!(load_merchants(Path::new("config.yaml")), Ok(Vec::new()));
+    }
+}
+
+mod shopping_list_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn shopping_list(inventory: &[(&str, u32)]) -> ShoppingList {
+        let ash_yam = ingredient("Ash Yam", &[])
+            .value(1)
+            .weight(0.5)
+            .found(&format!("Found near {}", "Ash Yam"), "80")
+            .shared();
+        let bread = ingredient("Bread", &[])
+            .value(1)
+            .weight(0.2)
+            .found(&format!("Found near {}", "Bread"), "80")
+            .shared();
+        let pearl = ingredient("Pearl", &[])
+            .value(100)
+            .weight(0.2)
+            .found(&format!("Found near {}", "Pearl"), "80")
+            .shared();
+        let recipes = vec![
+            (Potion::new_potion_from_ingredients(&[&ash_yam, &bread]), 3),
+            (Potion::new_potion_from_ingredients(&[&bread, &pearl]), 2),
+        ];
+        let merchants = vec![Merchant {
+            name: "Ajira".to_string(),
+            location: "Balmora, Mages Guild".to_string(),
+            ingredients: vec!["Bread".to_string()],
+            barter_gold: 500,
+        }];
+        let inventory: BTreeMap<String, u32> = inventory
+            .iter()
+            .map(|(id, count)| (id.to_string(), *count))
+            .collect();
+
+        ShoppingList::new(
+            &recipes,
+            &inventory,
+            &Barter::default(),
+            &Character::default(),
+            &merchants,
+        )
+    }
+
+    #[test]
+    fn test_shopping_list_sums_recipes_less_inventory() {
+        let shopping_list = shopping_list(&[("bread", 1), ("pearl", 5)]);
+
+        assert_eq!(
+            shopping_list
+                .items
+                .iter()
+                .map(|item| (item.name.as_str(), item.needed, item.owned, item.to_buy))
+                .collect::<Vec<_>>(),
+            vec![("Ash Yam", 3, 0, 3), ("Bread", 5, 1, 4), ("Pearl", 2, 2, 0)]
+        );
+        // Ash Yam and Bread cost 1 gold each
+        assert_eq!(shopping_list.total_cost, 3 + 4);
+        assert!((shopping_list.total_weight - (1.5 + 0.8)).abs() < 0.001);
+        assert_eq!(
+            shopping_list.items[1].merchants,
+            vec!["Ajira (Balmora, Mages Guild)"]
+        );
+        assert!(shopping_list.items[0].merchants.is_empty());
+    }
+
+    #[test]
+    fn test_shopping_list_costs_at_merchant_prices() {
+        let shopping_list = shopping_list(&[]);
+
+        // Each Pearl costs 115 gold rather than its base value of 100
+        assert_eq!(shopping_list.items[2].cost, 2 * 115);
+        assert_eq!(shopping_list.total_cost, 3 + 5 + 230);
+    }
+
+    #[test]
+    fn test_shopping_list_as_text() {
+        let text = shopping_list(&[("pearl", 2)]).to_string();
+
+        assert_eq!(
+            text,
+            "Shopping List\n\
+             [ ] 3 × Ash Yam (have 0 of 3): 3 gold, 1.5 weight\n    \
+             Found: Found near Ash Yam (80% harvest chance)\n\
+             [ ] 5 × Bread (have 0 of 5): 5 gold, 1.0 weight\n    \
+             Buy from: Ajira (Balmora, Mages Guild)\n    \
+             Found: Found near Bread (80% harvest chance)\n\
+             [x] Pearl (have all 2)\n\
+             Total: 8 gold, 2.5 weight"
+        );
+    }
+}
+
+mod kit_tests {
+    use super::*;
+
+    fn target(effect: Effect) -> [Option<Effect>; 4] {
+        [Some(effect), None, None, None]
+    }
+
+    fn query(objective: KitObjective) -> KitQuery {
+        KitQuery {
+            targets: vec![
+                target(Effect::RestoreHealth),
+                target(Effect::RestoreMagicka),
+            ],
+            objective,
+            max_ingredients: 4,
+            allow_extra_effects: true,
+        }
+    }
+
+    fn names(kit: &Kit) -> Vec<String> {
+        kit.ingredients
+            .iter()
+            .map(|ingredient| ingredient.borrow().name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_fewest_ingredients() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreHealth, Effect::Burden]).value(1),
+            ingredient("Berry", &[Effect::RestoreHealth]).value(2),
+            ingredient("Cress", &[Effect::RestoreMagicka]).value(1),
+            ingredient("Daisy", &[Effect::RestoreMagicka]).value(1),
+            // A dear ingredient that pairs with either of the others
+            ingredient(
+                "Emerald",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::RestoreMagicka,
+                    Effect::Burden,
+                ],
+            )
+            .value(100),
+        ]);
+        let kit = query(KitObjective::FewestIngredients)
+            .solve(&ingredients)
+            .unwrap();
+
+        assert!(kit.exhaustive);
+        // Apple rather than Berry, as it's cheaper
+        assert_eq!(names(&kit), vec!["Apple", "Cress", "Emerald"]);
+        assert_eq!(
+            kit.recipes
+                .iter()
+                .map(|recipe| recipe.recipe_key())
+                .collect::<Vec<_>>(),
+            vec!["apple+emerald", "cress+emerald"]
+        );
+    }
+
+    #[test]
+    fn test_cheapest_ingredients() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreHealth, Effect::Burden]).value(1),
+            ingredient("Berry", &[Effect::RestoreHealth]).value(2),
+            ingredient("Cress", &[Effect::RestoreMagicka]).value(1),
+            ingredient("Daisy", &[Effect::RestoreMagicka]).value(1),
+            // A dear ingredient that pairs with either of the others
+            ingredient(
+                "Emerald",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::RestoreMagicka,
+                    Effect::Burden,
+                ],
+            )
+            .value(100),
+        ]);
+        let kit = query(KitObjective::Cheapest).solve(&ingredients).unwrap();
+
+        assert_eq!(names(&kit), vec!["Apple", "Berry", "Cress", "Daisy"]);
+        assert!(kit.recipes[0].effects.contains(&Effect::RestoreHealth));
+        assert!(kit.recipes[1].effects.contains(&Effect::RestoreMagicka));
+    }
+
+    #[test]
+    fn test_extra_effects_rule_out_recipes() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreHealth, Effect::Burden]).value(1),
+            ingredient("Berry", &[Effect::RestoreHealth]).value(2),
+            ingredient("Cress", &[Effect::RestoreMagicka]).value(1),
+            ingredient("Daisy", &[Effect::RestoreMagicka]).value(1),
+            // A dear ingredient that pairs with either of the others
+            ingredient(
+                "Emerald",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::RestoreMagicka,
+                    Effect::Burden,
+                ],
+            )
+            .value(100),
+        ]);
+        let kit = KitQuery {
+            allow_extra_effects: false,
+            ..query(KitObjective::FewestIngredients)
+        }
+        .solve(&ingredients)
+        .unwrap();
+
+        // Apple and Emerald also brew Burden
+        assert_eq!(names(&kit), vec!["Berry", "Cress", "Emerald"]);
+    }
+
+    #[test]
+    fn test_unbrewable_target() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreHealth]),
+            ingredient("Berry", &[Effect::RestoreHealth]),
+        ]);
+        let error = KitQuery {
+            targets: vec![target(Effect::WaterWalking)],
+            ..query(KitObjective::Cheapest)
+        }
+        .solve(&ingredients)
+        .unwrap_err();
+
+        assert_eq!(error, "No recipe makes Water Walking");
+    }
+
+    #[test]
+    fn test_kit_from_bundled_ingredients() {
+        let kit = KitQuery {
+            targets: vec![
+                target(Effect::RestoreHealth),
+                target(Effect::RestoreMagicka),
+                target(Effect::CureCommonDisease),
+                target(Effect::WaterWalking),
+            ],
+            ..query(KitObjective::FewestIngredients)
+        }
+        .solve(&bundled_ingredients())
+        .unwrap();
+
+        assert_eq!(kit.recipes.len(), 4);
+        for (recipe, effect) in kit.recipes.iter().zip([
+            Effect::RestoreHealth,
+            Effect::RestoreMagicka,
+            Effect::CureCommonDisease,
+            Effect::WaterWalking,
+        ]) {
+            assert!(recipe.effects.contains(&effect));
+        }
+        assert!(kit.exhaustive);
+        // Every recipe is brewed from the kit alone
+        for recipe in kit.recipes.iter() {
+            for ingredient in recipe.ingredients.iter().flatten() {
+                assert!(kit
+                    .ingredients
+                    .iter()
+                    .any(|kit_ingredient| Rc::ptr_eq(kit_ingredient, ingredient)));
+            }
+        }
+    }
+}
+
+mod batch_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn query(stock: &[(&str, u32)]) -> BatchQuery {
+        BatchQuery {
+            engine: Engine::OpenMw,
+            character: Character {
+                alchemy: 100,
+                intelligence: 100,
+                luck: 100,
+                apparatus: Apparatus {
+                    mortar_and_pestle: Some(ApparatusQuality::Master),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            barter: Barter::default(),
+            stock: stock
+                .iter()
+                .map(|(id, count)| (id.to_string(), *count))
+                .collect(),
+            objective: BatchObjective::SaleValue,
+            max_weight: None,
+            max_ingredients: 4,
+        }
+    }
+
+    fn brews(plan: &BatchPlan) -> Vec<(String, u32)> {
+        plan.brews
+            .iter()
+            .map(|brew| (brew.recipe.recipe_key(), brew.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_brews_as_many_potions_as_the_stock_allows() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Berry", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Cress", &[Effect::RestoreFatigue, Effect::RestoreHealth]).weight(5.0),
+        ]);
+        let plan = query(&[("apple", 3), ("berry", 3), ("cress", 2)]).plan(&ingredients);
+
+        // Every potion is worth the same, so pairing the most plentiful ingredients brews the most
+        assert_eq!(plan.potions, 4);
+        assert!(plan.leftover.is_empty());
+        assert_eq!(
+            plan.total_value,
+            plan.brews
+                .iter()
+                .map(|brew| brew.price * brew.count)
+                .sum::<u32>()
+        );
+        assert!(plan.total_value > 0);
+        assert!((plan.weight - 16.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_batch_reports_leftovers() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Berry", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Daisy", &[Effect::RestoreHealth]).weight(1.0),
+        ]);
+        let plan = query(&[("apple", 5), ("berry", 1), ("daisy", 2)]).plan(&ingredients);
+
+        assert_eq!(brews(&plan), vec![("apple+berry".to_string(), 1)]);
+        assert_eq!(
+            plan.leftover,
+            BTreeMap::from([("apple".to_string(), 4), ("daisy".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_batch_respects_the_weight_limit() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Berry", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Cress", &[Effect::RestoreFatigue, Effect::RestoreHealth]).weight(5.0),
+        ]);
+        let plan = BatchQuery {
+            max_weight: Some(4.5),
+            ..query(&[("apple", 3), ("berry", 3), ("cress", 2)])
+        }
+        .plan(&ingredients);
+
+        // Only the light Apple and Berry, twice
+        assert_eq!(brews(&plan), vec![("apple+berry".to_string(), 2)]);
+        assert!(plan.weight <= 4.5);
+    }
+
+    #[test]
+    fn test_batch_maximises_an_effect() {
+        let ingredients = shared([
+            ingredient("Apple", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Berry", &[Effect::RestoreFatigue]).weight(1.0),
+            ingredient("Cress", &[Effect::RestoreFatigue, Effect::RestoreHealth]).weight(5.0),
+            ingredient("Daisy", &[Effect::RestoreHealth]).weight(1.0),
+        ]);
+        let plan = BatchQuery {
+            objective: BatchObjective::EffectCount(Effect::RestoreHealth),
+            ..query(&[("apple", 3), ("berry", 3), ("cress", 2), ("daisy", 2)])
+        }
+        .plan(&ingredients);
+
+        assert_eq!(brews(&plan), vec![("cress+daisy".to_string(), 2)]);
+        assert_eq!(plan.leftover.get("apple"), Some(&3));
+    }
+}
+
+mod substitution_tests {
+    use super::*;
+
+    #[test]
+    fn test_substitutes_ranked_by_kept_and_new_effects() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            ),
+            ingredient(
+                "Vampire Dust",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            ),
+            ingredient(
+                "Chokeweed",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            ),
+            ingredient(
+                "Daedra Skin",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            ),
+            ingredient("Emerald", &[Effect::RestoreHealth]),
+            ingredient("Frost Salts", &[Effect::Poison]),
+        ]);
+        let recipe = Potion::new_potion_from_ingredients(&[&ingredients[0], &ingredients[1]]);
+        assert_eq!(
+            recipe.effects,
+            vec![Effect::FortifyStrength, Effect::RestoreHealth]
+        );
+
+        let substitutions = suggest_substitutes(&recipe, &ingredients[1], &ingredients);
+
+        // Frost Salts shares nothing Vampire Dust brings, so it's no substitute
+        assert_eq!(
+            substitutions
+                .iter()
+                .map(|substitution| substitution.substitute.borrow().name.clone())
+                .collect::<Vec<_>>(),
+            vec!["Daedra Skin", "Chokeweed", "Emerald"]
+        );
+        assert_eq!(
+            substitutions[0].kept,
+            vec![Effect::FortifyStrength, Effect::RestoreHealth]
+        );
+        assert!(substitutions[0].lost.is_empty() && substitutions[0].new.is_empty());
+        assert_eq!(substitutions[1].new, vec![Effect::Poison]);
+        assert_eq!(substitutions[2].kept, vec![Effect::RestoreHealth]);
+        assert_eq!(substitutions[2].lost, vec![Effect::FortifyStrength]);
+        assert_eq!(substitutions[2].potion.recipe_key(), "ash_salts+emerald");
+    }
+
+    #[test]
+    fn test_no_substitutes_for_an_ingredient_adding_nothing() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            ),
+            ingredient(
+                "Vampire Dust",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            ),
+            ingredient(
+                "Chokeweed",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            ),
+            ingredient(
+                "Daedra Skin",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            ),
+            ingredient("Emerald", &[Effect::RestoreHealth]),
+            ingredient("Frost Salts", &[Effect::Poison]),
+        ]);
+        let recipe = Potion::new_potion_from_ingredients(&[
+            &ingredients[0],
+            &ingredients[1],
+            &ingredients[5],
+        ]);
+
+        // Frost Salts adds Poison along with Ash Salts, so it can be swapped for Chokeweed
+        let substitutions = suggest_substitutes(&recipe, &ingredients[5], &ingredients);
+        assert_eq!(
+            substitutions
+                .iter()
+                .map(|substitution| substitution.substitute.borrow().name.clone())
+                .collect::<Vec<_>>(),
+            vec!["Chokeweed"]
+        );
+
+        let recipe = Potion::new_potion_from_ingredients(&[
+            &ingredients[1],
+            &ingredients[3],
+            &ingredients[5],
+        ]);
+        assert!(suggest_substitutes(&recipe, &ingredients[5], &ingredients).is_empty());
+    }
+}
+
+mod completion_tests {
+    use super::*;
+
+    fn query(locked: &[Rc<RefCell<Ingredient>>], ranking: CompletionRanking) -> CompletionQuery {
+        CompletionQuery {
+            locked: locked.to_vec(),
+            desired_effects: [
+                Some(Effect::RestoreHealth),
+                Some(Effect::FortifyStrength),
+                None,
+                None,
+            ],
+            max_ingredients: 4,
+            ranking,
+            engine: Engine::CodePatch,
+            character: Character {
+                alchemy: 100,
+                ..Default::default()
+            },
+            barter: Barter::default(),
+        }
+    }
+
+    fn keys(potions: &[Potion]) -> Vec<String> {
+        potions.iter().map(|potion| potion.recipe_key()).collect()
+    }
+
+    #[test]
+    fn test_complete_by_cost() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(5),
+            ingredient(
+                "Bonemeal",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            )
+            .value(10),
+            ingredient("Comberry", &[Effect::RestoreHealth]).value(1),
+            ingredient("Daedra Skin", &[Effect::FortifyStrength]).value(1),
+            ingredient(
+                "Ectoplasm",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(2),
+        ]);
+        let potions = query(&ingredients[..1], CompletionRanking::Cost).complete(&ingredients);
+
+        // Every completion keeps Ash Salts and adds one or two ingredients
+        assert!(potions
+            .iter()
+            .all(|potion| potion.recipe_key().contains("ash_salts")
+                && (2..=3).contains(&potion.ingredients.iter().flatten().count())));
+        assert_eq!(
+            keys(&potions[..3]),
+            vec![
+                "ash_salts+ectoplasm",
+                "ash_salts+comberry+daedra_skin",
+                "ash_salts+comberry+ectoplasm"
+            ]
+        );
+
+        // Each ingredient's price is rounded down on its own, so from a merchant who charges almost
+        // double, Comberry and Daedra Skin cost less together than Ectoplasm alone
+        let greedy_merchant = Barter {
+            disposition: 0,
+            merchant_mercantile: 100,
+            merchant_personality: 100,
+            merchant_luck: 100,
+        };
+        let query = CompletionQuery {
+            barter: greedy_merchant,
+            ..query(&ingredients[..1], CompletionRanking::Cost)
+        };
+        let potions = query.complete(&ingredients);
+        assert_eq!(
+            keys(&potions[..2]),
+            vec!["ash_salts+comberry+daedra_skin", "ash_salts+ectoplasm"]
+        );
+        assert!(potions.windows(2).all(|pair| greedy_merchant
+            .recipe_cost(&query.character, &pair[0])
+            <= greedy_merchant.recipe_cost(&query.character, &pair[1])));
+    }
+
+    #[test]
+    fn test_complete_by_side_effects() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(5),
+            ingredient(
+                "Bonemeal",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            )
+            .value(10),
+            ingredient("Comberry", &[Effect::RestoreHealth]).value(1),
+            ingredient("Daedra Skin", &[Effect::FortifyStrength]).value(1),
+            ingredient(
+                "Ectoplasm",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(2),
+        ]);
+        let potions =
+            query(&ingredients[..1], CompletionRanking::SideEffects).complete(&ingredients);
+
+        assert_eq!(
+            keys(&potions[..2]),
+            vec!["ash_salts+bonemeal", "ash_salts+bonemeal+comberry"]
+        );
+        assert!(potions.last().unwrap().effects.contains(&Effect::Poison));
+    }
+
+    #[test]
+    fn test_complete_by_value() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(5),
+            ingredient(
+                "Bonemeal",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            )
+            .value(10),
+            ingredient("Comberry", &[Effect::RestoreHealth]).value(1),
+            ingredient("Daedra Skin", &[Effect::FortifyStrength]).value(1),
+            ingredient(
+                "Ectoplasm",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(2),
+        ]);
+        let query = query(&ingredients[..1], CompletionRanking::Value);
+        let potions = query.complete(&ingredients);
+
+        let values: Vec<u32> = potions
+            .iter()
+            .map(|potion| query.engine.brew(potion, &query.character).value)
+            .collect();
+        assert!(values.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_complete_adds_at_most_the_free_slots() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(5),
+            ingredient(
+                "Bonemeal",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            )
+            .value(10),
+            ingredient("Comberry", &[Effect::RestoreHealth]).value(1),
+            ingredient("Daedra Skin", &[Effect::FortifyStrength]).value(1),
+            ingredient(
+                "Ectoplasm",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            )
+            .value(2),
+        ]);
+        let potions = query(&ingredients[1..4], CompletionRanking::Cost).complete(&ingredients);
+
+        assert_eq!(
+            keys(&potions),
+            vec![
+                "bonemeal+comberry+daedra_skin+ectoplasm",
+                "ash_salts+bonemeal+comberry+daedra_skin"
+            ]
+        );
+        // Comberry has no Fortify Strength, so one ingredient more can't be enough
+        let potions = CompletionQuery {
+            max_ingredients: 2,
+            ..query(&ingredients[2..3], CompletionRanking::Cost)
+        }
+        .complete(&ingredients);
+        assert!(potions.is_empty());
+    }
+}
+
+mod effect_matrix_tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_match_every_combination() {
+        let ingredients = bundled(&[
+            "Ash Yam",
+            "Bonemeal",
+            "Bread",
+            "Comberry",
+            "Corkbulb Root",
+            "Daedra's Heart",
+            "Diamond",
+            "Pearl",
+            "Saltrice",
+        ]);
+        for max_ingredients in 2..=4 {
+            let matrix = EffectMatrix::new(&ingredients, max_ingredients);
+            for &effect_1 in matrix.effects.iter() {
+                for &effect_2 in matrix.effects.iter() {
+                    let desired_effects = [Some(effect_1), Some(effect_2), None, None];
+                    let potential_ingredients =
+                        get_potential_ingredients(&desired_effects, &ingredients);
+                    let potions = create_potential_potions(
+                        &desired_effects,
+                        &potential_ingredients,
+                        max_ingredients,
+                    );
+                    assert_eq!(
+                        matrix.count(effect_1, effect_2),
+                        potions.len() as u64,
+                        "{} and {} with up to {} ingredients",
+                        effect_1,
+                        effect_2,
+                        max_ingredients
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_effects_and_impossible_pairs() {
+        let ingredients = shared([
+            ingredient(
+                "Ash Salts",
+                &[
+                    Effect::RestoreHealth,
+                    Effect::FortifyStrength,
+                    Effect::Poison,
+                ],
+            ),
+            ingredient(
+                "Bonemeal",
+                &[Effect::RestoreHealth, Effect::FortifyStrength],
+            ),
+            ingredient("Comberry", &[Effect::RestoreHealth]),
+            ingredient("Daedra Skin", &[Effect::FortifyStrength]),
+            ingredient("Ectoplasm", &[Effect::Poison, Effect::RestoreHealth]),
+            ingredient("Fire Salts", &[Effect::Poison]),
+            ingredient("Gravedust", &[Effect::NightEye]),
+        ]);
+        let matrix = EffectMatrix::new(&ingredients, 4);
+
+        assert_eq!(
+            matrix.effects,
+            vec![
+                Effect::FortifyStrength,
+                Effect::NightEye,
+                Effect::Poison,
+                Effect::RestoreHealth
+            ]
+        );
+        // Only Gravedust has Night Eye, and no ingredient has Paralyze
+        assert_eq!(matrix.count(Effect::NightEye, Effect::RestoreHealth), 0);
+        assert_eq!(matrix.count(Effect::NightEye, Effect::NightEye), 0);
+        assert_eq!(matrix.count(Effect::Paralyze, Effect::Poison), 0);
+        assert_eq!(
+            matrix.count(Effect::Poison, Effect::FortifyStrength),
+            matrix.count(Effect::FortifyStrength, Effect::Poison)
+        );
+        // Of two ingredients, only Ash Salts and Bonemeal share both effects
+        assert_eq!(
+            EffectMatrix::new(&ingredients[..4], 2)
+                .count(Effect::FortifyStrength, Effect::RestoreHealth),
+            1
+        );
+    }
+}
+
+mod intelligence_loop_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn intelligence_loop(
+        character: Character,
+        stock: &[(&str, u32)],
+        min_strength_gain: f32,
+    ) -> IntelligenceLoop {
+        IntelligenceLoop {
+            engine: Engine::OpenMw,
+            character,
+            stock: stock
+                .iter()
+                .map(|(id, count)| (id.to_string(), *count))
+                .collect(),
+            min_strength_gain,
+            seconds_per_potion: 0,
+        }
+    }
+
+    /// Alchemy factor 110 with a Secret Master's mortar and pestle, so potion strength 110
+    fn master() -> Character {
+        Character {
+            alchemy: 100,
+            intelligence: 50,
+            luck: 50,
+            apparatus: Apparatus {
+                mortar_and_pestle: Some(ApparatusQuality::SecretMaster),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_loop_until_out_of_ingredients() {
+        let result = intelligence_loop(master(), &[("ash_yam", 3), ("bloat", 2)], 1.0)
+            .simulate(&bundled_ingredients());
+
+        assert_eq!(result.end, IntelligenceLoopEnd::OutOfIngredients);
+        assert_eq!(
+            result
+                .steps
+                .iter()
+                .map(|step| (step.intelligence, step.magnitude))
+                .collect::<Vec<_>>(),
+            // 110 / 1.5 = 73.3, then (100 + 12.3 + 5) / 1.5 = 78.2
+            vec![(50, 73), (123, 78)]
+        );
+        assert_eq!(result.steps[0].recipe.recipe_key(), "ash_yam+bloat");
+        assert_eq!(result.final_intelligence, 201);
+        assert_eq!(
+            result.leftover,
+            BTreeMap::from([("ash_yam".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_loop_pairs_the_most_plentiful_ingredients() {
+        let result = intelligence_loop(
+            master(),
+            &[("ash_yam", 2), ("bloat", 2), ("netch_leather", 2)],
+            1.0,
+        )
+        .simulate(&bundled_ingredients());
+
+        assert_eq!(result.steps.len(), 3);
+        assert!(result.leftover.is_empty());
+        assert!(result
+            .steps
+            .windows(2)
+            .all(|steps| steps[1].strength > steps[0].strength));
+    }
+
+    #[test]
+    fn test_loop_stops_when_returns_flatten() {
+        // Potion strength 9.5 gives 6 points, which only adds 0.15 strength
+        let novice = Character {
+            alchemy: 30,
+            intelligence: 40,
+            luck: 40,
+            ..Default::default()
+        };
+
+        let result = intelligence_loop(novice, &[("ash_yam", 10), ("bloat", 10)], 0.5)
+            .simulate(&bundled_ingredients());
+
+        assert_eq!(result.end, IntelligenceLoopEnd::Flattened);
+        assert_eq!(result.steps.len(), 1);
+        assert_eq!(result.final_intelligence, 46);
+    }
+
+    #[test]
+    fn test_potions_wear_off() {
+        let stock = [("ash_yam", 10), ("bloat", 10)];
+        let first_potion = intelligence_loop(master(), &stock, 1.0)
+            .simulate(&bundled_ingredients())
+            .steps[0]
+            .clone();
+
+        let slow_loop = IntelligenceLoop {
+            seconds_per_potion: first_potion.duration,
+            ..intelligence_loop(master(), &stock, 1.0)
+        };
+        let result = slow_loop.simulate(&bundled_ingredients());
+
+        // The first potion has worn off by the time the second would be brewed
+        assert_eq!(result.end, IntelligenceLoopEnd::Flattened);
+        assert_eq!(result.steps.len(), 1);
+        assert_eq!(result.final_intelligence, 50);
+
+        let quick_loop = IntelligenceLoop {
+            seconds_per_potion: first_potion.duration - 1,
+            ..intelligence_loop(master(), &stock, 1.0)
+        };
+        assert_eq!(
+            quick_loop.simulate(&bundled_ingredients()).steps[1].intelligence,
+            123
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use eframe::egui;
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+
+/// The "#fragment" part of the page URL, including the leading '#'
+pub fn location_hash() -> Option<String> {
+    web_sys::window()?.location().hash().ok()
+}
+
+/// The full page URL without the "#fragment" part
+pub fn location_url_without_hash() -> Option<String> {
+    let href = web_sys::window()?.location().href().ok()?;
+    href.split('#').next().map(|url| url.to_string())
+}
+
+/// Change the "#fragment" part of the page URL
+/// The browser records this as a new history entry, so back/forward can step through it
+pub fn set_location_hash(hash: &str) {
+    if let Some(window) = web_sys::window() {
+        // Failing to update the URL is not worth crashing over
+        let _ = window.location().set_hash(hash);
+    }
+}
+
+/// egui only repaints on input, so ask for a repaint whenever the fragment changes underneath us,
+/// e.g. when the user presses back/forward or edits the URL
+pub fn repaint_on_hash_change(ctx: &egui::Context) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let ctx = ctx.clone();
+    let closure = Closure::<dyn FnMut()>::new(move || ctx.request_repaint());
+    let _ = window.add_event_listener_with_callback("hashchange", closure.as_ref().unchecked_ref());
+    // The listener lives for as long as the page does
+    closure.forget();
+}
+
+fn js_error(error: JsValue) -> String {
+    error.as_string().unwrap_or_else(|| format!("{:?}", error))
+}
+
+/// Offer `contents` to the user as a file download
+pub fn download_file(file_name: &str, contents: &str) -> Result<(), String> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("Unable to access the page")?;
+
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let blob = web_sys::Blob::new_with_str_sequence(&parts).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let anchor: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|_| "Unable to create a download link")?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)
+}
+
+/// Ask the user to choose a file, then call `on_load` with its name and contents once it is read
+/// Nothing is called if the user cancels the file chooser
+pub fn pick_file(
+    accept: &str,
+    on_load: impl FnOnce(Result<(String, Vec<u8>), String>) + 'static,
+) -> Result<(), String> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("Unable to access the page")?;
+
+    let input: web_sys::HtmlInputElement = document
+        .create_element("input")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|_| "Unable to create a file chooser")?;
+    input.set_type("file");
+    input.set_accept(accept);
+
+    let chosen_files = input.clone();
+    let on_change = Closure::once(move || {
+        let Some(file) = chosen_files.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        let reader = match web_sys::FileReader::new() {
+            Ok(reader) => reader,
+            Err(error) => {
+                on_load(Err(js_error(error)));
+                return;
+            }
+        };
+
+        let file_name = file.name();
+        let finished_reader = reader.clone();
+        let on_load_end = Closure::once(move || {
+            let bytes = finished_reader
+                .result()
+                .map(|buffer| js_sys::Uint8Array::new(&buffer).to_vec())
+                .map_err(js_error);
+            on_load(bytes.map(|bytes| (file_name, bytes)));
+        });
+        reader.set_onloadend(Some(on_load_end.as_ref().unchecked_ref()));
+        // Each closure runs once and then lives for as long as the page does
+        on_load_end.forget();
+
+        // Errors are reported through the load end callback
+        let _ = reader.read_as_array_buffer(&file);
+    });
+    input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+    on_change.forget();
+
+    input.click();
+    Ok(())
+}